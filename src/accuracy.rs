@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct PatternAccuracy {
+    pub pattern: String,
+    /// Times this pattern was predicted / how many of those were right.
+    pub predicted: u64,
+    pub predicted_correct: u64,
+    /// Times this pattern was the true label / how many of those were found.
+    pub actual: u64,
+    pub actual_correct: u64,
+    pub precision: Option<f64>,
+    pub recall: Option<f64>,
+}
+
+/// Rows are actual labels, columns predicted labels, both in `labels` order.
+#[derive(Debug, Serialize)]
+pub struct ConfusionMatrix {
+    pub labels: Vec<String>,
+    pub matrix: Vec<Vec<u64>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccuracyReport {
+    pub total: u64,
+    pub correct: u64,
+    pub accuracy: Option<f64>,
+    pub per_pattern: Vec<PatternAccuracy>,
+    pub confusion: ConfusionMatrix,
+}
+
+fn rate(n: u64, d: u64) -> Option<f64> {
+    (d > 0).then(|| n as f64 / d as f64)
+}
+
+impl AccuracyReport {
+    /// Builds the report from `(predicted, actual)` label pairs.
+    pub fn from_pairs<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let pairs: Vec<(&str, &str)> = pairs.into_iter().collect();
+
+        let labels: Vec<String> = pairs
+            .iter()
+            .flat_map(|(p, a)| [*p, *a])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let position: BTreeMap<&str, usize> = labels
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (l.as_str(), i))
+            .collect();
+
+        let mut matrix = vec![vec![0u64; labels.len()]; labels.len()];
+        for (predicted, actual) in &pairs {
+            matrix[position[actual]][position[predicted]] += 1;
+        }
+
+        let per_pattern = labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| {
+                let hits = matrix[i][i];
+                let predicted: u64 = matrix.iter().map(|row| row[i]).sum();
+                let actual: u64 = matrix[i].iter().sum();
+                PatternAccuracy {
+                    pattern: label.clone(),
+                    predicted,
+                    predicted_correct: hits,
+                    actual,
+                    actual_correct: hits,
+                    precision: rate(hits, predicted),
+                    recall: rate(hits, actual),
+                }
+            })
+            .collect();
+
+        let total = pairs.len() as u64;
+        let correct = pairs.iter().filter(|(p, a)| p == a).count() as u64;
+
+        AccuracyReport {
+            total,
+            correct,
+            accuracy: rate(correct, total),
+            per_pattern,
+            confusion: ConfusionMatrix { labels, matrix },
+        }
+    }
+}
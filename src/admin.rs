@@ -0,0 +1,319 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post, put},
+    Router,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::models::Pattern;
+use crate::prompts::{PromptLibrary, PromptSummary};
+use crate::taxonomy::{save_patterns, Taxonomy, TaxonomySummary};
+use crate::taxonomy_history::{self, HistoryEntry, TaxonomyDiff};
+use crate::{ApiError, AppState};
+
+pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/taxonomies/{name}/patterns", post(create_pattern))
+        .route(
+            "/taxonomies/{name}/patterns/{pattern}",
+            put(update_pattern).delete(delete_pattern),
+        )
+        .route("/taxonomies/{name}/order", put(reorder_patterns))
+        .route("/taxonomies/{name}/history", get(history))
+        .route("/taxonomies/{name}/diff", get(diff))
+        .route("/taxonomies/{name}/rollback", post(rollback))
+        .route("/prompts/reload", post(reload_prompts))
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+/// Bearer-token check against `ADMIN_TOKEN`. The admin API is disabled
+/// entirely when no token is configured.
+async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(expected) = state.config.admin_token.as_deref() else {
+        return Err((StatusCode::FORBIDDEN, "Admin API disabled: ADMIN_TOKEN not set".to_string()));
+    };
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if !provided.is_some_and(|token| tokens_match(token, expected)) {
+        warn!("Rejected admin request to {}", request.uri());
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Compares SHA-256 digests without an early exit, so response timing leaks
+/// neither the matching prefix nor the token length.
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    let (a, b) = (Sha256::digest(provided), Sha256::digest(expected));
+    a.iter().zip(b.iter()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Applies `edit` to a copy of the taxonomy's patterns, persists the result to
+/// its CSV, records a new history version and swaps it into `AppState`.
+async fn mutate(
+    state: &AppState,
+    name: &str,
+    action: &str,
+    detail: String,
+    edit: impl FnOnce(&mut Vec<Pattern>) -> Result<(), ApiError>,
+) -> Result<Json<TaxonomySummary>, ApiError> {
+    let mut taxonomies = state.taxonomies.write().await;
+    let current = taxonomies
+        .get(name)
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown taxonomy: {}", name)))?;
+
+    let mut patterns = current.patterns.clone();
+    edit(&mut patterns)?;
+    if patterns.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A taxonomy needs at least one pattern".to_string()));
+    }
+
+    let version = current.version + 1;
+    save_patterns(&current.path, &patterns).map_err(internal)?;
+    let recorded = taxonomy_history::record(
+        &state.config.taxonomy_history_dir,
+        name,
+        version,
+        action,
+        &detail,
+        &patterns,
+    );
+    if let Err(e) = recorded {
+        // Put the previous CSV back so disk, memory and history keep agreeing.
+        if let Err(restore) = save_patterns(&current.path, &current.patterns) {
+            warn!("Failed to restore {}: {}", current.path.display(), restore);
+        }
+        return Err(internal(e));
+    }
+
+    info!("Taxonomy {:?} v{}: {} ({})", name, version, action, detail);
+
+    let updated = Arc::new(Taxonomy {
+        patterns,
+        version,
+        ..(**current).clone()
+    });
+    let summary = updated.summary();
+    taxonomies.insert(name.to_string(), updated);
+
+    Ok(Json(summary))
+}
+
+fn internal(e: String) -> ApiError {
+    (StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+fn validate(pattern: &Pattern) -> Result<(), ApiError> {
+    if pattern.name.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Pattern name must not be empty".to_string()));
+    }
+    if pattern.description.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Pattern description must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+fn position_of(patterns: &[Pattern], name: &str) -> Result<usize, ApiError> {
+    patterns
+        .iter()
+        .position(|p| p.name.eq_ignore_ascii_case(name))
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown pattern: {}", name)))
+}
+
+#[derive(Deserialize)]
+struct CreatePattern {
+    #[serde(flatten)]
+    pattern: Pattern,
+    /// Zero-based insertion index; appends when omitted.
+    position: Option<usize>,
+}
+
+async fn create_pattern(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(body): Json<CreatePattern>,
+) -> Result<impl IntoResponse, ApiError> {
+    validate(&body.pattern)?;
+    let detail = format!("added {}", body.pattern.name);
+
+    let summary = mutate(&state, &name, "create", detail, |patterns| {
+        if position_of(patterns, &body.pattern.name).is_ok() {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("Pattern already exists: {}", body.pattern.name),
+            ));
+        }
+        let index = body.position.unwrap_or(patterns.len()).min(patterns.len());
+        patterns.insert(index, body.pattern);
+        Ok(())
+    })
+    .await?;
+
+    Ok((StatusCode::CREATED, summary))
+}
+
+async fn update_pattern(
+    State(state): State<Arc<AppState>>,
+    Path((name, pattern_name)): Path<(String, String)>,
+    Json(pattern): Json<Pattern>,
+) -> Result<Json<TaxonomySummary>, ApiError> {
+    validate(&pattern)?;
+    let detail = if pattern.name == pattern_name {
+        format!("updated {}", pattern_name)
+    } else {
+        format!("updated {} (renamed to {})", pattern_name, pattern.name)
+    };
+
+    mutate(&state, &name, "update", detail, |patterns| {
+        let index = position_of(patterns, &pattern_name)?;
+        if let Ok(other) = position_of(patterns, &pattern.name) {
+            if other != index {
+                return Err((
+                    StatusCode::CONFLICT,
+                    format!("Pattern already exists: {}", pattern.name),
+                ));
+            }
+        }
+        patterns[index] = pattern;
+        Ok(())
+    })
+    .await
+}
+
+async fn delete_pattern(
+    State(state): State<Arc<AppState>>,
+    Path((name, pattern_name)): Path<(String, String)>,
+) -> Result<Json<TaxonomySummary>, ApiError> {
+    let detail = format!("deleted {}", pattern_name);
+
+    mutate(&state, &name, "delete", detail, |patterns| {
+        let index = position_of(patterns, &pattern_name)?;
+        patterns.remove(index);
+        Ok(())
+    })
+    .await
+}
+
+#[derive(Deserialize)]
+struct ReorderRequest {
+    order: Vec<String>,
+}
+
+async fn reorder_patterns(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(body): Json<ReorderRequest>,
+) -> Result<Json<TaxonomySummary>, ApiError> {
+    let detail = format!("reordered {} patterns", body.order.len());
+
+    mutate(&state, &name, "reorder", detail, |patterns| {
+        if body.order.len() != patterns.len() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Order must list all {} patterns exactly once (got {})",
+                    patterns.len(),
+                    body.order.len()
+                ),
+            ));
+        }
+        let mut remaining = std::mem::take(patterns);
+        for pattern_name in &body.order {
+            let index = position_of(&remaining, pattern_name).map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown or duplicated pattern in order: {}", pattern_name),
+                )
+            })?;
+            patterns.push(remaining.remove(index));
+        }
+        Ok(())
+    })
+    .await
+}
+
+async fn history(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<HistoryEntry>>, ApiError> {
+    state.taxonomy(Some(&name)).await?;
+    let entries =
+        taxonomy_history::load_history(&state.config.taxonomy_history_dir, &name).map_err(internal)?;
+    Ok(Json(entries))
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: Option<u64>,
+    to: Option<u64>,
+}
+
+/// Diffs two recorded versions; defaults to the latest change
+/// (`from = to - 1`, `to = current`).
+async fn diff(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<TaxonomyDiff>, ApiError> {
+    let taxonomy = state.taxonomy(Some(&name)).await?;
+    let to = query.to.unwrap_or(taxonomy.version);
+    let from = query.from.unwrap_or(to.saturating_sub(1).max(1));
+
+    let history_dir = &state.config.taxonomy_history_dir;
+    let not_found = |e: String| (StatusCode::NOT_FOUND, e);
+    let old = taxonomy_history::load_version(history_dir, &name, from).map_err(not_found)?;
+    let new = taxonomy_history::load_version(history_dir, &name, to).map_err(not_found)?;
+
+    Ok(Json(taxonomy_history::diff(from, &old, to, &new)))
+}
+
+#[derive(Deserialize)]
+struct RollbackRequest {
+    version: u64,
+}
+
+/// Restores an earlier version by recording it again as the newest one, so
+/// the rollback itself stays visible (and reversible) in the history.
+async fn rollback(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(body): Json<RollbackRequest>,
+) -> Result<Json<TaxonomySummary>, ApiError> {
+    state.taxonomy(Some(&name)).await?;
+    let restored =
+        taxonomy_history::load_version(&state.config.taxonomy_history_dir, &name, body.version)
+            .map_err(|e| (StatusCode::NOT_FOUND, e))?;
+    let detail = format!("restored v{}", body.version);
+
+    mutate(&state, &name, "rollback", detail, |patterns| {
+        *patterns = restored;
+        Ok(())
+    })
+    .await
+}
+
+/// Re-reads the prompt directory so wording changes go live without a restart.
+async fn reload_prompts(State(state): State<Arc<AppState>>) -> Json<Vec<PromptSummary>> {
+    let library = PromptLibrary::load(&state.config.prompt_dir);
+    let summaries = library.summaries();
+    *state.prompts.write().await = library;
+    info!("Reloaded {} prompt templates", summaries.len());
+    Json(summaries)
+}
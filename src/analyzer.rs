@@ -1,86 +1,188 @@
+use std::fmt;
+
 use reqwest::Client;
 use tracing::info;
 
-use crate::models::{DeepSeekMessage, DeepSeekRequest, DeepSeekResponse, Pattern};
+use crate::models::{
+    Confidence, DeepSeekMessage, DeepSeekRequest, DeepSeekResponse, Direction, OccurrenceSource,
+    PatternCategory, PatternOccurrence,
+};
+use crate::i18n::{self, DEFAULT_LANG};
+use crate::prompts::{self, PromptTemplate};
+use crate::taxonomy::Taxonomy;
 
 const DEEPSEEK_URL: &str = "https://api.deepseek.com/chat/completions";
+pub const DEFAULT_MODEL: &str = "deepseek-reasoner";
+/// Reasoner models this client knows how to price.
+pub const MODELS: &[&str] = &[DEFAULT_MODEL, "deepseek-chat"];
 
 // DeepSeek Reasoner pricing (per million tokens)
-const REASONER_INPUT_PRICE: f64 = 0.55;      // $0.55/M input tokens (cache miss)
+pub const REASONER_INPUT_PRICE: f64 = 0.55;      // $0.55/M input tokens (cache miss)
 const REASONER_INPUT_CACHE_PRICE: f64 = 0.14; // $0.14/M input tokens (cache hit)
 const REASONER_OUTPUT_PRICE: f64 = 2.19;      // $2.19/M output tokens
 const REASONER_REASONING_PRICE: f64 = 2.19;   // reasoning tokens priced as output
 
+// DeepSeek Chat (V3) pricing (per million tokens)
+const CHAT_INPUT_PRICE: f64 = 0.27;           // $0.27/M input tokens (cache miss)
+const CHAT_INPUT_CACHE_PRICE: f64 = 0.07;     // $0.07/M input tokens (cache hit)
+const CHAT_OUTPUT_PRICE: f64 = 1.10;          // $1.10/M output tokens
+
+/// Per-request knobs for the reasoner stage.
+pub struct AnalyzerOptions<'a> {
+    pub model: &'a str,
+    pub lang: &'a str,
+    pub template: &'a PromptTemplate,
+    /// Rendered few-shot block for the `{{examples}}` placeholder; may be empty.
+    pub examples: &'a str,
+    /// Prior-trend line for the `{{trend_context}}` placeholder; may be empty.
+    pub trend_context: &'a str,
+    /// Ask for every pattern occurrence with its candle range, not just the best match.
+    pub occurrences: bool,
+}
+
 pub struct AnalyzerResult {
     pub pattern: String,
-    pub category: String,
-    pub direction: String,
-    pub confidence: String,
+    pub category: Option<PatternCategory>,
+    pub direction: Option<Direction>,
+    pub confidence: Confidence,
     pub reasoning: String,
+    /// Next-best taxonomy patterns, most likely first.
+    pub alternatives: Vec<String>,
+    /// Most recent candles that form the pattern, when the model said.
+    pub candle_count: Option<usize>,
+    /// Every occurrence the model listed, when asked; in its order.
+    pub occurrences: Vec<PatternOccurrence>,
     pub chain_of_thought: Option<String>,
     pub prompt_tokens: u64,
     pub completion_tokens: u64,
     pub reasoning_tokens: u64,
+    #[allow(dead_code)]
     pub cache_hit_tokens: u64,
     pub cost_usd: f64,
 }
 
-fn build_system_prompt(patterns: &[Pattern]) -> String {
-    let mut prompt = String::from(
-        "You are an expert candlestick pattern analyst. Given a text description of a candlestick chart, \
-         identify which pattern it most closely matches from the taxonomy below.\n\n\
-         PATTERN TAXONOMY:\n",
-    );
+fn format_taxonomy(taxonomy: &Taxonomy) -> String {
+    taxonomy
+        .patterns
+        .iter()
+        .map(|p| {
+            format!(
+                "- {} | Category: {} | Direction: {} | {}\n",
+                p.name, p.category, p.direction, p.description
+            )
+        })
+        .collect()
+}
 
-    for p in patterns {
-        prompt.push_str(&format!(
-            "- {} | Category: {} | Direction: {} | {}\n",
-            p.name, p.category, p.direction, p.description
-        ));
+fn language_instruction(lang: &str) -> String {
+    if lang == DEFAULT_LANG {
+        return String::new();
     }
+    format!(
+        "\nLANGUAGE:\n\
+         Write the \"reasoning\" value in {}. Keep \"pattern\" exactly as the English name \
+         from the taxonomy and keep \"category\", \"direction\" and \"confidence\" in English.\n",
+        i18n::language_name(lang)
+    )
+}
 
-    prompt.push_str(
-        "\nINSTRUCTIONS:\n\
-         1. Carefully analyze the chart description\n\
-         2. Compare against all 71 patterns in the taxonomy\n\
-         3. Identify the best matching pattern\n\
-         4. If no pattern matches well, say \"No Clear Pattern\" with explanation\n\n\
-         Respond with ONLY a JSON object (no markdown, no code fences) in this exact format:\n\
-         {\"pattern\": \"<pattern name>\", \"category\": \"<Single/Two/Three/Multi/Continuation/Special>\", \
-         \"direction\": \"<Bullish/Bearish/Neutral>\", \"confidence\": \"<High/Medium/Low>\", \
-         \"reasoning\": \"<brief explanation of why this pattern matches>\"}\n",
-    );
+const OCCURRENCES_INSTRUCTION: &str = "\nMULTIPLE PATTERNS:\n\
+     Also list every taxonomy pattern that occurs anywhere in the chart, not only the best match, \
+     as \"occurrences\": [{\"pattern\": \"<pattern name>\", \"start\": <index of its first candle>, \
+     \"end\": <index of its last candle>, \"confidence\": \"<High/Medium/Low>\"}], numbering candles \
+     from 0 at the left.\n";
+
+/// Why a reasoner call failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The request, the API or its response envelope failed.
+    Request,
+    /// The model answered, but not with the pattern JSON asked for.
+    Parse,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalyzerError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for AnalyzerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for AnalyzerError {
+    fn from(message: String) -> Self {
+        AnalyzerError { kind: ErrorKind::Request, message }
+    }
+}
 
-    prompt
+/// (input cache miss, input cache hit, output, reasoning) USD per million tokens.
+fn pricing(model: &str) -> (f64, f64, f64, f64) {
+    match model {
+        "deepseek-chat" => (
+            CHAT_INPUT_PRICE,
+            CHAT_INPUT_CACHE_PRICE,
+            CHAT_OUTPUT_PRICE,
+            CHAT_OUTPUT_PRICE,
+        ),
+        _ => (
+            REASONER_INPUT_PRICE,
+            REASONER_INPUT_CACHE_PRICE,
+            REASONER_OUTPUT_PRICE,
+            REASONER_REASONING_PRICE,
+        ),
+    }
 }
 
 pub async fn analyze_pattern(
     client: &Client,
     api_key: &str,
     chart_description: &str,
-    patterns: &[Pattern],
-) -> Result<AnalyzerResult, String> {
-    let system_prompt = build_system_prompt(patterns);
+    taxonomy: &Taxonomy,
+    options: &AnalyzerOptions<'_>,
+) -> Result<AnalyzerResult, AnalyzerError> {
+    let AnalyzerOptions {
+        model,
+        lang,
+        template,
+        examples,
+        trend_context,
+        occurrences,
+    } = *options;
+    let taxonomy_text = format_taxonomy(taxonomy);
+    let pattern_count = taxonomy.patterns.len().to_string();
+    let language_instruction = language_instruction(lang);
+    let values = [
+        ("taxonomy", taxonomy_text.as_str()),
+        ("taxonomy_name", taxonomy.name.as_str()),
+        ("pattern_count", pattern_count.as_str()),
+        ("chart_description", chart_description),
+        ("examples", examples),
+        ("trend_context", trend_context),
+        ("occurrences_instruction", if occurrences { OCCURRENCES_INSTRUCTION } else { "" }),
+        ("language", i18n::language_name(lang)),
+        ("language_instruction", language_instruction.as_str()),
+    ];
 
     let request = DeepSeekRequest {
-        model: "deepseek-reasoner".to_string(),
+        model: model.to_string(),
         messages: vec![
             DeepSeekMessage {
                 role: "system".to_string(),
-                content: system_prompt,
+                content: prompts::render(&template.system, &values),
             },
             DeepSeekMessage {
                 role: "user".to_string(),
-                content: format!(
-                    "Analyze this candlestick chart description and identify the pattern:\n\n{}",
-                    chart_description
-                ),
+                content: prompts::render(&template.user, &values),
             },
         ],
         stream: false,
     };
 
-    info!("Sending chart description to DeepSeek Reasoner...");
+    info!("Sending chart description to {}...", model);
 
     let resp = client
         .post(DEEPSEEK_URL)
@@ -98,7 +200,7 @@ pub async fn analyze_pattern(
         .map_err(|e| format!("Failed to read DeepSeek response: {}", e))?;
 
     if !status.is_success() {
-        return Err(format!("DeepSeek API error ({}): {}", status, body));
+        return Err(format!("DeepSeek API error ({}): {}", status, body).into());
     }
 
     let ds_resp: DeepSeekResponse = serde_json::from_str(&body)
@@ -107,7 +209,7 @@ pub async fn analyze_pattern(
     let choice = ds_resp
         .choices
         .first()
-        .ok_or("DeepSeek returned no choices")?;
+        .ok_or_else(|| "DeepSeek returned no choices".to_string())?;
 
     let content = &choice.message.content;
     let chain_of_thought = choice.message.reasoning_content.clone();
@@ -127,10 +229,11 @@ pub async fn analyze_pattern(
 
     // Calculate cost
     let cache_miss_tokens = prompt_tokens.saturating_sub(cache_hit_tokens);
-    let cost_usd = (cache_miss_tokens as f64 / 1_000_000.0) * REASONER_INPUT_PRICE
-        + (cache_hit_tokens as f64 / 1_000_000.0) * REASONER_INPUT_CACHE_PRICE
-        + (completion_tokens as f64 / 1_000_000.0) * REASONER_OUTPUT_PRICE
-        + (reasoning_tokens as f64 / 1_000_000.0) * REASONER_REASONING_PRICE;
+    let (input_price, cache_price, output_price, reasoning_price) = pricing(model);
+    let cost_usd = (cache_miss_tokens as f64 / 1_000_000.0) * input_price
+        + (cache_hit_tokens as f64 / 1_000_000.0) * cache_price
+        + (completion_tokens as f64 / 1_000_000.0) * output_price
+        + (reasoning_tokens as f64 / 1_000_000.0) * reasoning_price;
 
     info!(
         "DeepSeek usage: {} prompt ({} cached), {} completion, {} reasoning — ${:.6}",
@@ -148,34 +251,86 @@ pub async fn analyze_pattern(
         .unwrap_or(content.trim())
         .trim();
 
-    let parsed: serde_json::Value = serde_json::from_str(json_str).map_err(|e| {
-        format!(
-            "Failed to parse pattern JSON from DeepSeek: {} — content: {}",
-            e, content
-        )
+    let parsed: serde_json::Value = serde_json::from_str(json_str).map_err(|e| AnalyzerError {
+        kind: ErrorKind::Parse,
+        message: format!("Failed to parse pattern JSON from DeepSeek: {} — content: {}", e, content),
     })?;
 
+    let pattern = parsed["pattern"]
+        .as_str()
+        .unwrap_or("Unknown")
+        .trim()
+        .to_string();
+
+    // Category and direction come from the taxonomy when the reasoner named a
+    // known pattern; otherwise fall back to whatever it reported, leniently parsed.
+    let known = taxonomy.find(&pattern);
+
+    let category = match known {
+        Some(p) => Some(p.category),
+        None => parsed["category"].as_str().and_then(|s| s.parse().ok()),
+    };
+    let direction = match known {
+        Some(p) => Some(p.direction),
+        None => parsed["direction"].as_str().and_then(|s| s.parse().ok()),
+    };
+    let confidence = parsed["confidence"]
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(Confidence::Low);
+
+    // Only alternatives that name real taxonomy entries are useful downstream.
+    let alternatives: Vec<String> = parsed["alternatives"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|v| v.as_str())
+                .filter_map(|name| taxonomy.find(name.trim()))
+                .map(|p| p.name.clone())
+                .filter(|name| Some(name) != known.map(|k| &k.name))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let occurrences = parsed["occurrences"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let pattern = taxonomy.find(item["pattern"].as_str()?.trim())?;
+                    let start = item["start"].as_u64()? as usize;
+                    let end = item["end"].as_u64()? as usize;
+                    (start <= end).then(|| PatternOccurrence {
+                        pattern: pattern.name.clone(),
+                        direction: Some(pattern.direction),
+                        start,
+                        end,
+                        confidence: item["confidence"].as_str().and_then(|s| s.parse().ok()),
+                        sources: vec![OccurrenceSource::Reasoner],
+                        actionable: false,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
     Ok(AnalyzerResult {
-        pattern: parsed["pattern"]
-            .as_str()
-            .unwrap_or("Unknown")
-            .to_string(),
-        category: parsed["category"]
-            .as_str()
-            .unwrap_or("Unknown")
-            .to_string(),
-        direction: parsed["direction"]
-            .as_str()
-            .unwrap_or("Unknown")
-            .to_string(),
-        confidence: parsed["confidence"]
-            .as_str()
-            .unwrap_or("Unknown")
-            .to_string(),
+        pattern: known.map(|p| p.name.clone()).unwrap_or(pattern),
+        category,
+        direction,
+        confidence,
         reasoning: parsed["reasoning"]
             .as_str()
             .unwrap_or("No reasoning provided")
             .to_string(),
+        alternatives,
+        candle_count: parsed["candle_count"]
+            .as_u64()
+            .filter(|&n| n > 0)
+            .map(|n| n as usize),
+        occurrences,
         chain_of_thought,
         prompt_tokens,
         completion_tokens,
@@ -0,0 +1,165 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgb, RgbImage};
+
+use crate::extract;
+use crate::models::Direction;
+
+fn direction_color(direction: Option<Direction>) -> Rgb<u8> {
+    match direction {
+        Some(Direction::Bullish | Direction::BullishContinuation) => Rgb([0, 170, 60]),
+        Some(Direction::Bearish | Direction::BearishContinuation) => Rgb([220, 30, 30]),
+        _ => Rgb([30, 110, 230]),
+    }
+}
+
+fn fill(img: &mut RgbImage, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgb<u8>) {
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    for y in y0.max(0)..=y1.min(h - 1) {
+        for x in x0.max(0)..=x1.min(w - 1) {
+            img.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+/// 5x7 bitmap glyphs, one byte per row with the low five bits set.
+fn glyph(ch: char) -> [u8; 7] {
+    match ch.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '\'' => [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '&' => [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D],
+        _ => [0; 7],
+    }
+}
+
+const SCALE: i64 = 2;
+const GLYPH_ADVANCE: i64 = 6 * SCALE;
+const LABEL_HEIGHT: i64 = 7 * SCALE + 8;
+const BOX_PADDING: i64 = 6;
+const BORDER: i64 = 3;
+
+fn draw_text(img: &mut RgbImage, x: i64, y: i64, text: &str, color: Rgb<u8>) {
+    for (i, ch) in text.chars().enumerate() {
+        let gx = x + i as i64 * GLYPH_ADVANCE;
+        for (row, bits) in glyph(ch).iter().enumerate() {
+            for col in 0..5 {
+                if bits & (0x10 >> col) != 0 {
+                    let px = gx + col * SCALE;
+                    let py = y + row as i64 * SCALE;
+                    fill(img, px, py, px + SCALE - 1, py + SCALE - 1, color);
+                }
+            }
+        }
+    }
+}
+
+/// Which of the candles found in the chart to box.
+#[derive(Debug, Clone, Copy)]
+pub enum Span {
+    /// The last `n` candles.
+    Last(usize),
+    /// Inclusive candle indexes, 0 = leftmost candle.
+    Range(usize, usize),
+}
+
+/// Boxes the `span` candles found in the chart and labels the box with the
+/// pattern name in the direction's colour. Returns a PNG.
+pub fn annotate(
+    image_bytes: &[u8],
+    pattern: &str,
+    direction: Option<Direction>,
+    span: Span,
+) -> Result<Vec<u8>, String> {
+    let mut img = image::load_from_memory(image_bytes)
+        .map_err(|e| format!("Failed to decode chart image: {}", e))?
+        .to_rgb8();
+
+    let candles = extract::find_candles(&img);
+    if candles.is_empty() {
+        return Err("No candles found in the chart image".to_string());
+    }
+    let selected = match span {
+        Span::Last(n) => &candles[candles.len().saturating_sub(n.max(1))..],
+        Span::Range(start, end) if start <= end && end < candles.len() => &candles[start..=end],
+        Span::Range(start, end) => {
+            return Err(format!(
+                "Candles {}-{} are outside the {} candles found in the chart",
+                start,
+                end,
+                candles.len()
+            ))
+        }
+    };
+
+    let left = selected.iter().map(|c| c.left).min().unwrap_or(0) as i64 - BOX_PADDING;
+    let right = selected.iter().map(|c| c.right).max().unwrap_or(0) as i64 + BOX_PADDING;
+    let top = selected.iter().map(|c| c.top).min().unwrap_or(0) as i64 - BOX_PADDING;
+    let bottom = selected.iter().map(|c| c.bottom).max().unwrap_or(0) as i64 + BOX_PADDING;
+
+    let color = direction_color(direction);
+    fill(&mut img, left, top, right, top + BORDER - 1, color);
+    fill(&mut img, left, bottom - BORDER + 1, right, bottom, color);
+    fill(&mut img, left, top, left + BORDER - 1, bottom, color);
+    fill(&mut img, right - BORDER + 1, top, right, bottom, color);
+
+    // Label above the box, or below it when the box touches the top edge;
+    // shifted left so it stays inside the image.
+    let label = pattern.to_uppercase();
+    let label_width = label.chars().count() as i64 * GLYPH_ADVANCE + 8;
+    let label_top = if top - LABEL_HEIGHT >= 0 { top - LABEL_HEIGHT } else { bottom + 1 };
+    let label_left = left.min(img.width() as i64 - label_width).max(0);
+    fill(
+        &mut img,
+        label_left,
+        label_top,
+        label_left + label_width - 1,
+        label_top + LABEL_HEIGHT - 1,
+        color,
+    );
+    draw_text(&mut img, label_left + 4, label_top + 4, &label, Rgb([255, 255, 255]));
+
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(png)
+}
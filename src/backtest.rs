@@ -0,0 +1,515 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::candles::{self, Candle};
+use crate::import::OhlcInput;
+use crate::models::{Direction, Pattern};
+use crate::scan::{self, InputArgs, TimelineEntry};
+use crate::taxonomy;
+use crate::{ApiError, AppState};
+
+const USAGE: &str = concat!(
+    "\
+Usage: deepseek-test backtest <ohlc>... [options]
+
+Scans OHLC history for patterns like `scan` and measures what price did
+after each detection: hit rate against the pattern's direction, average
+move, drawdown and significance against the series' base rate, per
+pattern and horizon. A detection within a horizon of the previous one
+measured for the same pattern is left out of that horizon, so the
+samples do not overlap. Writes the report as JSON.
+
+Options:
+",
+    scan::input_options_help!(),
+    "
+  --horizons <a,b,...>   bars after the pattern to measure (default 1,3,5,10,20)
+  --alpha <p>            significance level (default 0.05)"
+);
+
+const DEFAULT_HORIZONS: [usize; 5] = [1, 3, 5, 10, 20];
+const DEFAULT_ALPHA: f64 = 0.05;
+const MAX_HORIZON: usize = 500;
+/// Fewer samples than this are never called significant.
+const MIN_SAMPLES: usize = 10;
+
+/// Forward performance of one pattern at one horizon. Returns are fractions
+/// of the close of the bar completing the pattern.
+#[derive(Debug, Serialize)]
+pub struct HorizonStats {
+    pub horizon: usize,
+    /// Detections with `horizon` bars of history after them, skipping any that
+    /// complete within the previous sample's window so samples are independent.
+    pub samples: usize,
+    /// Share of samples that moved the way the pattern points; not for patterns
+    /// without a direction.
+    pub hit_rate: Option<f64>,
+    /// Share of all bars in the series that moved that way over the same horizon.
+    pub baseline_hit_rate: Option<f64>,
+    pub average_return: f64,
+    /// Average return signed by the pattern's direction, so positive means it worked.
+    pub average_move: Option<f64>,
+    /// Worst adverse excursion before the horizon, averaged and at its worst.
+    pub average_drawdown: Option<f64>,
+    pub max_drawdown: Option<f64>,
+    /// t statistic of the directional moves against zero.
+    pub t_stat: Option<f64>,
+    /// Two-sided p-value of the hit rate against the baseline.
+    pub p_value: Option<f64>,
+    pub significant: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatternBacktest {
+    pub pattern: String,
+    pub direction: Direction,
+    pub detections: usize,
+    pub horizons: Vec<HorizonStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BacktestReport {
+    pub series: usize,
+    pub bars: usize,
+    pub detections: usize,
+    pub horizons: Vec<usize>,
+    pub alpha: f64,
+    /// Most detections first.
+    pub patterns: Vec<PatternBacktest>,
+}
+
+/// One detection's outcome at one horizon.
+struct Outcome {
+    ret: f64,
+    drawdown: Option<f64>,
+}
+
+struct PatternOutcomes {
+    direction: Direction,
+    detections: usize,
+    /// Per horizon, in `horizons` order.
+    outcomes: Vec<Vec<Outcome>>,
+}
+
+/// Accumulates outcomes over any number of series, then reports.
+pub struct Backtest {
+    horizons: Vec<usize>,
+    series: usize,
+    bars: usize,
+    /// Per horizon: bars that rose, bars that fell, bars with a forward close.
+    baseline: Vec<(usize, usize, usize)>,
+    patterns: BTreeMap<String, PatternOutcomes>,
+}
+
+/// Complementary error function (Numerical Recipes' Chebyshev fit, error < 1.2e-7).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+        .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// Two-sided p-value of `hits` out of `n` against success rate `p0`, by the
+/// normal approximation to the binomial.
+fn binomial_p_value(hits: usize, n: usize, p0: f64) -> Option<f64> {
+    let variance = n as f64 * p0 * (1.0 - p0);
+    if n == 0 || variance <= 0.0 {
+        return None;
+    }
+    let z = (hits as f64 - n as f64 * p0) / variance.sqrt();
+    Some(erfc(z.abs() / std::f64::consts::SQRT_2))
+}
+
+fn t_stat(values: &[f64]) -> Option<f64> {
+    let n = values.len();
+    if n < 2 {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+    (variance > 0.0).then(|| mean / (variance / n as f64).sqrt())
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, n) = values.fold((0.0, 0), |(sum, n), v| (sum + v, n + 1));
+    (n > 0).then(|| sum / n as f64)
+}
+
+fn round(value: f64) -> f64 {
+    (value * 1e6).round() / 1e6
+}
+
+pub fn validate_horizons(horizons: &[usize]) -> Result<(), String> {
+    if horizons.is_empty() {
+        return Err("At least one horizon is required".to_string());
+    }
+    match horizons.iter().find(|&&h| h == 0 || h > MAX_HORIZON) {
+        Some(h) => Err(format!("Horizons must be between 1 and {}, got {}", MAX_HORIZON, h)),
+        None => Ok(()),
+    }
+}
+
+impl Backtest {
+    pub fn new(horizons: Vec<usize>) -> Self {
+        Backtest {
+            baseline: vec![(0, 0, 0); horizons.len()],
+            horizons,
+            series: 0,
+            bars: 0,
+            patterns: BTreeMap::new(),
+        }
+    }
+
+    /// Adds one series and the detections `scan::timeline` found in it, in
+    /// order of their completing bar.
+    pub fn add(&mut self, candles: &[Candle], entries: &[TimelineEntry]) {
+        self.series += 1;
+        self.bars += candles.len();
+        for (i, &horizon) in self.horizons.iter().enumerate() {
+            for (entry, exit) in candles.iter().zip(candles.iter().skip(horizon)) {
+                let baseline = &mut self.baseline[i];
+                baseline.0 += usize::from(exit.close > entry.close);
+                baseline.1 += usize::from(exit.close < entry.close);
+                baseline.2 += 1;
+            }
+        }
+
+        // Per pattern and horizon, the first bar a new sample may complete on
+        let mut next_free: HashMap<&str, Vec<usize>> = HashMap::new();
+        for entry in entries {
+            let next_free = next_free
+                .entry(entry.pattern.as_str())
+                .or_insert_with(|| vec![0; self.horizons.len()]);
+            let outcomes = self
+                .patterns
+                .entry(entry.pattern.clone())
+                .or_insert_with(|| PatternOutcomes {
+                    direction: entry.direction,
+                    detections: 0,
+                    outcomes: self.horizons.iter().map(|_| Vec::new()).collect(),
+                });
+            outcomes.detections += 1;
+            let price = candles[entry.end].close;
+            for (i, &horizon) in self.horizons.iter().enumerate() {
+                if entry.end < next_free[i] {
+                    continue;
+                }
+                let Some(exit) = candles.get(entry.end + horizon) else {
+                    continue;
+                };
+                next_free[i] = entry.end + horizon;
+                let path = &candles[entry.end + 1..=entry.end + horizon];
+                let drawdown = match entry.direction.sign() {
+                    Some(s) if s > 0.0 => path.iter().map(|c| c.low).reduce(f64::min).map(|low| low / price - 1.0),
+                    Some(_) => path.iter().map(|c| c.high).reduce(f64::max).map(|high| 1.0 - high / price),
+                    None => None,
+                };
+                outcomes.outcomes[i].push(Outcome {
+                    ret: exit.close / price - 1.0,
+                    drawdown: drawdown.map(|d| d.min(0.0)),
+                });
+            }
+        }
+    }
+
+    fn horizon_stats(&self, i: usize, direction: Direction, outcomes: &[Outcome], alpha: f64) -> HorizonStats {
+        let samples = outcomes.len();
+        let average_return = mean(outcomes.iter().map(|o| o.ret)).unwrap_or(0.0);
+        let (rose, fell, total) = self.baseline[i];
+        let Some(sign) = direction.sign() else {
+            return HorizonStats {
+                horizon: self.horizons[i],
+                samples,
+                hit_rate: None,
+                baseline_hit_rate: None,
+                average_return: round(average_return),
+                average_move: None,
+                average_drawdown: None,
+                max_drawdown: None,
+                t_stat: None,
+                p_value: None,
+                significant: false,
+            };
+        };
+
+        let moves: Vec<f64> = outcomes.iter().map(|o| o.ret * sign).collect();
+        let hits = moves.iter().filter(|&&m| m > 0.0).count();
+        let baseline = (total > 0).then(|| if sign > 0.0 { rose } else { fell } as f64 / total as f64);
+        let p_value = baseline.and_then(|p0| binomial_p_value(hits, samples, p0));
+        let drawdowns = || outcomes.iter().filter_map(|o| o.drawdown);
+        HorizonStats {
+            horizon: self.horizons[i],
+            samples,
+            hit_rate: (samples > 0).then(|| round(hits as f64 / samples as f64)),
+            baseline_hit_rate: baseline.map(round),
+            average_return: round(average_return),
+            average_move: mean(moves.iter().copied()).map(round),
+            average_drawdown: mean(drawdowns()).map(round),
+            max_drawdown: drawdowns().reduce(f64::min).map(round),
+            t_stat: t_stat(&moves).map(round),
+            p_value: p_value.map(round),
+            significant: samples >= MIN_SAMPLES && p_value.is_some_and(|p| p < alpha),
+        }
+    }
+
+    pub fn report(&self, alpha: f64) -> BacktestReport {
+        let mut patterns: Vec<PatternBacktest> = self
+            .patterns
+            .iter()
+            .map(|(pattern, outcomes)| PatternBacktest {
+                pattern: pattern.clone(),
+                direction: outcomes.direction,
+                detections: outcomes.detections,
+                horizons: outcomes
+                    .outcomes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, o)| self.horizon_stats(i, outcomes.direction, o, alpha))
+                    .collect(),
+            })
+            .collect();
+        patterns.sort_by_key(|p| std::cmp::Reverse(p.detections));
+        BacktestReport {
+            series: self.series,
+            bars: self.bars,
+            detections: patterns.iter().map(|p| p.detections).sum(),
+            horizons: self.horizons.clone(),
+            alpha,
+            patterns,
+        }
+    }
+}
+
+fn parse_list<T: std::str::FromStr>(value: &str, what: &str) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(|v| v.trim().parse().map_err(|_| format!("{} expects numbers, got {}", what, v)))
+        .collect()
+}
+
+/// Parses the shared scan options plus `--horizons` and `--alpha`.
+fn parse_args(args: &[String]) -> Result<(InputArgs, Vec<usize>, f64), String> {
+    let mut horizons = DEFAULT_HORIZONS.to_vec();
+    let mut alpha = DEFAULT_ALPHA;
+    let parsed = scan::parse_args(args, USAGE, |option, value| {
+        match option {
+            "--horizons" => horizons = parse_list(value, option)?,
+            "--alpha" => {
+                alpha = value
+                    .parse()
+                    .ok()
+                    .filter(|a| *a > 0.0 && *a < 1.0)
+                    .ok_or_else(|| format!("--alpha expects a number between 0 and 1, got {}", value))?
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })?;
+    validate_horizons(&horizons)?;
+    Ok((parsed, horizons, alpha))
+}
+
+fn log_report(report: &BacktestReport) {
+    for pattern in &report.patterns {
+        let summary: Vec<String> = pattern
+            .horizons
+            .iter()
+            .map(|h| match (h.hit_rate, h.average_move) {
+                (Some(hit_rate), Some(average_move)) => format!(
+                    "{}: {:.0}% hit, {:+.2}%{}",
+                    h.horizon,
+                    hit_rate * 100.0,
+                    average_move * 100.0,
+                    if h.significant { " *" } else { "" }
+                ),
+                _ => format!("{}: {:+.2}%", h.horizon, h.average_return * 100.0),
+            })
+            .collect();
+        info!("  {} ({}): {}", pattern.pattern, pattern.detections, summary.join(" | "));
+    }
+    info!(
+        "Backtested {} detections over {} bars in {} series",
+        report.detections, report.bars, report.series
+    );
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (args, horizons, alpha) = parse_args(args)?;
+    let patterns = taxonomy::load_patterns(taxonomy::csv_path(&args.taxonomy))?;
+    let patterns = scan::select_patterns(patterns, &args.taxonomy, args.patterns.as_deref())?;
+
+    let mut backtest = Backtest::new(horizons);
+    for path in &args.inputs {
+        let candles = candles::load_file(path, &args.import)?;
+        let entries = scan::timeline(&path.display().to_string(), &candles, &patterns);
+        backtest.add(&candles, &entries);
+    }
+    let report = backtest.report(alpha);
+    log_report(&report);
+
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    match &args.out {
+        Some(path) => std::fs::write(path, json)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e)),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BacktestRequest {
+    #[serde(flatten)]
+    ohlc: OhlcInput,
+    taxonomy: Option<String>,
+    patterns: Option<Vec<String>>,
+    horizons: Option<Vec<usize>>,
+    alpha: Option<f64>,
+}
+
+async fn backtest_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BacktestRequest>,
+) -> Result<Json<BacktestReport>, ApiError> {
+    let bad_request = |e: String| (StatusCode::BAD_REQUEST, e);
+    let candles = request.ohlc.into_candles().map_err(bad_request)?;
+    let horizons = request.horizons.unwrap_or_else(|| DEFAULT_HORIZONS.to_vec());
+    validate_horizons(&horizons).map_err(bad_request)?;
+    let alpha = request.alpha.unwrap_or(DEFAULT_ALPHA);
+    if !(alpha > 0.0 && alpha < 1.0) {
+        return Err(bad_request(format!("alpha must be between 0 and 1, got {}", alpha)));
+    }
+
+    let taxonomy = state.taxonomy(request.taxonomy.as_deref()).await?;
+    let patterns: Vec<Pattern> = scan::select_patterns(
+        taxonomy.patterns.clone(),
+        &taxonomy.name,
+        request.patterns.as_deref(),
+    )
+    .map_err(bad_request)?;
+
+    let entries = scan::timeline("request", &candles, &patterns);
+    let mut backtest = Backtest::new(horizons);
+    backtest.add(&candles, &entries);
+    Ok(Json(backtest.report(alpha)))
+}
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new().route("/backtest", post(backtest_handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PatternCategory;
+
+    fn close_to(actual: Option<f64>, expected: f64) -> bool {
+        actual.is_some_and(|a| (a - expected).abs() < 1e-6)
+    }
+
+    #[test]
+    fn erfc_matches_tabulated_values() {
+        for (x, expected) in [(0.0, 1.0), (0.5, 0.4795001), (1.0, 0.1572992), (2.0, 0.0046777), (-1.0, 1.8427008)] {
+            assert!((erfc(x) - expected).abs() < 1e-6, "erfc({}) = {}", x, erfc(x));
+        }
+    }
+
+    #[test]
+    fn binomial_p_value_is_two_sided() {
+        assert!(close_to(binomial_p_value(50, 100, 0.5), 1.0));
+        // z = ±2 either way: p = erfc(√2) ≈ 0.0455
+        assert!(close_to(binomial_p_value(60, 100, 0.5), 0.0455003));
+        assert!(close_to(binomial_p_value(40, 100, 0.5), 0.0455003));
+        assert_eq!(binomial_p_value(0, 0, 0.5), None);
+        assert_eq!(binomial_p_value(5, 10, 1.0), None);
+    }
+
+    #[test]
+    fn t_stat_needs_spread() {
+        // mean 2, sample variance 1: 2 / sqrt(1/3)
+        assert!(close_to(t_stat(&[1.0, 2.0, 3.0]), 3.4641016));
+        assert_eq!(t_stat(&[1.0, 1.0, 1.0]), None);
+        assert_eq!(t_stat(&[1.0]), None);
+    }
+
+    fn series(closes: &[f64]) -> Vec<Candle> {
+        closes
+            .iter()
+            .map(|&close| Candle { time: None, open: close, high: close + 1.0, low: close - 2.0, close, volume: None })
+            .collect()
+    }
+
+    fn detection(pattern: &str, direction: Direction, end: usize, candles: &[Candle]) -> TimelineEntry {
+        TimelineEntry {
+            series: "test".to_string(),
+            pattern: pattern.to_string(),
+            category: PatternCategory::Single,
+            direction,
+            start: end,
+            end,
+            start_time: None,
+            end_time: None,
+            close: candles[end].close,
+        }
+    }
+
+    #[test]
+    fn add_measures_moves_drawdowns_and_the_baseline() {
+        let candles = series(&[10.0, 11.0, 12.0, 11.0, 13.0, 12.0]);
+        let entries = [
+            detection("Hammer", Direction::Bullish, 0, &candles),
+            detection("Hammer", Direction::Bullish, 1, &candles),
+            detection("Hanging Man", Direction::Bearish, 2, &candles),
+            detection("Hammer", Direction::Bullish, 3, &candles),
+        ];
+        let mut backtest = Backtest::new(vec![1, 2]);
+        backtest.add(&candles, &entries);
+        // 1 bar: up, up, down, up, down; 2 bars: up, flat, up, up
+        assert_eq!(backtest.baseline, [(3, 2, 5), (3, 0, 4)]);
+
+        let report = backtest.report(0.05);
+        assert_eq!((report.series, report.bars, report.detections), (1, 6, 4));
+        let hammer = &report.patterns[0];
+        assert_eq!((hammer.pattern.as_str(), hammer.detections), ("Hammer", 3));
+
+        let one = &hammer.horizons[0];
+        assert_eq!(one.samples, 3);
+        assert!(close_to(one.hit_rate, 1.0));
+        assert!(close_to(one.baseline_hit_rate, 0.6));
+        assert!(close_to(one.average_move, (0.1 + 1.0 / 11.0 + 2.0 / 11.0) / 3.0));
+        // Bullish drawdown is the lowest low below entry: 9/10 and 10/11; the
+        // third never trades below its entry
+        assert!(close_to(one.average_drawdown, (-0.1 - 1.0 / 11.0) / 3.0));
+        assert!(close_to(one.max_drawdown, -0.1));
+
+        // The detection on bar 1 completes inside bar 0's two-bar window
+        let two = &hammer.horizons[1];
+        assert_eq!(two.samples, 2);
+        assert!(close_to(two.baseline_hit_rate, 0.75));
+        assert!(close_to(two.average_move, (0.2 + 1.0 / 11.0) / 2.0));
+
+        // Bearish moves count falls as positive and drawdown against the highs
+        let hanging_man = &report.patterns[1];
+        let one = &hanging_man.horizons[0];
+        assert!(close_to(one.average_move, 1.0 / 12.0));
+        assert!(close_to(one.baseline_hit_rate, 0.4));
+        assert!(close_to(one.max_drawdown, 0.0));
+        assert!(close_to(hanging_man.horizons[1].max_drawdown, 1.0 - 14.0 / 12.0));
+    }
+}
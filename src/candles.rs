@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::import::{self, ImportOptions};
+use crate::prefilter::Trend;
+use crate::trend;
+
+/// One OHLC bar. `time` is a unix timestamp in seconds when known.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    #[serde(default)]
+    pub time: Option<i64>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    #[serde(default)]
+    pub volume: Option<f64>,
+}
+
+impl Candle {
+    pub fn is_bullish(&self) -> bool {
+        self.close >= self.open
+    }
+
+    pub fn body(&self) -> f64 {
+        (self.close - self.open).abs()
+    }
+
+    pub fn range(&self) -> f64 {
+        self.high - self.low
+    }
+
+    pub fn body_top(&self) -> f64 {
+        self.open.max(self.close)
+    }
+
+    pub fn body_bottom(&self) -> f64 {
+        self.open.min(self.close)
+    }
+
+    pub fn upper_wick(&self) -> f64 {
+        self.high - self.body_top()
+    }
+
+    pub fn lower_wick(&self) -> f64 {
+        self.body_bottom() - self.low
+    }
+}
+
+/// Rejects empty series and bars whose high/low do not bound open and close.
+pub fn validate(candles: &[Candle]) -> Result<(), String> {
+    if candles.is_empty() {
+        return Err("No candles".to_string());
+    }
+    for (i, c) in candles.iter().enumerate() {
+        let values = [c.open, c.high, c.low, c.close];
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(format!("Candle {} has a non-finite price", i));
+        }
+        if c.high < c.open.max(c.close) || c.low > c.open.min(c.close) {
+            return Err(format!(
+                "Candle {} is inconsistent: high {} / low {} must bound open {} and close {}",
+                i, c.high, c.low, c.open, c.close
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a JSON array of candles, or one candle object per line.
+pub fn parse_json(text: &str) -> Result<Vec<Candle>, String> {
+    let candles = if text.trim_start().starts_with('[') {
+        serde_json::from_str(text).map_err(|e| format!("Invalid OHLC JSON: {}", e))?
+    } else {
+        text.lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(n, l)| {
+                serde_json::from_str(l).map_err(|e| format!("Invalid OHLC line {}: {}", n + 1, e))
+            })
+            .collect::<Result<Vec<Candle>, String>>()?
+    };
+    validate(&candles)?;
+    Ok(candles)
+}
+
+/// Loads `.json`/`.jsonl` files as JSON and anything else as a CSV export.
+pub fn load_file(path: &Path, options: &ImportOptions) -> Result<Vec<Candle>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let candles = if matches!(extension, "json" | "jsonl") {
+        parse_json(&text)
+    } else {
+        import::parse(&text, options).map(|imported| imported.candles)
+    };
+    candles.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Writes candles as `time,open,high,low,close,volume` CSV.
+pub fn save_csv(path: &Path, candles: &[Candle]) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    for candle in candles {
+        writer
+            .serialize(candle)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn wick(length: f64, candle: &Candle) -> String {
+    let body = candle.body();
+    if length < candle.range() * 0.05 {
+        "no".to_string()
+    } else if body > 0.0 && length >= body * 2.0 {
+        format!("a long ({:.1}x body)", length / body)
+    } else if length >= candle.range() * 0.3 {
+        "a long".to_string()
+    } else {
+        "a short".to_string()
+    }
+}
+
+/// Plain-text chart description in the vocabulary the vision model uses, so
+/// extracted candles can go through the same reasoner prompt and prefilter.
+pub fn describe(candles: &[Candle]) -> String {
+    let n = candles.len();
+    let recent = n.min(5);
+    let mut text = format!("The chart shows {} candles.", n);
+    text.push_str(match trend::classify(&candles[..n - recent]) {
+        Some(Trend::Up) => " Before the most recent candles price is in an uptrend.",
+        Some(Trend::Down) => " Before the most recent candles price is in a downtrend.",
+        Some(Trend::Sideways) => " Before the most recent candles price moves sideways.",
+        None => "",
+    });
+
+    let average_body = candles.iter().map(Candle::body).sum::<f64>() / n.max(1) as f64;
+    for i in n - recent..n {
+        let c = &candles[i];
+        let kind = if c.body() <= c.range() * 0.1 {
+            "doji"
+        } else if c.is_bullish() {
+            "bullish (green)"
+        } else {
+            "bearish (red)"
+        };
+        let size = if c.body() > average_body * 1.5 {
+            "long"
+        } else if c.body() < average_body * 0.5 {
+            "small"
+        } else {
+            "medium"
+        };
+        text.push_str(&format!(
+            " Candle {} of the last {}: {} {} body with {} upper wick and {} lower wick",
+            i + recent + 1 - n,
+            recent,
+            size,
+            kind,
+            wick(c.upper_wick(), c),
+            wick(c.lower_wick(), c),
+        ));
+
+        if let Some(prev) = i.checked_sub(1).map(|j| &candles[j]) {
+            let (top, bottom) = (c.body_top(), c.body_bottom());
+            let (prev_top, prev_bottom) = (prev.body_top(), prev.body_bottom());
+            if c.low > prev.high {
+                text.push_str(", gapping up above the previous high");
+            } else if c.high < prev.low {
+                text.push_str(", gapping down below the previous low");
+            }
+            if top > prev_top && bottom < prev_bottom && c.is_bullish() != prev.is_bullish() {
+                text.push_str(", its body engulfing the previous body");
+            } else if top <= prev_top && bottom >= prev_bottom && c.body() < prev.body() {
+                text.push_str(", its body inside the previous body");
+            }
+            if c.close > prev.high {
+                text.push_str(", closing above the previous high");
+            } else if c.close < prev.low {
+                text.push_str(", closing below the previous low");
+            }
+        }
+        text.push('.');
+    }
+    text
+}
@@ -1,9 +1,48 @@
 use std::env;
 
+use crate::ensemble::MAX_SAMPLES;
+use crate::extract::VisionMode;
+use crate::prefilter::PrefilterConfig;
+use crate::preprocess::{OutputFormat, PreprocessConfig};
+use crate::retrieval::FewShotConfig;
+use crate::volume::VolumeConfig;
+
 pub struct Config {
     pub deepseek_api_key: String,
     pub replicate_api_token: String,
     pub port: u16,
+    pub taxonomy_dir: String,
+    pub taxonomy_history_dir: String,
+    pub admin_token: Option<String>,
+    pub locale_dir: String,
+    pub prompt_dir: String,
+    pub experiments_file: String,
+    pub data_dir: String,
+    pub prefilter: PrefilterConfig,
+    pub few_shot: FewShotConfig,
+    pub preprocess: PreprocessConfig,
+    /// Default for the per-request `vision` option.
+    pub vision_mode: VisionMode,
+    /// Default reasoner samples per analysis; above 1 the answers are voted on.
+    pub reasoner_samples: usize,
+    /// Models the samples cycle through; empty uses the request's reasoner model.
+    pub ensemble_models: Vec<String>,
+    pub volume: VolumeConfig,
+}
+
+/// Directory of named taxonomy CSVs; also read by CLI commands that run without API keys.
+pub fn taxonomy_dir() -> String {
+    env::var("TAXONOMY_DIR").unwrap_or_else(|_| "taxonomies".to_string())
+}
+
+/// Comma-separated model names, blanks dropped.
+pub fn models_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .map(String::from)
+        .collect()
 }
 
 impl Config {
@@ -17,6 +56,77 @@ impl Config {
                 .unwrap_or_else(|_| "3000".to_string())
                 .parse()
                 .expect("PORT must be a valid u16"),
+            taxonomy_dir: taxonomy_dir(),
+            taxonomy_history_dir: env::var("TAXONOMY_HISTORY_DIR")
+                .unwrap_or_else(|_| "taxonomy_history".to_string()),
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+            locale_dir: env::var("LOCALE_DIR").unwrap_or_else(|_| "locales".to_string()),
+            prompt_dir: env::var("PROMPT_DIR").unwrap_or_else(|_| "prompts".to_string()),
+            experiments_file: env::var("EXPERIMENTS_FILE")
+                .unwrap_or_else(|_| "experiments.json".to_string()),
+            data_dir: env::var("DATA_DIR").unwrap_or_else(|_| "data".to_string()),
+            prefilter: PrefilterConfig {
+                enabled: env::var("PREFILTER")
+                    .map(|v| v != "off" && v != "false" && v != "0")
+                    .unwrap_or(true),
+                max_candidates: env::var("PREFILTER_MAX_CANDIDATES")
+                    .unwrap_or_else(|_| "25".to_string())
+                    .parse()
+                    .expect("PREFILTER_MAX_CANDIDATES must be a positive integer"),
+                min_confidence: env::var("PREFILTER_MIN_CONFIDENCE")
+                    .unwrap_or_else(|_| "0.5".to_string())
+                    .parse()
+                    .expect("PREFILTER_MIN_CONFIDENCE must be a number between 0 and 1"),
+            },
+            few_shot: FewShotConfig {
+                max_examples: env::var("FEW_SHOT_EXAMPLES")
+                    .unwrap_or_else(|_| "3".to_string())
+                    .parse()
+                    .expect("FEW_SHOT_EXAMPLES must be a non-negative integer"),
+                token_budget: env::var("FEW_SHOT_TOKEN_BUDGET")
+                    .unwrap_or_else(|_| "2000".to_string())
+                    .parse()
+                    .expect("FEW_SHOT_TOKEN_BUDGET must be a non-negative integer"),
+            },
+            preprocess: PreprocessConfig {
+                max_dimension: env::var("IMAGE_MAX_DIMENSION")
+                    .unwrap_or_else(|_| "1600".to_string())
+                    .parse()
+                    .expect("IMAGE_MAX_DIMENSION must be a positive integer"),
+                autocrop: env::var("IMAGE_AUTOCROP")
+                    .map(|v| v == "on" || v == "true" || v == "1")
+                    .unwrap_or(false),
+                format: match env::var("IMAGE_FORMAT").as_deref() {
+                    Ok("jpeg") | Ok("jpg") => OutputFormat::Jpeg,
+                    Ok("png") | Err(_) => OutputFormat::Png,
+                    Ok(other) => panic!("IMAGE_FORMAT must be png or jpeg, got {}", other),
+                },
+            },
+            vision_mode: env::var("VISION_MODE")
+                .unwrap_or_else(|_| "model".to_string())
+                .parse()
+                .unwrap_or_else(|e| panic!("VISION_MODE: {}", e)),
+            reasoner_samples: env::var("REASONER_SAMPLES")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .ok()
+                .filter(|n| (1..=MAX_SAMPLES).contains(n))
+                .unwrap_or_else(|| panic!("REASONER_SAMPLES must be between 1 and {}", MAX_SAMPLES)),
+            ensemble_models: env::var("ENSEMBLE_MODELS")
+                .map(|v| models_list(&v))
+                .unwrap_or_default(),
+            volume: VolumeConfig {
+                lookback: env::var("VOLUME_LOOKBACK")
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .expect("VOLUME_LOOKBACK must be a positive integer"),
+                confirm_ratio: env::var("VOLUME_CONFIRM_RATIO")
+                    .unwrap_or_else(|_| "1.5".to_string())
+                    .parse()
+                    .expect("VOLUME_CONFIRM_RATIO must be a number"),
+            },
         }
     }
 }
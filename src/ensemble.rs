@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::analyzer::{self, AnalyzerError, AnalyzerOptions, AnalyzerResult, ErrorKind};
+use crate::models::Confidence;
+use crate::pipeline::AnalysisPlan;
+use crate::taxonomy::Taxonomy;
+use crate::AppState;
+
+/// Upper bound on `samples`, so one request cannot fan out without limit.
+pub const MAX_SAMPLES: usize = 9;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternVote {
+    pub pattern: String,
+    pub votes: usize,
+    /// Fraction of successful samples that chose this pattern.
+    pub share: f64,
+    /// Models that voted for it, one entry per vote.
+    pub models: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnsembleReport {
+    pub samples: usize,
+    pub succeeded: usize,
+    /// Errors of samples that failed; the vote uses the rest.
+    pub errors: Vec<String>,
+    /// Vote share of the winning pattern.
+    pub agreement: f64,
+    /// Most votes first.
+    pub votes: Vec<PatternVote>,
+}
+
+/// Confidence from agreement alone: a pattern every sample picks is more
+/// trustworthy than whatever one sample claims about itself.
+fn calibrated(agreement: f64, succeeded: usize) -> Confidence {
+    if agreement >= 0.8 && succeeded >= 3 {
+        Confidence::High
+    } else if agreement >= 0.5 {
+        Confidence::Medium
+    } else {
+        Confidence::Low
+    }
+}
+
+fn confidence_weight(confidence: Confidence) -> u32 {
+    match confidence {
+        Confidence::Low => 1,
+        Confidence::Medium => 2,
+        Confidence::High => 3,
+    }
+}
+
+/// Majority vote over sample answers. Ties go to the pattern with the higher
+/// summed self-reported confidence, then to the one voted first. The winning
+/// sample with the highest confidence supplies reasoning and details; tokens
+/// and cost are summed over all samples.
+fn aggregate(samples: Vec<(String, AnalyzerResult)>, errors: Vec<String>) -> (AnalyzerResult, EnsembleReport) {
+    let succeeded = samples.len();
+    let mut tallies: Vec<(String, usize, u32, Vec<String>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (model, result) in &samples {
+        let i = *index.entry(result.pattern.clone()).or_insert_with(|| {
+            tallies.push((result.pattern.clone(), 0, 0, Vec::new()));
+            tallies.len() - 1
+        });
+        tallies[i].1 += 1;
+        tallies[i].2 += confidence_weight(result.confidence);
+        tallies[i].3.push(model.clone());
+    }
+    // Stable sort keeps first-voted order among full ties.
+    tallies.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)));
+
+    let winner = tallies[0].0.clone();
+    let agreement = tallies[0].1 as f64 / succeeded as f64;
+
+    let (prompt_tokens, completion_tokens, reasoning_tokens, cache_hit_tokens, cost_usd) =
+        samples.iter().fold((0, 0, 0, 0, 0.0), |acc, (_, r)| {
+            (
+                acc.0 + r.prompt_tokens,
+                acc.1 + r.completion_tokens,
+                acc.2 + r.reasoning_tokens,
+                acc.3 + r.cache_hit_tokens,
+                acc.4 + r.cost_usd,
+            )
+        });
+
+    let mut alternatives: Vec<String> = tallies[1..].iter().map(|t| t.0.clone()).collect();
+    let representative = samples
+        .into_iter()
+        .map(|(_, r)| r)
+        .filter(|r| r.pattern == winner)
+        .reduce(|best, r| if r.confidence > best.confidence { r } else { best })
+        .expect("winner has at least one vote");
+    for name in &representative.alternatives {
+        if !alternatives.contains(name) && *name != winner {
+            alternatives.push(name.clone());
+        }
+    }
+
+    let report = EnsembleReport {
+        samples: succeeded + errors.len(),
+        succeeded,
+        errors,
+        agreement,
+        votes: tallies
+            .into_iter()
+            .map(|(pattern, votes, _, models)| PatternVote {
+                pattern,
+                votes,
+                share: votes as f64 / succeeded as f64,
+                models,
+            })
+            .collect(),
+    };
+
+    let result = AnalyzerResult {
+        confidence: calibrated(agreement, succeeded),
+        alternatives,
+        prompt_tokens,
+        completion_tokens,
+        reasoning_tokens,
+        cache_hit_tokens,
+        cost_usd,
+        ..representative
+    };
+    (result, report)
+}
+
+/// Runs `plan.samples` reasoner calls concurrently, cycling through
+/// `plan.ensemble_models` (or the plan's model), and votes on the answers.
+/// Fails only when every sample fails, as a parse failure when every answer was unparseable.
+pub async fn run(
+    state: &AppState,
+    plan: &AnalysisPlan,
+    description: &str,
+    taxonomy: &Taxonomy,
+    examples: &str,
+    trend_context: &str,
+) -> Result<(AnalyzerResult, EnsembleReport), AnalyzerError> {
+    let models = if plan.ensemble_models.is_empty() {
+        vec![plan.reasoner_model.clone()]
+    } else {
+        plan.ensemble_models.clone()
+    };
+    let description: Arc<str> = description.into();
+    let examples: Arc<str> = examples.into();
+    let trend_context: Arc<str> = trend_context.into();
+    let taxonomy = Arc::new(taxonomy.clone());
+
+    info!("Ensemble: {} samples across {}", plan.samples, models.join(", "));
+    let mut tasks = JoinSet::new();
+    for i in 0..plan.samples {
+        let model = models[i % models.len()].clone();
+        let client = state.client.clone();
+        let api_key = state.config.deepseek_api_key.clone();
+        let (description, examples, taxonomy) = (description.clone(), examples.clone(), taxonomy.clone());
+        let trend_context = trend_context.clone();
+        let (template, lang) = (plan.reasoner_prompt.clone(), plan.lang.clone());
+        let occurrences = plan.multiple;
+        tasks.spawn(async move {
+            let result = analyzer::analyze_pattern(
+                &client,
+                &api_key,
+                &description,
+                &taxonomy,
+                &AnalyzerOptions {
+                    model: &model,
+                    lang: &lang,
+                    template: &template,
+                    examples: &examples,
+                    trend_context: &trend_context,
+                    occurrences,
+                },
+            )
+            .await;
+            (i, model, result)
+        });
+    }
+
+    let mut outcomes = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        outcomes.push(joined.map_err(|e| format!("Ensemble sample panicked: {}", e))?);
+    }
+    // Completion order is arbitrary; vote in sample order so ties are stable.
+    outcomes.sort_by_key(|(i, _, _)| *i);
+
+    let mut samples = Vec::new();
+    let mut errors = Vec::new();
+    let mut all_unparseable = true;
+    for (i, model, result) in outcomes {
+        match result {
+            Ok(result) => samples.push((model, result)),
+            Err(e) => {
+                warn!("Ensemble sample {} ({}) failed: {}", i + 1, model, e);
+                all_unparseable &= e.kind == ErrorKind::Parse;
+                errors.push(format!("{}: {}", model, e));
+            }
+        }
+    }
+    if samples.is_empty() {
+        return Err(AnalyzerError {
+            kind: if all_unparseable { ErrorKind::Parse } else { ErrorKind::Request },
+            message: format!("All {} ensemble samples failed: {}", errors.len(), errors.join("; ")),
+        });
+    }
+
+    let (result, report) = aggregate(samples, errors);
+    info!(
+        "Ensemble: {} with {:.0}% agreement over {} samples",
+        result.pattern,
+        report.agreement * 100.0,
+        report.succeeded
+    );
+    Ok((result, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(pattern: &str, confidence: Confidence, reasoning: &str) -> AnalyzerResult {
+        AnalyzerResult {
+            pattern: pattern.to_string(),
+            category: None,
+            direction: None,
+            confidence,
+            reasoning: reasoning.to_string(),
+            alternatives: Vec::new(),
+            candle_count: None,
+            occurrences: Vec::new(),
+            chain_of_thought: None,
+            prompt_tokens: 1_000,
+            completion_tokens: 100,
+            reasoning_tokens: 50,
+            cache_hit_tokens: 200,
+            cost_usd: 0.01,
+        }
+    }
+
+    fn vote(model: &str, pattern: &str, confidence: Confidence) -> (String, AnalyzerResult) {
+        (model.to_string(), answer(pattern, confidence, model))
+    }
+
+    #[test]
+    fn majority_wins_a_split_vote() {
+        let mut best = answer("Hammer", Confidence::High, "best");
+        best.alternatives = vec!["Doji (Standard)".to_string(), "Shooting Star".to_string()];
+        let samples = vec![
+            vote("a", "Hammer", Confidence::Medium),
+            vote("b", "Doji (Standard)", Confidence::High),
+            ("a".to_string(), best),
+        ];
+        let (result, report) = aggregate(samples, vec!["c: timed out".to_string()]);
+
+        assert_eq!(result.pattern, "Hammer");
+        // The most confident winning sample speaks for the ensemble
+        assert_eq!(result.reasoning, "best");
+        assert_eq!(result.alternatives, ["Doji (Standard)", "Shooting Star"]);
+        assert_eq!(result.confidence, Confidence::Medium);
+
+        assert_eq!((report.samples, report.succeeded, report.errors.len()), (4, 3, 1));
+        assert!((report.agreement - 2.0 / 3.0).abs() < 1e-12);
+        let votes: Vec<(&str, usize, &[String])> =
+            report.votes.iter().map(|v| (v.pattern.as_str(), v.votes, v.models.as_slice())).collect();
+        assert_eq!(
+            votes,
+            [
+                ("Hammer", 2, &["a".to_string(), "a".to_string()][..]),
+                ("Doji (Standard)", 1, &["b".to_string()][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn usage_and_cost_are_summed_over_samples() {
+        let samples = vec![
+            vote("a", "Hammer", Confidence::High),
+            vote("b", "Hammer", Confidence::High),
+            vote("c", "Doji (Standard)", Confidence::Low),
+        ];
+        let (result, _) = aggregate(samples, Vec::new());
+        assert_eq!(
+            (result.prompt_tokens, result.completion_tokens, result.reasoning_tokens, result.cache_hit_tokens),
+            (3_000, 300, 150, 600)
+        );
+        assert!((result.cost_usd - 0.03).abs() < 1e-12);
+    }
+
+    #[test]
+    fn ties_go_to_summed_confidence_then_first_vote() {
+        // Two votes each; Doji's confidences sum to 4 against Hammer's 2
+        let samples = vec![
+            vote("a", "Hammer", Confidence::Low),
+            vote("b", "Doji (Standard)", Confidence::High),
+            vote("c", "Hammer", Confidence::Low),
+            vote("d", "Doji (Standard)", Confidence::Low),
+        ];
+        let (result, report) = aggregate(samples, Vec::new());
+        assert_eq!((result.pattern.as_str(), result.reasoning.as_str()), ("Doji (Standard)", "b"));
+        assert_eq!(report.agreement, 0.5);
+        assert_eq!(result.confidence, Confidence::Medium);
+        assert_eq!(result.alternatives, ["Hammer"]);
+
+        let samples = vec![vote("a", "Hammer", Confidence::Medium), vote("b", "Doji (Standard)", Confidence::Medium)];
+        let (result, _) = aggregate(samples, Vec::new());
+        assert_eq!(result.pattern, "Hammer");
+    }
+
+    #[test]
+    fn calibrated_needs_agreement_and_enough_samples() {
+        assert_eq!(calibrated(0.8, 3), Confidence::High);
+        assert_eq!(calibrated(1.0, 2), Confidence::Medium);
+        assert_eq!(calibrated(0.75, 4), Confidence::Medium);
+        assert_eq!(calibrated(0.5, 4), Confidence::Medium);
+        assert_eq!(calibrated(0.4, 5), Confidence::Low);
+
+        // Agreement overrides what the samples said about themselves
+        let unanimous = (0..3).map(|i| vote(&i.to_string(), "Hammer", Confidence::Low)).collect();
+        assert_eq!(aggregate(unanimous, Vec::new()).0.confidence, Confidence::High);
+    }
+}
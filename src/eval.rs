@@ -0,0 +1,522 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::accuracy::AccuracyReport;
+use crate::candles;
+use crate::import::ImportOptions;
+use crate::models::{AnalyzeResponse, Confidence};
+use crate::pipeline::{self, AnalysisPlan};
+use crate::vision::VisionResult;
+use crate::AppState;
+
+const USAGE: &str = "\
+Usage: deepseek-test eval <manifest> [options]
+
+Runs the analysis pipeline over a labelled manifest and writes
+report.json, report.md and results.jsonl.
+
+Manifest: a JSON array (or JSON lines) of entries with a ground-truth
+\"pattern\" and one input: \"image\" (path), \"description\" (text) or
+\"ohlc\" (path); an \"image\" with \"ohlc\" uses the OHLC for trend,
+verification and volume. Optional per entry: \"id\", \"taxonomy\", \"options\".
+Relative paths resolve against the manifest's directory.
+
+Options:
+  --concurrency <n>   parallel pipeline runs (default 4)
+  --out <dir>         report directory (default eval-out)
+  --top-k <k>         k for top-k accuracy (default 3)
+  --set <key=value>   analysis option applied to every entry, same keys as
+                      the /analyze form (taxonomy, lang, reasoner_prompt, ...)
+
+Experiments and few-shot retrieval are off unless asked for, e.g.
+--set experiment=<name> or --set examples=3.";
+
+#[derive(Debug, Clone, Deserialize)]
+struct ManifestEntry {
+    id: Option<String>,
+    pattern: String,
+    image: Option<String>,
+    description: Option<String>,
+    ohlc: Option<String>,
+    taxonomy: Option<String>,
+    #[serde(default)]
+    options: HashMap<String, String>,
+}
+
+struct EvalArgs {
+    manifest: PathBuf,
+    concurrency: usize,
+    out: PathBuf,
+    top_k: usize,
+    options: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EntryResult {
+    id: String,
+    expected: String,
+    expected_category: Option<String>,
+    expected_direction: Option<String>,
+    predicted: Option<String>,
+    predicted_category: Option<String>,
+    predicted_direction: Option<String>,
+    confidence: Option<Confidence>,
+    alternatives: Vec<String>,
+    correct: bool,
+    top_k_hit: bool,
+    cost_usd: f64,
+    latency_ms: u64,
+    error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    count: usize,
+    total: f64,
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p95: f64,
+    max: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct GroupAccuracy {
+    group: String,
+    total: u64,
+    correct: u64,
+    accuracy: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct EvalReport {
+    manifest: String,
+    entries: usize,
+    succeeded: usize,
+    failed: usize,
+    /// Over all entries; failed runs count as wrong.
+    accuracy: f64,
+    top_k: usize,
+    top_k_accuracy: f64,
+    category_accuracy: f64,
+    direction_accuracy: f64,
+    per_category: Vec<GroupAccuracy>,
+    per_direction: Vec<GroupAccuracy>,
+    patterns: AccuracyReport,
+    cost_usd: Summary,
+    latency_ms: Summary,
+}
+
+fn parse_args(args: &[String]) -> Result<EvalArgs, String> {
+    let mut manifest = None;
+    let mut parsed = EvalArgs {
+        manifest: PathBuf::new(),
+        concurrency: 4,
+        out: PathBuf::from("eval-out"),
+        top_k: 3,
+        options: HashMap::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value\n\n{}", name, USAGE))
+        };
+        match arg.as_str() {
+            "--concurrency" => {
+                parsed.concurrency = value(arg)?
+                    .parse::<usize>()
+                    .map_err(|e| format!("--concurrency: {}", e))?
+                    .max(1)
+            }
+            "--out" => parsed.out = PathBuf::from(value(arg)?),
+            "--top-k" => {
+                parsed.top_k = value(arg)?
+                    .parse::<usize>()
+                    .map_err(|e| format!("--top-k: {}", e))?
+                    .max(1)
+            }
+            "--set" => {
+                let pair = value(arg)?;
+                let (key, val) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("--set expects key=value, got {}", pair))?;
+                parsed.options.insert(key.to_string(), val.to_string());
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other if other.starts_with("--") => {
+                return Err(format!("Unknown option {}\n\n{}", other, USAGE))
+            }
+            other => manifest = Some(PathBuf::from(other)),
+        }
+    }
+
+    parsed.manifest = manifest.ok_or_else(|| USAGE.to_string())?;
+    Ok(parsed)
+}
+
+fn load_manifest(path: &Path) -> Result<Vec<ManifestEntry>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    if text.trim_start().starts_with('[') {
+        return serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid manifest {}: {}", path.display(), e));
+    }
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(n, l)| {
+            serde_json::from_str(l)
+                .map_err(|e| format!("Invalid manifest line {}: {}", n + 1, e))
+        })
+        .collect()
+}
+
+async fn run_entry(
+    state: &AppState,
+    base: &Path,
+    entry: &ManifestEntry,
+    options: HashMap<String, String>,
+) -> Result<AnalyzeResponse, String> {
+    let plan = AnalysisPlan::resolve(state, &options)
+        .await
+        .map_err(|(_, e)| e)?;
+
+    if let Some(description) = &entry.description {
+        let vision_result = VisionResult {
+            description: description.clone(),
+            predict_seconds: 0.0,
+        };
+        return pipeline::analyze_description(state, &plan, vision_result, None)
+            .await
+            .map_err(|e| e.message);
+    }
+
+    let candles = match &entry.ohlc {
+        Some(ohlc) => Some(candles::load_file(&base.join(ohlc), &ImportOptions::default())?),
+        None => None,
+    };
+
+    // An image with OHLC alongside is analyzed like an /analyze upload with both
+    if let Some(image) = &entry.image {
+        let path = base.join(image);
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        return pipeline::analyze_image(state, &plan, &bytes, candles.as_deref())
+            .await
+            .map_err(|e| e.message);
+    }
+
+    if let Some(candles) = candles {
+        return pipeline::analyze_candles(state, &plan, &candles)
+            .await
+            .map_err(|e| e.message);
+    }
+
+    Err("Entry has no image, description or ohlc input".to_string())
+}
+
+fn summarize(mut values: Vec<f64>) -> Summary {
+    if values.is_empty() {
+        return Summary::default();
+    }
+    values.sort_by(f64::total_cmp);
+    let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+    let total: f64 = values.iter().sum();
+    Summary {
+        count: values.len(),
+        total,
+        mean: total / values.len() as f64,
+        p50: at(0.5),
+        p90: at(0.9),
+        p95: at(0.95),
+        max: values[values.len() - 1],
+    }
+}
+
+fn group_accuracy<'a>(rows: impl Iterator<Item = (Option<&'a str>, bool)>) -> Vec<GroupAccuracy> {
+    let mut groups: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    for (group, correct) in rows {
+        let entry = groups.entry(group.unwrap_or("Unknown").to_string()).or_default();
+        entry.0 += 1;
+        if correct {
+            entry.1 += 1;
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(group, (total, correct))| GroupAccuracy {
+            group,
+            total,
+            correct,
+            accuracy: correct as f64 / total as f64,
+        })
+        .collect()
+}
+
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+fn build_report(manifest: &Path, top_k: usize, results: &[EntryResult]) -> EvalReport {
+    let succeeded: Vec<&EntryResult> = results.iter().filter(|r| r.error.is_none()).collect();
+    let n = results.len();
+
+    let category_hits = succeeded
+        .iter()
+        .filter(|r| r.expected_category.is_some() && r.expected_category == r.predicted_category)
+        .count();
+    let direction_hits = succeeded
+        .iter()
+        .filter(|r| r.expected_direction.is_some() && r.expected_direction == r.predicted_direction)
+        .count();
+
+    EvalReport {
+        manifest: manifest.display().to_string(),
+        entries: n,
+        succeeded: succeeded.len(),
+        failed: n - succeeded.len(),
+        accuracy: ratio(results.iter().filter(|r| r.correct).count(), n),
+        top_k,
+        top_k_accuracy: ratio(results.iter().filter(|r| r.top_k_hit).count(), n),
+        category_accuracy: ratio(category_hits, n),
+        direction_accuracy: ratio(direction_hits, n),
+        per_category: group_accuracy(
+            results.iter().map(|r| (r.expected_category.as_deref(), r.correct)),
+        ),
+        per_direction: group_accuracy(
+            results.iter().map(|r| (r.expected_direction.as_deref(), r.correct)),
+        ),
+        patterns: AccuracyReport::from_pairs(
+            succeeded
+                .iter()
+                .filter_map(|r| Some((r.predicted.as_deref()?, r.expected.as_str()))),
+        ),
+        cost_usd: summarize(succeeded.iter().map(|r| r.cost_usd).collect()),
+        latency_ms: summarize(succeeded.iter().map(|r| r.latency_ms as f64).collect()),
+    }
+}
+
+fn render_markdown(report: &EvalReport, results: &[EntryResult]) -> String {
+    let mut md = String::new();
+    let pct = |x: f64| format!("{:.1}%", x * 100.0);
+
+    let _ = writeln!(md, "# Evaluation report\n");
+    let _ = writeln!(md, "Manifest: `{}`\n", report.manifest);
+    let _ = writeln!(md, "| Metric | Value |\n|---|---|");
+    let _ = writeln!(md, "| Entries | {} ({} failed) |", report.entries, report.failed);
+    let _ = writeln!(md, "| Accuracy | {} |", pct(report.accuracy));
+    let _ = writeln!(md, "| Top-{} accuracy | {} |", report.top_k, pct(report.top_k_accuracy));
+    let _ = writeln!(md, "| Category accuracy | {} |", pct(report.category_accuracy));
+    let _ = writeln!(md, "| Direction accuracy | {} |", pct(report.direction_accuracy));
+    let _ = writeln!(
+        md,
+        "| Cost (total / mean / p95) | ${:.4} / ${:.6} / ${:.6} |",
+        report.cost_usd.total, report.cost_usd.mean, report.cost_usd.p95
+    );
+    let _ = writeln!(
+        md,
+        "| Latency ms (p50 / p90 / max) | {:.0} / {:.0} / {:.0} |\n",
+        report.latency_ms.p50, report.latency_ms.p90, report.latency_ms.max
+    );
+
+    for (title, groups) in [
+        ("By category", &report.per_category),
+        ("By direction", &report.per_direction),
+    ] {
+        let _ = writeln!(md, "## {}\n\n| Group | Total | Correct | Accuracy |\n|---|---|---|---|", title);
+        for g in groups {
+            let _ = writeln!(md, "| {} | {} | {} | {} |", g.group, g.total, g.correct, pct(g.accuracy));
+        }
+        md.push('\n');
+    }
+
+    let _ = writeln!(md, "## By pattern\n\n| Pattern | Actual | Predicted | Precision | Recall |\n|---|---|---|---|---|");
+    let opt = |x: Option<f64>| x.map(pct).unwrap_or_else(|| "-".to_string());
+    for p in &report.patterns.per_pattern {
+        let _ = writeln!(
+            md,
+            "| {} | {} | {} | {} | {} |",
+            p.pattern,
+            p.actual,
+            p.predicted,
+            opt(p.precision),
+            opt(p.recall)
+        );
+    }
+
+    let confusion = &report.patterns.confusion;
+    if !confusion.labels.is_empty() {
+        let _ = writeln!(md, "\n## Confusion matrix\n\nRows: actual, columns: predicted.\n");
+        let _ = writeln!(md, "| | {} |", confusion.labels.join(" | "));
+        let _ = writeln!(md, "|---|{}", "---|".repeat(confusion.labels.len()));
+        for (label, row) in confusion.labels.iter().zip(&confusion.matrix) {
+            let cells: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+            let _ = writeln!(md, "| **{}** | {} |", label, cells.join(" | "));
+        }
+    }
+
+    let failures: Vec<&EntryResult> = results.iter().filter(|r| r.error.is_some()).collect();
+    if !failures.is_empty() {
+        let _ = writeln!(md, "\n## Failures\n");
+        for r in failures {
+            let _ = writeln!(md, "- `{}`: {}", r.id, r.error.as_deref().unwrap_or_default());
+        }
+    }
+
+    md
+}
+
+pub async fn run(state: Arc<AppState>, args: &[String]) -> Result<(), String> {
+    let args = parse_args(args)?;
+    let entries = load_manifest(&args.manifest)?;
+    let base = args
+        .manifest
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    info!(
+        "Evaluating {} entries from {} (concurrency {})",
+        entries.len(),
+        args.manifest.display(),
+        args.concurrency
+    );
+
+    let semaphore = Arc::new(Semaphore::new(args.concurrency));
+    let mut tasks = JoinSet::new();
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        let state = state.clone();
+        let semaphore = semaphore.clone();
+        let base = base.clone();
+        let mut options = args.options.clone();
+        options.extend(entry.options.clone());
+        if let Some(taxonomy) = &entry.taxonomy {
+            options.insert("taxonomy".to_string(), taxonomy.clone());
+        }
+        // Random variants and few-shot examples drawn from live feedback (which
+        // may label these very charts) would make runs neither reproducible
+        // nor leak-free, so both need an explicit option.
+        options.entry("experiment".to_string()).or_insert_with(|| "none".to_string());
+        options.entry("examples".to_string()).or_insert_with(|| "0".to_string());
+        let top_k = args.top_k;
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire().await.expect("semaphore closed");
+            let id = entry.id.clone().unwrap_or_else(|| format!("#{}", index + 1));
+
+            let taxonomy = state.taxonomy(options.get("taxonomy").map(String::as_str)).await.ok();
+            let expected = taxonomy.as_ref().and_then(|t| t.find(&entry.pattern));
+            if expected.is_none() {
+                warn!("{}: expected pattern {:?} is not in the taxonomy", id, entry.pattern);
+            }
+
+            let started = Instant::now();
+            let outcome = run_entry(&state, &base, &entry, options).await;
+            let latency_ms = started.elapsed().as_millis() as u64;
+
+            let expected_name = expected.map(|p| p.name.clone()).unwrap_or(entry.pattern.clone());
+            let mut result = EntryResult {
+                id,
+                expected_category: expected.map(|p| p.category.to_string()),
+                expected_direction: expected.map(|p| p.direction.to_string()),
+                expected: expected_name,
+                predicted: None,
+                predicted_category: None,
+                predicted_direction: None,
+                confidence: None,
+                alternatives: Vec::new(),
+                correct: false,
+                top_k_hit: false,
+                cost_usd: 0.0,
+                latency_ms,
+                error: None,
+            };
+
+            match outcome {
+                Ok(response) => {
+                    result.correct = response.pattern.eq_ignore_ascii_case(&result.expected);
+                    result.top_k_hit = std::iter::once(&response.pattern)
+                        .chain(&response.alternatives)
+                        .take(top_k)
+                        .any(|p| p.eq_ignore_ascii_case(&result.expected));
+                    result.predicted_category = response.category.map(|c| c.to_string());
+                    result.predicted_direction = response.direction.map(|d| d.to_string());
+                    result.confidence = Some(response.confidence);
+                    result.cost_usd = response.cost.total_cost_usd;
+                    result.alternatives = response.alternatives;
+                    result.predicted = Some(response.pattern);
+                }
+                Err(e) => result.error = Some(e),
+            }
+
+            info!(
+                "{}: expected {} / got {} ({} ms)",
+                result.id,
+                result.expected,
+                result.predicted.as_deref().unwrap_or("error"),
+                result.latency_ms
+            );
+            (index, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined.map_err(|e| format!("Evaluation task panicked: {}", e))?);
+    }
+    results.sort_by_key(|(index, _)| *index);
+    let results: Vec<EntryResult> = results.into_iter().map(|(_, r)| r).collect();
+
+    let report = build_report(&args.manifest, args.top_k, &results);
+
+    std::fs::create_dir_all(&args.out)
+        .map_err(|e| format!("Failed to create {}: {}", args.out.display(), e))?;
+    let write = |name: &str, contents: String| {
+        let path = args.out.join(name);
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    };
+    write(
+        "report.json",
+        serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?,
+    )?;
+    write("report.md", render_markdown(&report, &results))?;
+    write(
+        "results.jsonl",
+        results
+            .iter()
+            .filter_map(|r| serde_json::to_string(r).ok())
+            .map(|l| l + "\n")
+            .collect(),
+    )?;
+
+    info!(
+        "Accuracy {:.1}% (top-{} {:.1}%) over {} entries, {} failed — reports in {}",
+        report.accuracy * 100.0,
+        report.top_k,
+        report.top_k_accuracy * 100.0,
+        report.entries,
+        report.failed,
+        args.out.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::analyzer;
+use crate::prompts::{PromptLibrary, Stage};
+use crate::store::AnalysisStore;
+
+/// One arm of an experiment. Unset fields fall back to the server defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    pub vision_prompt: Option<String>,
+    /// Replicate model version for the vision stage.
+    pub vision_version: Option<String>,
+    pub reasoner_prompt: Option<String>,
+    pub reasoner_model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Experiment {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub variants: Vec<Variant>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Which experiment arm served an analysis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub experiment: String,
+    pub variant: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct VariantMetrics {
+    pub requests: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub parse_failures: u64,
+    pub total_cost_usd: f64,
+    /// Requests with a recorded latency, which older records lack.
+    pub timed: u64,
+    pub total_latency_ms: u64,
+    pub max_latency_ms: u64,
+}
+
+/// How a served request ended, for metrics purposes.
+enum Outcome {
+    Succeeded { cost_usd: f64 },
+    ParseFailure,
+    Failed,
+}
+
+#[derive(Debug, Default)]
+pub struct ExperimentMetrics {
+    by_variant: HashMap<(String, String), VariantMetrics>,
+}
+
+impl ExperimentMetrics {
+    /// Tallies the stored analyses and failures of experiment requests per
+    /// variant, so the numbers survive restarts.
+    pub fn from_store(store: &AnalysisStore) -> Self {
+        let mut metrics = ExperimentMetrics::default();
+        for analysis in store.analyses() {
+            if let Some(assignment) = &analysis.experiment {
+                let outcome = Outcome::Succeeded { cost_usd: analysis.total_cost_usd };
+                metrics.record(assignment, outcome, analysis.latency_ms);
+            }
+        }
+        for failure in store.failures() {
+            let outcome = if failure.parse_failure { Outcome::ParseFailure } else { Outcome::Failed };
+            metrics.record(&failure.experiment, outcome, Some(failure.latency_ms));
+        }
+        metrics
+    }
+
+    fn record(&mut self, assignment: &Assignment, outcome: Outcome, latency_ms: Option<u64>) {
+        let m = self
+            .by_variant
+            .entry((assignment.experiment.clone(), assignment.variant.clone()))
+            .or_default();
+        m.requests += 1;
+        if let Some(latency_ms) = latency_ms {
+            m.timed += 1;
+            m.total_latency_ms += latency_ms;
+            m.max_latency_ms = m.max_latency_ms.max(latency_ms);
+        }
+        match outcome {
+            Outcome::Succeeded { cost_usd } => {
+                m.succeeded += 1;
+                m.total_cost_usd += cost_usd;
+            }
+            Outcome::ParseFailure => {
+                m.failed += 1;
+                m.parse_failures += 1;
+            }
+            Outcome::Failed => m.failed += 1,
+        }
+    }
+
+    pub fn get(&self, experiment: &str, variant: &str) -> VariantMetrics {
+        self.by_variant
+            .get(&(experiment.to_string(), variant.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VariantReport {
+    #[serde(flatten)]
+    pub variant: Variant,
+    pub metrics: VariantMetrics,
+    pub avg_cost_usd: f64,
+    pub avg_latency_ms: f64,
+    pub parse_failure_rate: f64,
+    /// Analyses from this variant that received user feedback, and the share
+    /// the user marked correct.
+    pub feedback_count: u64,
+    pub feedback_agreement: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExperimentReport {
+    pub name: String,
+    pub enabled: bool,
+    pub variants: Vec<VariantReport>,
+}
+
+fn ratio(n: f64, d: u64) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n / d as f64
+    }
+}
+
+/// `agreement` maps `(experiment, variant)` to `(agreed, total)` feedback counts.
+pub fn report(
+    experiments: &[Experiment],
+    metrics: &ExperimentMetrics,
+    agreement: &HashMap<(String, String), (u64, u64)>,
+) -> Vec<ExperimentReport> {
+    experiments
+        .iter()
+        .map(|e| ExperimentReport {
+            name: e.name.clone(),
+            enabled: e.enabled,
+            variants: e
+                .variants
+                .iter()
+                .map(|v| {
+                    let m = metrics.get(&e.name, &v.name);
+                    let (agreed, feedback_count) = agreement
+                        .get(&(e.name.clone(), v.name.clone()))
+                        .copied()
+                        .unwrap_or((0, 0));
+                    VariantReport {
+                        feedback_count,
+                        feedback_agreement: (feedback_count > 0)
+                            .then(|| agreed as f64 / feedback_count as f64),
+                        avg_cost_usd: ratio(m.total_cost_usd, m.succeeded),
+                        avg_latency_ms: ratio(m.total_latency_ms as f64, m.timed),
+                        parse_failure_rate: ratio(m.parse_failures as f64, m.requests),
+                        variant: v.clone(),
+                        metrics: m,
+                    }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Reads experiment definitions from a JSON array. A missing file just means
+/// no experiments are running. Variants naming a prompt or model that does
+/// not exist are dropped, so they fail here rather than on every request.
+pub fn load_experiments(path: &str, prompts: &PromptLibrary) -> Vec<Experiment> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => {
+            info!("No experiments file at {}", path);
+            return Vec::new();
+        }
+    };
+
+    match serde_json::from_str(&text) {
+        Ok(experiments) => validate(experiments, prompts),
+        Err(e) => {
+            warn!("Ignoring experiments file {}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+fn check_variant(variant: &Variant, prompts: &PromptLibrary) -> Result<(), String> {
+    if let Some(name) = &variant.vision_prompt {
+        prompts.get(Stage::Vision, Some(name))?;
+    }
+    if let Some(name) = &variant.reasoner_prompt {
+        prompts.get(Stage::Reasoner, Some(name))?;
+    }
+    match &variant.reasoner_model {
+        Some(model) if !analyzer::MODELS.contains(&model.as_str()) => {
+            Err(format!("Unknown reasoner model: {}", model))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Drops invalid variants, then experiments left without any weight.
+fn validate(experiments: Vec<Experiment>, prompts: &PromptLibrary) -> Vec<Experiment> {
+    experiments
+        .into_iter()
+        .filter_map(|mut e| {
+            e.variants.retain(|v| match check_variant(v, prompts) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!("Skipping variant {:?} of experiment {:?}: {}", v.name, e.name, err);
+                    false
+                }
+            });
+            let total: u32 = e.variants.iter().map(|v| v.weight).sum();
+            if total == 0 {
+                warn!("Skipping experiment {:?}: no variant has weight", e.name);
+                return None;
+            }
+            info!(
+                "Loaded experiment {:?} ({} variants{})",
+                e.name,
+                e.variants.len(),
+                if e.enabled { "" } else { ", disabled" }
+            );
+            Some(e)
+        })
+        .collect()
+}
+
+/// Picks an experiment (the requested one, else the first enabled) and draws a
+/// variant by weight.
+pub fn assign<'a>(
+    experiments: &'a [Experiment],
+    requested: Option<&str>,
+) -> Result<Option<(Assignment, &'a Variant)>, String> {
+    assign_with(experiments, requested, &mut rand::thread_rng())
+}
+
+fn assign_with<'a>(
+    experiments: &'a [Experiment],
+    requested: Option<&str>,
+    rng: &mut impl Rng,
+) -> Result<Option<(Assignment, &'a Variant)>, String> {
+    let experiment = match requested {
+        Some(name) => Some(
+            experiments
+                .iter()
+                .find(|e| e.name == name)
+                .ok_or_else(|| format!("Unknown experiment: {}", name))?,
+        ),
+        None => experiments.iter().find(|e| e.enabled),
+    };
+    let Some(experiment) = experiment else {
+        return Ok(None);
+    };
+
+    let total: u32 = experiment.variants.iter().map(|v| v.weight).sum();
+    let mut pick = rng.gen_range(0..total);
+    let variant = experiment
+        .variants
+        .iter()
+        .find(|v| {
+            if pick < v.weight {
+                return true;
+            }
+            pick -= v.weight;
+            false
+        })
+        .expect("weights sum to total");
+
+    Ok(Some((
+        Assignment {
+            experiment: experiment.name.clone(),
+            variant: variant.name.clone(),
+        },
+        variant,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::models::Confidence;
+    use crate::store::{FailedAnalysis, StoredAnalysis};
+
+    fn variant(name: &str, weight: u32) -> Variant {
+        Variant {
+            name: name.to_string(),
+            weight,
+            vision_prompt: None,
+            vision_version: None,
+            reasoner_prompt: None,
+            reasoner_model: None,
+        }
+    }
+
+    fn experiment(name: &str, enabled: bool, variants: Vec<Variant>) -> Experiment {
+        Experiment { name: name.to_string(), enabled, variants }
+    }
+
+    fn assignment(experiment: &str, variant: &str) -> Assignment {
+        Assignment { experiment: experiment.to_string(), variant: variant.to_string() }
+    }
+
+    #[test]
+    fn assign_draws_variants_by_weight() {
+        let experiments = [experiment("a", true, vec![variant("x", 3), variant("y", 1), variant("z", 0)])];
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for _ in 0..4000 {
+            let (assignment, _) = assign_with(&experiments, None, &mut rng).unwrap().unwrap();
+            *counts.entry(assignment.variant).or_default() += 1;
+        }
+        let x = counts["x"] as f64 / 4000.0;
+        assert!((x - 0.75).abs() < 0.03, "x drawn {:.3} of the time", x);
+        assert!(!counts.contains_key("z"));
+    }
+
+    #[test]
+    fn assign_picks_the_requested_or_first_enabled_experiment() {
+        let experiments = [
+            experiment("off", false, vec![variant("x", 1)]),
+            experiment("on", true, vec![variant("y", 1)]),
+        ];
+        let mut rng = StdRng::seed_from_u64(1);
+        let pick = |requested, rng: &mut StdRng| {
+            assign_with(&experiments, requested, rng).map(|a| a.map(|(a, _)| a.experiment))
+        };
+        assert_eq!(pick(None, &mut rng), Ok(Some("on".to_string())));
+        // Asking by name works even for a disabled experiment
+        assert_eq!(pick(Some("off"), &mut rng), Ok(Some("off".to_string())));
+        assert!(pick(Some("missing"), &mut rng).is_err());
+        assert!(assign_with(&experiments[..1], None, &mut rng).unwrap().is_none());
+    }
+
+    #[test]
+    fn validation_drops_variants_with_unknown_prompts_or_models() {
+        let prompts = PromptLibrary::load("prompts");
+        let mut bad_prompt = variant("bad-prompt", 1);
+        bad_prompt.reasoner_prompt = Some("missing".to_string());
+        let mut bad_model = variant("bad-model", 1);
+        bad_model.reasoner_model = Some("gpt-4".to_string());
+        let mut chat = variant("chat", 1);
+        chat.reasoner_model = Some("deepseek-chat".to_string());
+        chat.vision_prompt = Some("default".to_string());
+
+        let kept = validate(
+            vec![
+                experiment("mixed", true, vec![bad_prompt.clone(), chat, variant("plain", 1)]),
+                experiment("all-bad", true, vec![bad_prompt, bad_model]),
+            ],
+            &prompts,
+        );
+        assert_eq!(kept.len(), 1);
+        let names: Vec<&str> = kept[0].variants.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["chat", "plain"]);
+    }
+
+    #[test]
+    fn shipped_experiments_are_valid() {
+        let experiments = load_experiments("experiments.json", &PromptLibrary::load("prompts"));
+        assert_eq!(experiments.len(), 1);
+        assert_eq!(experiments[0].variants.len(), 2);
+    }
+
+    fn stored(id: &str, experiment: Option<Assignment>, cost: f64, latency_ms: Option<u64>) -> StoredAnalysis {
+        StoredAnalysis {
+            id: id.to_string(),
+            timestamp: 0,
+            taxonomy: "default".to_string(),
+            pattern: "Hammer".to_string(),
+            category: None,
+            direction: None,
+            confidence: Confidence::High,
+            chart_description: String::new(),
+            experiment,
+            total_cost_usd: cost,
+            latency_ms,
+        }
+    }
+
+    fn failed(experiment: Assignment, parse_failure: bool, latency_ms: u64) -> FailedAnalysis {
+        FailedAnalysis { timestamp: 0, experiment, parse_failure, latency_ms }
+    }
+
+    #[test]
+    fn metrics_are_derived_from_stored_records() {
+        let dir = std::env::temp_dir().join(format!("experiments-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut store = AnalysisStore::load(dir.to_str().unwrap()).unwrap();
+        let x = assignment("a", "x");
+        store.insert(stored("1", Some(x.clone()), 0.02, Some(100))).unwrap();
+        store.insert(stored("2", Some(x.clone()), 0.04, Some(300))).unwrap();
+        // A record from before latencies were kept, and one outside any experiment
+        store.insert(stored("3", Some(x.clone()), 0.06, None)).unwrap();
+        store.insert(stored("4", None, 1.0, Some(5000))).unwrap();
+        store.insert_failure(failed(x.clone(), true, 200)).unwrap();
+        store.insert_failure(failed(x, false, 400)).unwrap();
+
+        // Reloading gives the same numbers as the live store
+        let reloaded = AnalysisStore::load(dir.to_str().unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        for store in [&store, &reloaded] {
+            let m = ExperimentMetrics::from_store(store).get("a", "x");
+            assert_eq!((m.requests, m.succeeded, m.failed, m.parse_failures), (5, 3, 2, 1));
+            assert_eq!((m.timed, m.total_latency_ms, m.max_latency_ms), (4, 1000, 400));
+            assert!((m.total_cost_usd - 0.12).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn report_averages_over_the_right_counts() {
+        let experiments = [experiment("a", true, vec![variant("x", 1), variant("y", 1)])];
+        let x = assignment("a", "x");
+        let mut metrics = ExperimentMetrics::default();
+        metrics.record(&x, Outcome::Succeeded { cost_usd: 0.02 }, Some(100));
+        metrics.record(&x, Outcome::Succeeded { cost_usd: 0.04 }, Some(300));
+        metrics.record(&x, Outcome::ParseFailure, Some(200));
+        metrics.record(&x, Outcome::Failed, Some(400));
+        metrics.record(&x, Outcome::Failed, None);
+        let agreement = HashMap::from([(("a".to_string(), "x".to_string()), (1, 4))]);
+
+        let report = report(&experiments, &metrics, &agreement);
+        let x = &report[0].variants[0];
+        assert_eq!((x.metrics.requests, x.metrics.succeeded, x.metrics.failed), (5, 2, 3));
+        assert_eq!(x.metrics.max_latency_ms, 400);
+        // Cost is averaged over successes, latency over timed requests and
+        // parse failures over all of them
+        assert!((x.avg_cost_usd - 0.03).abs() < 1e-12);
+        assert_eq!(x.avg_latency_ms, 250.0);
+        assert_eq!(x.parse_failure_rate, 0.2);
+        assert_eq!((x.feedback_count, x.feedback_agreement), (4, Some(0.25)));
+
+        let y = &report[0].variants[1];
+        assert_eq!((y.metrics.requests, y.avg_cost_usd, y.feedback_agreement), (0, 0.0, None));
+    }
+}
@@ -0,0 +1,492 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use image::{Rgb, RgbImage};
+use serde::Serialize;
+
+use crate::candles::Candle;
+use crate::prefilter::{ChartFeatures, Trend};
+use crate::trend;
+
+/// Pixel extent of one candle found in a chart image; bounds are inclusive.
+#[derive(Debug, Clone, Copy)]
+pub struct CandleColumn {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+/// Colour distance (sum of channel differences) above which a pixel is
+/// treated as chart ink rather than background or faint gridlines.
+const INK_THRESHOLD: u32 = 90;
+/// Rows with more ink than this fraction are axes or horizontal gridlines.
+const LINE_ROW_FRACTION: f64 = 0.6;
+/// Vertical gaps up to this many pixels still belong to the same candle.
+const MAX_GAP: u32 = 3;
+/// A row is part of the body when its ink spans this much of the candle width.
+const BODY_SPAN: f64 = 0.6;
+/// Body colour clusters closer than this are one colour (outlined/hollow styles).
+const COLOR_SPLIT: f64 = 60.0;
+/// Reported as the vision model when the description came from extraction.
+pub const LOCAL_MODEL: &str = "local-cv";
+/// Extraction is trusted on its own only with this many evenly spaced candles.
+const MIN_CANDLES: usize = 3;
+const MAX_SPACING_CV: f64 = 0.2;
+
+/// Where the chart description comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VisionMode {
+    /// Always the vision model; local extraction only cross-checks it.
+    Model,
+    /// Always local extraction; Replicate is never called.
+    Local,
+    /// Local extraction when the chart segments cleanly, else the vision model.
+    Auto,
+}
+
+impl FromStr for VisionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "model" | "vl2" => Ok(VisionMode::Model),
+            "local" => Ok(VisionMode::Local),
+            "auto" => Ok(VisionMode::Auto),
+            other => Err(format!("Unknown vision mode {} (expected model, local or auto)", other)),
+        }
+    }
+}
+
+pub fn distance(a: &Rgb<u8>, b: &Rgb<u8>) -> u32 {
+    a.0.iter().zip(b.0.iter()).map(|(x, y)| x.abs_diff(*y) as u32).sum()
+}
+
+/// Most common colour among `pixels`, quantized so JPEG noise does not split
+/// one flat colour into many.
+pub fn dominant<'a>(pixels: impl Iterator<Item = &'a Rgb<u8>>) -> Rgb<u8> {
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in pixels {
+        *counts.entry(pixel.0.map(|c| c & 0xF0)).or_default() += 1;
+    }
+    let key = counts.into_iter().max_by_key(|(_, n)| *n).map(|(k, _)| k).unwrap_or([255; 3]);
+    Rgb(key.map(|c| c | 0x08))
+}
+
+/// Which pixels are chart ink, with axis and horizontal gridline rows cleared.
+struct InkMask {
+    width: u32,
+    height: u32,
+    ink: Vec<bool>,
+}
+
+impl InkMask {
+    fn new(img: &RgbImage) -> Self {
+        let (w, h) = img.dimensions();
+        // Ink must stand out from both the page background and its own column's
+        // dominant colour, so shaded bands and vertical gridlines do not count.
+        let page = dominant(
+            (0..w)
+                .flat_map(|x| [(x, 0), (x, h - 1)])
+                .chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]))
+                .map(|(x, y)| img.get_pixel(x, y)),
+        );
+        let columns: Vec<Rgb<u8>> = (0..w)
+            .map(|x| dominant((0..h).map(|y| img.get_pixel(x, y))))
+            .collect();
+
+        let mut ink = vec![false; (w * h) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                let pixel = img.get_pixel(x, y);
+                ink[(y * w + x) as usize] = distance(pixel, &page) > INK_THRESHOLD
+                    && distance(pixel, &columns[x as usize]) > INK_THRESHOLD;
+            }
+        }
+        for y in 0..h {
+            let row = &mut ink[(y * w) as usize..((y + 1) * w) as usize];
+            if row.iter().filter(|&&i| i).count() as f64 > w as f64 * LINE_ROW_FRACTION {
+                row.fill(false);
+            }
+        }
+
+        InkMask { width: w, height: h, ink }
+    }
+
+    fn get(&self, x: u32, y: u32) -> bool {
+        self.ink[(y * self.width + x) as usize]
+    }
+}
+
+fn columns(mask: &InkMask) -> Vec<CandleColumn> {
+    let (w, h) = (mask.width, mask.height);
+    let active: Vec<bool> = (0..w).map(|x| (0..h).any(|y| mask.get(x, y))).collect();
+
+    let mut runs = Vec::new();
+    let mut x = 0;
+    while x < w {
+        if !active[x as usize] {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < w && active[x as usize] {
+            x += 1;
+        }
+        if x - start >= 2 && x - start <= w / 4 {
+            runs.push((start, x - 1));
+        }
+    }
+
+    let mut candles = Vec::new();
+    for (left, right) in runs {
+        // The topmost ink segment across the run; anything below a gap is
+        // usually the volume bar.
+        let mut top = None;
+        let mut bottom = 0;
+        let mut gap = 0;
+        for y in 0..h {
+            let hit = (left..=right).any(|x| mask.get(x, y));
+            match (hit, top) {
+                (true, None) => {
+                    top = Some(y);
+                    bottom = y;
+                }
+                (true, Some(_)) => {
+                    bottom = y;
+                    gap = 0;
+                }
+                (false, Some(_)) => {
+                    gap += 1;
+                    if gap > MAX_GAP {
+                        break;
+                    }
+                }
+                (false, None) => {}
+            }
+        }
+        if let Some(top) = top {
+            candles.push(CandleColumn { left, right, top, bottom });
+        }
+    }
+
+    // Drop runs much narrower than a typical candle (text, markers).
+    let mut widths: Vec<u32> = candles.iter().map(|c| c.right - c.left + 1).collect();
+    widths.sort_unstable();
+    if let Some(&median) = widths.get(widths.len() / 2) {
+        candles.retain(|c| (c.right - c.left + 1) * 5 >= median * 2);
+    }
+    candles
+}
+
+/// Candle bodies located by column scanning, left to right. Columns of ink at
+/// least two pixels wide become candles; 1px runs (gridlines, isolated wicks)
+/// and full-width rows (axes) are ignored.
+pub fn find_candles(img: &RgbImage) -> Vec<CandleColumn> {
+    let (w, h) = img.dimensions();
+    if w < 3 || h < 3 {
+        return Vec::new();
+    }
+    columns(&InkMask::new(img))
+}
+
+/// Body rows, fill ratio and mean colour of one candle.
+struct Body {
+    top: u32,
+    bottom: u32,
+    fill: f64,
+    color: [f64; 3],
+}
+
+fn measure_body(img: &RgbImage, mask: &InkMask, column: &CandleColumn) -> Body {
+    let width = column.right - column.left + 1;
+    let min_span = ((width as f64 * BODY_SPAN).ceil() as u32).max(2);
+    let span = |y: u32| {
+        let xs: Vec<u32> = (column.left..=column.right).filter(|&x| mask.get(x, y)).collect();
+        match (xs.first(), xs.last()) {
+            (Some(first), Some(last)) => last - first + 1,
+            _ => 0,
+        }
+    };
+
+    let rows: Vec<u32> = (column.top..=column.bottom).filter(|&y| span(y) >= min_span).collect();
+    let (top, bottom) = match (rows.first(), rows.last()) {
+        (Some(&t), Some(&b)) => (t, b),
+        // No wide row: a doji drawn as a wick only; use its widest row.
+        _ => {
+            let y = (column.top..=column.bottom).max_by_key(|&y| span(y)).unwrap_or(column.top);
+            (y, y)
+        }
+    };
+
+    let mut sum = [0.0; 3];
+    let mut inked = 0u32;
+    let mut interior = 0u32;
+    let mut interior_inked = 0u32;
+    for y in top..=bottom {
+        for x in column.left..=column.right {
+            let is_ink = mask.get(x, y);
+            if is_ink {
+                for (s, c) in sum.iter_mut().zip(img.get_pixel(x, y).0) {
+                    *s += c as f64;
+                }
+                inked += 1;
+            }
+            if x > column.left && x < column.right && y > top && y < bottom {
+                interior += 1;
+                interior_inked += is_ink as u32;
+            }
+        }
+    }
+
+    Body {
+        top,
+        bottom,
+        fill: if interior == 0 { 1.0 } else { interior_inked as f64 / interior as f64 },
+        color: sum.map(|s| s / inked.max(1) as f64),
+    }
+}
+
+fn color_distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum()
+}
+
+/// Splits bodies into bullish/bearish. Two distinct body colours: the greener
+/// (or, failing that, the lighter) one is bullish. One colour: hollow bodies
+/// are bullish, filled ones bearish.
+fn classify(bodies: &[Body]) -> Vec<bool> {
+    let colors: Vec<[f64; 3]> = bodies.iter().map(|b| b.color).collect();
+    let Some(first) = colors.first().copied() else {
+        return Vec::new();
+    };
+    let mut a = first;
+    let mut b = colors
+        .iter()
+        .copied()
+        .max_by(|x, y| color_distance(x, &first).total_cmp(&color_distance(y, &first)))
+        .unwrap_or(first);
+
+    for _ in 0..10 {
+        let (mut sa, mut na, mut sb, mut nb) = ([0.0; 3], 0.0, [0.0; 3], 0.0);
+        for c in &colors {
+            let (s, n) = if color_distance(c, &a) <= color_distance(c, &b) {
+                (&mut sa, &mut na)
+            } else {
+                (&mut sb, &mut nb)
+            };
+            for (acc, v) in s.iter_mut().zip(c) {
+                *acc += v;
+            }
+            *n += 1.0;
+        }
+        if na > 0.0 {
+            a = sa.map(|v| v / na);
+        }
+        if nb > 0.0 {
+            b = sb.map(|v| v / nb);
+        }
+    }
+
+    if color_distance(&a, &b) <= COLOR_SPLIT {
+        return bodies.iter().map(|body| body.fill < 0.5).collect();
+    }
+    let greenness = |c: &[f64; 3]| c[1] - c[0];
+    let lightness = |c: &[f64; 3]| c.iter().sum::<f64>();
+    let a_bullish = if (greenness(&a) - greenness(&b)).abs() >= 30.0 {
+        greenness(&a) > greenness(&b)
+    } else {
+        lightness(&a) > lightness(&b)
+    };
+    colors
+        .iter()
+        .map(|c| (color_distance(c, &a) <= color_distance(c, &b)) == a_bullish)
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CrossCheck {
+    pub extracted_candles: usize,
+    pub described_candles: Option<usize>,
+    pub extracted_trend: Option<Trend>,
+    pub described_trend: Option<Trend>,
+    /// Candle counts within one of each other and trends not contradicting.
+    pub agrees: bool,
+}
+
+/// Locally extracted candles, reported alongside the analysis.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocalExtraction {
+    /// Relative OHLC: prices run 0..100 across the detected candles.
+    pub candles: Vec<Candle>,
+    /// Coefficient of variation of the spacing between candle centres.
+    pub spacing_cv: f64,
+    pub reliable: bool,
+    /// The description sent to the reasoner came from these candles.
+    pub used_for_analysis: bool,
+    /// Comparison with the vision model's description, when it ran.
+    pub cross_check: Option<CrossCheck>,
+}
+
+impl LocalExtraction {
+    pub fn cross_check(&mut self, description: &str) {
+        let features = ChartFeatures::from_description(description);
+        let extracted_trend = trend::classify(&self.candles);
+        let counts_agree = features
+            .candle_count
+            .is_none_or(|n| n.abs_diff(self.candles.len()) <= 1);
+        let trends_agree = match (extracted_trend, features.trend) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        self.cross_check = Some(CrossCheck {
+            extracted_candles: self.candles.len(),
+            described_candles: features.candle_count,
+            extracted_trend,
+            described_trend: features.trend,
+            agrees: counts_agree && trends_agree,
+        });
+    }
+}
+
+/// Reconstructs relative OHLC from a chart image by colour segmentation and
+/// column scanning. Works on clean, conventionally styled charts.
+pub fn extract_candles(img: &RgbImage) -> Result<LocalExtraction, String> {
+    let (w, h) = img.dimensions();
+    if w < 3 || h < 3 {
+        return Err("Image too small for candle extraction".to_string());
+    }
+    let mask = InkMask::new(img);
+    let found = columns(&mask);
+    if found.is_empty() {
+        return Err("No candles found in the chart image".to_string());
+    }
+
+    let bodies: Vec<Body> = found.iter().map(|c| measure_body(img, &mask, c)).collect();
+    let bullish = classify(&bodies);
+
+    let y_top = found.iter().map(|c| c.top).min().unwrap_or(0) as f64;
+    let y_bottom = found.iter().map(|c| c.bottom).max().unwrap_or(0) as f64;
+    let scale = 100.0 / (y_bottom - y_top).max(1.0);
+    let price = |y: u32| ((y_bottom - y as f64) * scale * 100.0).round() / 100.0;
+
+    let candles: Vec<Candle> = found
+        .iter()
+        .zip(&bodies)
+        .zip(&bullish)
+        .map(|((column, body), &up)| {
+            let (upper, lower) = (price(body.top), price(body.bottom));
+            let (open, close) = if up { (lower, upper) } else { (upper, lower) };
+            Candle {
+                time: None,
+                open,
+                high: price(column.top),
+                low: price(column.bottom),
+                close,
+                volume: None,
+            }
+        })
+        .collect();
+
+    let centres: Vec<f64> = found.iter().map(|c| (c.left + c.right) as f64 / 2.0).collect();
+    let gaps: Vec<f64> = centres.windows(2).map(|p| p[1] - p[0]).collect();
+    let spacing_cv = if gaps.is_empty() {
+        0.0
+    } else {
+        let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+        let var = gaps.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / gaps.len() as f64;
+        var.sqrt() / mean.max(1.0)
+    };
+
+    Ok(LocalExtraction {
+        reliable: candles.len() >= MIN_CANDLES && spacing_cv <= MAX_SPACING_CV,
+        candles,
+        spacing_cv,
+        used_for_analysis: false,
+        cross_check: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{self, RenderOptions, THEMES};
+
+    /// A rising zig-zag: every third candle bearish, prices far enough apart
+    /// to survive the pixel grid.
+    fn series() -> Vec<Candle> {
+        (0..12)
+            .map(|i| {
+                let base = 100.0 + i as f64 * 4.0 - if i % 3 == 0 { 6.0 } else { 0.0 };
+                let (open, close) = if i % 3 == 0 { (base + 5.0, base) } else { (base, base + 5.0) };
+                Candle { time: None, open, high: base + 7.0, low: base - 2.0, close, volume: None }
+            })
+            .collect()
+    }
+
+    fn extract(candles: &[Candle], theme: &render::Theme, volume: bool) -> LocalExtraction {
+        let options = RenderOptions { theme, volume, ..RenderOptions::default() };
+        let png = render::render_png(candles, &options).unwrap();
+        extract_candles(&image::load_from_memory(&png).unwrap().to_rgb8()).unwrap()
+    }
+
+    /// Pairs ordered the same way in both series, for prices at least `gap` apart.
+    fn same_order(a: &[f64], b: &[f64], gap: f64) -> bool {
+        (0..a.len()).all(|i| {
+            (0..a.len()).all(|j| (a[i] - a[j]).abs() < gap || (a[i] < a[j]) == (b[i] < b[j]))
+        })
+    }
+
+    #[test]
+    fn extracts_rendered_candles_in_every_theme() {
+        let candles = series();
+        for theme in THEMES {
+            let local = extract(&candles, theme, false);
+            assert_eq!(local.candles.len(), candles.len(), "{} theme", theme.name);
+            assert!(local.reliable, "{} theme: spacing CV {}", theme.name, local.spacing_cv);
+            let bullish: Vec<bool> = local.candles.iter().map(Candle::is_bullish).collect();
+            let expected: Vec<bool> = candles.iter().map(Candle::is_bullish).collect();
+            assert_eq!(bullish, expected, "{} theme", theme.name);
+
+            let field = |series: &[Candle], f: fn(&Candle) -> f64| series.iter().map(f).collect::<Vec<f64>>();
+            for (name, f) in [
+                ("open", (|c: &Candle| c.open) as fn(&Candle) -> f64),
+                ("high", |c| c.high),
+                ("low", |c| c.low),
+                ("close", |c| c.close),
+            ] {
+                assert!(
+                    same_order(&field(&candles, f), &field(&local.candles, f), 1.0),
+                    "{} theme: {} order differs",
+                    theme.name,
+                    name
+                );
+            }
+            for c in &local.candles {
+                assert!(c.low <= c.open.min(c.close) && c.high >= c.open.max(c.close), "{} theme: {:?}", theme.name, c);
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_the_volume_pane() {
+        let candles: Vec<Candle> = series()
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| Candle { volume: Some(100.0 + i as f64 * 10.0), ..c })
+            .collect();
+        let local = extract(&candles, &THEMES[0], true);
+        assert_eq!(local.candles.len(), candles.len());
+        // Prices span 0..100 over the candles alone, so no volume bar was taken as a wick
+        assert_eq!(local.candles[0].low, 0.0);
+        assert_eq!(local.candles[11].high, 100.0);
+    }
+
+    #[test]
+    fn rejects_blank_images() {
+        let blank = RgbImage::from_pixel(200, 200, Rgb([255, 255, 255]));
+        assert!(extract_candles(&blank).is_err());
+        assert!(find_candles(&blank).is_empty());
+        assert!(extract_candles(&RgbImage::new(2, 2)).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::accuracy::AccuracyReport;
+use crate::retrieval::LabelledExample;
+use crate::store::{now_secs, AnalysisStore, Feedback, StoredAnalysis};
+use crate::{ApiError, AppState};
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/analyses/{id}", get(get_analysis))
+        .route("/analyses/{id}/feedback", post(submit_feedback))
+        .route("/feedback/stats", get(stats))
+}
+
+async fn get_analysis(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<StoredAnalysis>, ApiError> {
+    state
+        .store
+        .read()
+        .await
+        .get(&id)
+        .cloned()
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown analysis: {}", id)))
+}
+
+#[derive(Deserialize)]
+struct FeedbackRequest {
+    /// The pattern the chart really shows. May be omitted when `correct` is true.
+    correct_pattern: Option<String>,
+    /// Shorthand for "the prediction was right".
+    #[serde(default)]
+    correct: bool,
+    comment: Option<String>,
+}
+
+async fn submit_feedback(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<FeedbackRequest>,
+) -> Result<(StatusCode, Json<Feedback>), ApiError> {
+    let analysis = state
+        .store
+        .read()
+        .await
+        .get(&id)
+        .cloned()
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown analysis: {}", id)))?;
+
+    let correct_pattern = match (body.correct_pattern.as_deref(), body.correct) {
+        (Some(name), _) => {
+            let taxonomy = state.taxonomy(Some(&analysis.taxonomy)).await?;
+            taxonomy
+                .find(name.trim())
+                .map(|p| p.name.clone())
+                .ok_or((
+                    StatusCode::BAD_REQUEST,
+                    format!("{} is not a pattern in taxonomy {}", name, analysis.taxonomy),
+                ))?
+        }
+        (None, true) => analysis.pattern.clone(),
+        (None, false) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Provide correct_pattern or set correct to true".to_string(),
+            ))
+        }
+    };
+
+    let feedback = Feedback {
+        analysis_id: id,
+        timestamp: now_secs(),
+        correct: correct_pattern == analysis.pattern,
+        correct_pattern,
+        comment: body.comment.filter(|c| !c.trim().is_empty()),
+    };
+
+    state
+        .store
+        .write()
+        .await
+        .add_feedback(feedback.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.examples.write().await.upsert(LabelledExample {
+        id: feedback.analysis_id.clone(),
+        pattern: feedback.correct_pattern.clone(),
+        description: analysis.chart_description.clone(),
+    });
+
+    info!(
+        "Feedback for {}: predicted {} / actual {}",
+        feedback.analysis_id, analysis.pattern, feedback.correct_pattern
+    );
+
+    Ok((StatusCode::CREATED, Json(feedback)))
+}
+
+#[derive(Deserialize)]
+struct StatsQuery {
+    taxonomy: Option<String>,
+}
+
+#[derive(Serialize)]
+struct FeedbackStats {
+    taxonomy: Option<String>,
+    #[serde(flatten)]
+    report: AccuracyReport,
+}
+
+async fn stats(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StatsQuery>,
+) -> Json<FeedbackStats> {
+    let store = state.store.read().await;
+    let pairs = store
+        .with_feedback()
+        .filter(|(a, _)| query.taxonomy.as_ref().is_none_or(|t| &a.taxonomy == t))
+        .map(|(a, f)| (a.pattern.as_str(), f.correct_pattern.as_str()));
+
+    Json(FeedbackStats {
+        taxonomy: query.taxonomy.clone(),
+        report: AccuracyReport::from_pairs(pairs),
+    })
+}
+
+/// `(agreed, total)` feedback counts per `(experiment, variant)`.
+pub fn experiment_agreement(store: &AnalysisStore) -> HashMap<(String, String), (u64, u64)> {
+    let mut agreement: HashMap<(String, String), (u64, u64)> = HashMap::new();
+    for (analysis, feedback) in store.with_feedback() {
+        if let Some(assignment) = &analysis.experiment {
+            let entry = agreement
+                .entry((assignment.experiment.clone(), assignment.variant.clone()))
+                .or_default();
+            entry.1 += 1;
+            if feedback.correct {
+                entry.0 += 1;
+            }
+        }
+    }
+    agreement
+}
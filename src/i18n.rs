@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// Language of the canonical taxonomy. Needs no translation file.
+pub const DEFAULT_LANG: &str = "en";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternTranslation {
+    pub name: String,
+    pub description: String,
+}
+
+/// Per-language pattern translations keyed by canonical (English) pattern
+/// name, so one file covers every taxonomy that uses that name.
+#[derive(Debug, Default)]
+pub struct Translations {
+    langs: BTreeMap<String, HashMap<String, PatternTranslation>>,
+}
+
+impl Translations {
+    /// Loads every `<lang>.csv` in `dir` (columns: Pattern Name, Name, Description).
+    pub fn load(dir: &str) -> Self {
+        let mut translations = Translations::default();
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Locale directory {} not readable: {}", dir, e);
+                return translations;
+            }
+        };
+
+        for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+            if path.extension().and_then(|e| e.to_str()) != Some("csv") {
+                continue;
+            }
+            let Some(lang) = path.file_stem().and_then(|s| s.to_str()).map(normalize_lang) else {
+                continue;
+            };
+            match load_locale(&path) {
+                Ok(entries) => {
+                    info!("Loaded {} pattern translations for {:?}", entries.len(), lang);
+                    translations.langs.insert(lang, entries);
+                }
+                Err(e) => warn!("Skipping locale {}: {}", path.display(), e),
+            }
+        }
+
+        translations
+    }
+
+    pub fn supports(&self, lang: &str) -> bool {
+        lang == DEFAULT_LANG || self.langs.contains_key(lang)
+    }
+
+    pub fn languages(&self) -> Vec<String> {
+        std::iter::once(DEFAULT_LANG.to_string())
+            .chain(self.langs.keys().cloned())
+            .collect()
+    }
+
+    pub fn get(&self, lang: &str, pattern: &str) -> Option<&PatternTranslation> {
+        self.langs.get(lang)?.get(pattern)
+    }
+}
+
+fn load_locale(path: &Path) -> Result<HashMap<String, PatternTranslation>, String> {
+    let mut reader = csv::Reader::from_path(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut entries = HashMap::new();
+
+    for result in reader.records() {
+        let record = result.map_err(|e| format!("Failed to read CSV record: {}", e))?;
+        if record.len() >= 3 {
+            entries.insert(
+                record[0].to_string(),
+                PatternTranslation {
+                    name: record[1].to_string(),
+                    description: record[2].to_string(),
+                },
+            );
+        }
+    }
+
+    Ok(entries)
+}
+
+/// "th-TH", "TH" and "th_th" all map to "th".
+pub fn normalize_lang(lang: &str) -> String {
+    lang.trim()
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Human-readable language name used when instructing the reasoner.
+pub fn language_name(lang: &str) -> &str {
+    match lang {
+        "en" => "English",
+        "th" => "Thai",
+        "zh" => "Chinese",
+        "ja" => "Japanese",
+        "vi" => "Vietnamese",
+        other => other,
+    }
+}
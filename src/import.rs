@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::candles::{self, Candle};
+
+/// Where a CSV export came from; decides column layout and time format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// `time,open,high,low,close,volume`, as written by `save_csv`.
+    Generic,
+    /// Chart export: `time,open,high,low,close[,Volume]` plus indicator columns.
+    TradingView,
+    /// MT4 history (`2024.01.02,14:30,o,h,l,c,v`, no header) or MT5 export
+    /// (tab-separated `<DATE> <TIME> <OPEN> ...`). Times are broker server time.
+    MetaTrader,
+    /// Kline dumps: open time in ms or µs, then o,h,l,c,v and 6 more columns.
+    Binance,
+    /// `Date,Open,High,Low,Close,Adj Close,Volume`; `null` rows are skipped.
+    Yahoo,
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace([' ', '_', '-'], "").as_str() {
+            "generic" | "csv" => Ok(Source::Generic),
+            "tradingview" | "tv" => Ok(Source::TradingView),
+            "metatrader" | "mt4" | "mt5" => Ok(Source::MetaTrader),
+            "binance" => Ok(Source::Binance),
+            "yahoo" | "yahoofinance" => Ok(Source::Yahoo),
+            other => Err(format!(
+                "Unknown OHLC source {} (expected generic, tradingview, metatrader, binance or yahoo)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Source::Generic => "generic",
+            Source::TradingView => "tradingview",
+            Source::MetaTrader => "metatrader",
+            Source::Binance => "binance",
+            Source::Yahoo => "yahoo",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Detected from the content when absent.
+    pub source: Option<Source>,
+    /// Seconds east of UTC that timestamps without an offset are in. One fixed
+    /// offset for the whole file: MetaTrader server time that moves with DST
+    /// comes out an hour off on one side of the change.
+    pub utc_offset: i64,
+}
+
+#[derive(Debug)]
+pub struct Imported {
+    pub source: Source,
+    pub candles: Vec<Candle>,
+    /// Rows without prices (Yahoo writes `null` for non-trading days).
+    pub skipped: usize,
+}
+
+/// Columns of one export layout.
+struct Layout {
+    header: bool,
+    time: Option<usize>,
+    /// Separate time-of-day column (MetaTrader).
+    clock: Option<usize>,
+    prices: [usize; 4],
+    volume: Option<usize>,
+}
+
+/// Parses `Z`, `UTC`, `+02:00`, `-0500`, `+2` or `UTC+2` into seconds east of UTC.
+pub fn parse_offset(s: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid UTC offset {} (expected e.g. +02:00, -0500 or UTC)", s);
+    let trimmed = s.trim();
+    let rest = trimmed
+        .strip_prefix("UTC")
+        .or_else(|| trimmed.strip_prefix("GMT"))
+        .unwrap_or(trimmed);
+    if rest.is_empty() || rest == "Z" {
+        return Ok(0);
+    }
+    let (sign, digits) = if let Some(digits) = rest.strip_prefix('+') {
+        (1, digits)
+    } else if let Some(digits) = rest.strip_prefix('-') {
+        (-1, digits)
+    } else {
+        return Err(invalid());
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit() || b == b':') {
+        return Err(invalid());
+    }
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    let (hours, minutes): (i64, i64) = (
+        hours.parse().map_err(|_| invalid())?,
+        minutes.parse().map_err(|_| invalid())?,
+    );
+    if hours > 14 || minutes >= 60 {
+        return Err(invalid());
+    }
+    Ok(sign * (hours * 3_600 + minutes * 60))
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date (Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Year 10000; larger numeric times are not timestamps.
+const MAX_UNIX_SECONDS: f64 = 253_402_300_800.0;
+
+/// Unix seconds from a unix time in s/ms/µs (told apart by magnitude) or a
+/// `YYYY-MM-DD[ HH:MM[:SS[.fff]]][Z|±HH:MM]` date, with `.` or `/` also
+/// accepted between date parts. Dates without an offset are at `utc_offset`.
+pub fn parse_time(s: &str, utc_offset: i64) -> Result<i64, String> {
+    let s = s.trim();
+    let invalid = || format!("Invalid time {}", s);
+    if let Ok(number) = s.parse::<f64>() {
+        // f64 parsing also takes "NaN" and "inf"; neither is a time.
+        if !number.is_finite() {
+            return Err(invalid());
+        }
+        let seconds = if number < 1e11 {
+            number
+        } else if number < 1e14 {
+            number / 1e3
+        } else {
+            number / 1e6
+        };
+        if seconds.abs() >= MAX_UNIX_SECONDS {
+            return Err(invalid());
+        }
+        return Ok(seconds.floor() as i64);
+    }
+
+    let date = s.get(..10).ok_or_else(invalid)?;
+    let bytes = date.as_bytes();
+    if !date.is_ascii() || !matches!(bytes[4], b'-' | b'.' | b'/') || bytes[7] != bytes[4] {
+        return Err(invalid());
+    }
+    let number = |range: std::ops::Range<usize>| date[range].parse::<i64>().map_err(|_| invalid());
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(invalid());
+    }
+
+    let rest = s[10..].trim_start_matches(['T', ' ']);
+    let zone_at = rest
+        .char_indices()
+        .find(|&(i, c)| c == 'Z' || c == 'U' || ((c == '+' || c == '-') && i > 0))
+        .map_or(rest.len(), |(i, _)| i);
+    let (clock, zone) = rest.split_at(zone_at);
+    let mut seconds = 0;
+    if !clock.trim().is_empty() {
+        let mut parts = clock.trim().split(':');
+        for (unit, limit) in [(3_600, 24.0), (60, 60.0), (1, 61.0)] {
+            let Some(part) = parts.next() else { break };
+            let value: f64 = part
+                .parse()
+                .ok()
+                .filter(|v| (0.0..limit).contains(v))
+                .ok_or_else(invalid)?;
+            seconds += (value * unit as f64) as i64;
+        }
+    }
+    let offset = if zone.trim().is_empty() {
+        utc_offset
+    } else {
+        parse_offset(zone)?
+    };
+    Ok(days_from_civil(year, month, day) * 86_400 + seconds - offset)
+}
+
+/// Column names without MetaTrader's angle brackets, case or separators.
+fn normalize(name: &str) -> String {
+    name.trim()
+        .trim_matches(['<', '>', '"'])
+        .to_lowercase()
+        .replace([' ', '_', '-'], "")
+}
+
+fn delimiter(first_line: &str) -> u8 {
+    [b'\t', b';', b',']
+        .into_iter()
+        .max_by_key(|&d| first_line.bytes().filter(|&b| b == d).count())
+        .unwrap_or(b',')
+}
+
+fn looks_like_date(field: &str) -> bool {
+    parse_time(field, 0).is_ok() && field.trim().parse::<f64>().is_err()
+}
+
+/// Source of an export, from its header (or first row when headerless).
+pub fn detect(text: &str) -> Result<Source, String> {
+    let first = text.lines().find(|l| !l.trim().is_empty()).ok_or("Empty OHLC file")?;
+    let fields: Vec<&str> = first.split(delimiter(first) as char).collect();
+    let names: Vec<String> = fields.iter().map(|f| normalize(f)).collect();
+    let has = |name: &str| names.iter().any(|n| n == name);
+
+    if first.contains("<OPEN>") || first.contains("<open>") {
+        return Ok(Source::MetaTrader);
+    }
+    if has("open") && has("close") {
+        return Ok(if has("adjclose") || fields[0].trim() == "Date" {
+            Source::Yahoo
+        } else if has("opentime") || has("closetime") {
+            Source::Binance
+        } else if fields.iter().any(|f| f.trim() == "Volume") || !names.iter().all(|n| {
+            matches!(n.as_str(), "time" | "open" | "high" | "low" | "close" | "volume")
+        }) {
+            Source::TradingView
+        } else {
+            Source::Generic
+        });
+    }
+
+    // Headerless dumps
+    let leading = fields[0].trim();
+    if leading.len() >= 13 && leading.bytes().all(|b| b.is_ascii_digit()) && fields.len() >= 6 {
+        return Ok(Source::Binance);
+    }
+    if looks_like_date(leading) && leading.as_bytes().get(4) == Some(&b'.') {
+        return Ok(Source::MetaTrader);
+    }
+    Err(format!("Could not recognise the OHLC format from its first line: {}", first.trim()))
+}
+
+fn layout(source: Source, first: &[String]) -> Result<Layout, String> {
+    let names: HashMap<String, usize> = first
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (normalize(name), i))
+        .collect();
+    let column = |candidates: &[&str]| candidates.iter().find_map(|c| names.get(*c).copied());
+    let header = column(&["open"]).is_some();
+
+    if !header {
+        return match source {
+            Source::Binance => Ok(Layout { header, time: Some(0), clock: None, prices: [1, 2, 3, 4], volume: Some(5) }),
+            // MT4 history: date, time, open, high, low, close, volume
+            Source::MetaTrader if first.len() >= 7 => {
+                Ok(Layout { header, time: Some(0), clock: Some(1), prices: [2, 3, 4, 5], volume: Some(6) })
+            }
+            Source::MetaTrader => Ok(Layout { header, time: Some(0), clock: None, prices: [1, 2, 3, 4], volume: first.get(5).map(|_| 5) }),
+            _ => Err(format!("A {} export needs a header row", source)),
+        };
+    }
+
+    let price = |name: &str| column(&[name]).ok_or_else(|| format!("No {} column in the header", name));
+    let time = match source {
+        Source::MetaTrader => column(&["date", "time"]),
+        Source::Binance => column(&["opentime", "time", "timestamp"]),
+        _ => column(&["time", "date", "datetime", "timestamp"]),
+    };
+    Ok(Layout {
+        header,
+        time,
+        clock: (source == Source::MetaTrader && column(&["date"]).is_some())
+            .then(|| column(&["time"]))
+            .flatten(),
+        prices: [price("open")?, price("high")?, price("low")?, price("close")?],
+        // MT5's real <VOL> is zero for most forex and CFD symbols; tick volume is always set
+        volume: column(&["volume", "tickvol", "vol"]),
+    })
+}
+
+/// Reads an export into validated candles in time order. Descending exports
+/// are reversed; unordered or duplicate timestamps are rejected.
+pub fn parse(text: &str, options: &ImportOptions) -> Result<Imported, String> {
+    let source = match options.source {
+        Some(source) => source,
+        None => detect(text)?,
+    };
+    let first_line = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    let delimiter = delimiter(first_line);
+    // Semicolon-separated exports use decimal commas.
+    let decimal_comma = delimiter == b';';
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let mut rows = reader.records();
+    let first = rows
+        .next()
+        .ok_or("Empty OHLC file")?
+        .map_err(|e| format!("Invalid OHLC row 1: {}", e))?;
+    let first: Vec<String> = first.iter().map(str::to_string).collect();
+    let layout = layout(source, &first)?;
+
+    let mut candles = Vec::new();
+    let mut skipped = 0;
+    let records = rows.map(|r| r.map(|r| r.iter().map(str::to_string).collect::<Vec<String>>()));
+    let all = (!layout.header).then(|| Ok(first.clone())).into_iter().chain(records);
+    for (i, row) in all.enumerate() {
+        let n = i + if layout.header { 2 } else { 1 };
+        let row = row.map_err(|e| format!("Invalid OHLC row {}: {}", n, e))?;
+        if row.iter().all(|f| f.is_empty()) {
+            continue;
+        }
+        let field = |index: usize| row.get(index).map(String::as_str).unwrap_or("");
+        let number = |index: usize| -> Option<f64> {
+            let value = field(index);
+            if decimal_comma {
+                value.replace(',', ".").parse().ok()
+            } else {
+                value.parse().ok()
+            }
+        };
+        let prices: Vec<Option<f64>> = layout.prices.iter().map(|&i| number(i)).collect();
+        if prices.iter().any(Option::is_none) {
+            if layout.prices.iter().all(|&i| matches!(field(i), "" | "null" | "NaN")) {
+                skipped += 1;
+                continue;
+            }
+            return Err(format!("Invalid OHLC row {}: prices must be numbers", n));
+        }
+        let time = match layout.time {
+            Some(index) => {
+                let mut stamp = field(index).to_string();
+                if let Some(clock) = layout.clock {
+                    stamp = format!("{} {}", stamp, field(clock));
+                }
+                Some(
+                    parse_time(&stamp, options.utc_offset)
+                        .map_err(|e| format!("Invalid OHLC row {}: {}", n, e))?,
+                )
+            }
+            None => None,
+        };
+        candles.push(Candle {
+            time,
+            open: prices[0].unwrap_or_default(),
+            high: prices[1].unwrap_or_default(),
+            low: prices[2].unwrap_or_default(),
+            close: prices[3].unwrap_or_default(),
+            volume: layout.volume.and_then(number).filter(|v| v.is_finite()),
+        });
+    }
+
+    let times: Vec<i64> = candles.iter().filter_map(|c| c.time).collect();
+    if times.len() == candles.len() && times.windows(2).all(|w| w[0] > w[1]) {
+        candles.reverse();
+    }
+    if let Some(i) = candles
+        .windows(2)
+        .position(|w| matches!((w[0].time, w[1].time), (Some(a), Some(b)) if b <= a))
+    {
+        return Err(format!("Candle {} is not after the one before it", i + 1));
+    }
+    candles::validate(&candles)?;
+    Ok(Imported { source, candles, skipped })
+}
+
+/// OHLC in a JSON request body: `candles` directly, or a `csv` export with
+/// an optional `source` and fixed `utc_offset` (see `ImportOptions`).
+#[derive(Debug, Deserialize)]
+pub struct OhlcInput {
+    #[serde(default)]
+    candles: Vec<Candle>,
+    csv: Option<String>,
+    source: Option<Source>,
+    utc_offset: Option<String>,
+}
+
+impl OhlcInput {
+    pub fn into_candles(self) -> Result<Vec<Candle>, String> {
+        let Some(csv) = self.csv else {
+            candles::validate(&self.candles)?;
+            return Ok(self.candles);
+        };
+        if !self.candles.is_empty() {
+            return Err("Send either candles or csv, not both".to_string());
+        }
+        let options = ImportOptions {
+            source: self.source,
+            utc_offset: self.utc_offset.as_deref().map(parse_offset).transpose()?.unwrap_or(0),
+        };
+        Ok(parse(&csv, &options)?.candles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-02 14:30:00 UTC
+    const STAMP: i64 = 1_704_205_800;
+
+    fn import(text: &str) -> Imported {
+        parse(text, &ImportOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn detects_each_source() {
+        let cases = [
+            ("time,open,high,low,close,volume\n", Source::Generic),
+            ("time,open,high,low,close,Volume\n", Source::TradingView),
+            ("time,open,high,low,close,MA 20\n", Source::TradingView),
+            ("<DATE>\t<TIME>\t<OPEN>\t<HIGH>\t<LOW>\t<CLOSE>\t<TICKVOL>\n", Source::MetaTrader),
+            ("2024.01.02,14:30,1.1,1.2,1.0,1.15,120\n", Source::MetaTrader),
+            ("open_time,open,high,low,close,volume,close_time\n", Source::Binance),
+            ("1704205800000,1,2,0.5,1.5,10,1704205859999,15,3,5,7,0\n", Source::Binance),
+            ("Date,Open,High,Low,Close,Adj Close,Volume\n", Source::Yahoo),
+        ];
+        for (text, source) in cases {
+            assert_eq!(detect(text), Ok(source), "{}", text);
+        }
+        assert!(detect("").is_err());
+        assert!(detect("hello,world\n").is_err());
+    }
+
+    #[test]
+    fn parses_offsets() {
+        for (text, seconds) in [
+            ("Z", 0),
+            ("UTC", 0),
+            ("+02:00", 7_200),
+            ("-0500", -18_000),
+            ("+2", 7_200),
+            ("UTC+2", 7_200),
+            ("GMT-03:30", -12_600),
+        ] {
+            assert_eq!(parse_offset(text), Ok(seconds), "{}", text);
+        }
+        for text in ["\u{2212}02:00", "+\u{2212}0", "02:00", "+15", "+02:60", "++2", "+"] {
+            assert!(parse_offset(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn parses_times() {
+        for (text, offset, seconds) in [
+            ("1704205800", 0, STAMP),
+            ("1704205800000", 0, STAMP),
+            ("1704205800000000", 0, STAMP),
+            ("2024-01-02 14:30", 0, STAMP),
+            ("2024-01-02T14:30:00Z", 3_600, STAMP),
+            ("2024.01.02 16:30:00", 7_200, STAMP),
+            ("2024/01/02 09:30:00-05:00", 0, STAMP),
+            ("2024-01-02", 0, STAMP - 14 * 3_600 - 30 * 60),
+            ("2024-02-29", 0, 1_709_164_800),
+            ("2000-02-29", 0, 951_782_400),
+            ("2024-04-30", 0, 1_714_435_200),
+        ] {
+            assert_eq!(parse_time(text, offset), Ok(seconds), "{}", text);
+        }
+        for text in [
+            "NaN",
+            "inf",
+            "-inf",
+            "1e300",
+            "2024-13-02",
+            "2024-02-31",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-01-32",
+            "2024-01-02 25:00",
+            "2024-01-02 1e300",
+            "2024-\u{e9}1-02",
+            "2024-01-02 14:30\u{2212}02:00",
+        ] {
+            assert!(parse_time(text, 0).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn reads_mt4_history_in_server_time() {
+        let options = ImportOptions { source: None, utc_offset: 7_200 };
+        let imported = parse("2024.01.02,16:30,1.10,1.12,1.09,1.11,120\n2024.01.02,16:31,1.11,1.13,1.10,1.12,80\n", &options).unwrap();
+        assert_eq!(imported.source, Source::MetaTrader);
+        assert_eq!(imported.candles[0].time, Some(STAMP));
+        assert_eq!(imported.candles[1].volume, Some(80.0));
+    }
+
+    #[test]
+    fn reads_mt5_export_with_tick_volume() {
+        let text = "<DATE>\t<TIME>\t<OPEN>\t<HIGH>\t<LOW>\t<CLOSE>\t<TICKVOL>\t<VOL>\t<SPREAD>\n\
+                    2024.01.02\t14:30:00\t1.10\t1.12\t1.09\t1.11\t120\t0\t2\n";
+        let candles = import(text).candles;
+        assert_eq!(candles[0].time, Some(STAMP));
+        assert_eq!(candles[0].close, 1.11);
+        assert_eq!(candles[0].volume, Some(120.0));
+    }
+
+    #[test]
+    fn reads_binance_microseconds() {
+        let text = "1704205800000000,42000,42100,41900,42050,12.5,1704205859999999,525000,100,6,252000,0\n\
+                    1704205860000000,42050,42150,42000,42100,8,1704205919999999,336000,80,4,168000,0\n";
+        let imported = import(text);
+        assert_eq!(imported.source, Source::Binance);
+        assert_eq!(imported.candles[0].time, Some(STAMP));
+        assert_eq!(imported.candles[1].time, Some(STAMP + 60));
+        assert_eq!(imported.candles[0].volume, Some(12.5));
+    }
+
+    #[test]
+    fn skips_yahoo_null_rows() {
+        let text = "Date,Open,High,Low,Close,Adj Close,Volume\n\
+                    2024-01-02,10,11,9,10.5,10.5,1000\n\
+                    2024-01-03,null,null,null,null,null,null\n\
+                    2024-01-04,10.5,12,10,11,11,1200\n";
+        let imported = import(text);
+        assert_eq!(imported.source, Source::Yahoo);
+        assert_eq!(imported.skipped, 1);
+        assert_eq!(imported.candles.len(), 2);
+        assert_eq!(imported.candles[1].close, 11.0);
+    }
+
+    #[test]
+    fn reverses_descending_exports() {
+        let text = "time,open,high,low,close\n\
+                    2024-01-04,3,4,2,3.5\n\
+                    2024-01-03,2,3,1,2.5\n\
+                    2024-01-02,1,2,0.5,1.5\n";
+        let closes: Vec<f64> = import(text).candles.iter().map(|c| c.close).collect();
+        assert_eq!(closes, [1.5, 2.5, 3.5]);
+
+        let unordered = "time,open,high,low,close\n2024-01-02,1,2,0.5,1.5\n2024-01-04,3,4,2,3.5\n2024-01-03,2,3,1,2.5\n";
+        assert!(parse(unordered, &ImportOptions::default()).is_err());
+    }
+}
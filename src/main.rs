@@ -1,26 +1,66 @@
+mod accuracy;
+mod admin;
 mod analyzer;
+mod annotate;
+mod backtest;
+mod candles;
 mod config;
+mod ensemble;
+mod eval;
+mod experiments;
+mod extract;
+mod feedback;
+mod i18n;
+mod import;
 mod models;
+mod pipeline;
+mod prefilter;
+mod preprocess;
+mod prompts;
+mod render;
+mod resample;
+mod retrieval;
+mod rules;
+mod scan;
+mod store;
+mod synthetic;
+mod taxonomy;
+mod taxonomy_history;
+mod text;
+mod tracker;
+mod trend;
 mod vision;
+mod volume;
 
 use axum::{
-    extract::{Multipart, State},
+    extract::{Multipart, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Json},
     routing::{get, post},
     Router,
 };
 use config::Config;
-use models::{AnalyzeResponse, CostBreakdown, Pattern};
+use i18n::{Translations, DEFAULT_LANG};
+use import::ImportOptions;
+use experiments::{Experiment, ExperimentMetrics};
+use models::PatternView;
+use pipeline::AnalysisPlan;
+use prefilter::PrefilterStats;
+use prompts::PromptLibrary;
+use retrieval::Bm25Index;
+use store::{AnalysisStore, FailedAnalysis, StoredAnalysis};
 use reqwest::Client;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use std::time::Instant;
+use taxonomy::{Taxonomy, TaxonomySummary, DEFAULT_TAXONOMY};
 use tokio::sync::RwLock;
 use tower_http::services::ServeDir;
+use tracker::Tracker;
 use tracing::{error, info, warn};
 
-// Replicate DeepSeek-VL2 pricing: Nvidia A100 80GB @ $0.001400/sec
-const REPLICATE_GPU_RATE: f64 = 0.001400;
+pub(crate) type ApiError = (StatusCode, String);
 
 #[derive(Clone, Serialize)]
 pub struct WarmupStatus {
@@ -32,7 +72,15 @@ pub struct WarmupStatus {
 struct AppState {
     config: Config,
     client: Client,
-    patterns: Vec<Pattern>,
+    taxonomies: RwLock<BTreeMap<String, Arc<Taxonomy>>>,
+    translations: Translations,
+    prefilter_stats: RwLock<PrefilterStats>,
+    prompts: RwLock<PromptLibrary>,
+    experiments: Vec<Experiment>,
+    store: RwLock<AnalysisStore>,
+    /// Feedback-confirmed descriptions for few-shot retrieval.
+    examples: RwLock<Bm25Index>,
+    tracker: RwLock<Tracker>,
     warmup: RwLock<WarmupStatus>,
 }
 
@@ -43,29 +91,90 @@ async fn main() {
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "info".into()),
         )
+        // stdout stays clean for commands that write results there
+        .with_writer(std::io::stderr)
         .init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("eval") => {
+            if let Err(e) = eval::run(build_state(), &args[1..]).await {
+                error!("Evaluation failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("generate") => {
+            if let Err(e) = synthetic::run(&args[1..]) {
+                error!("Generation failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("scan") => {
+            if let Err(e) = scan::run(&args[1..]) {
+                error!("Scan failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("backtest") => {
+            if let Err(e) = backtest::run(&args[1..]) {
+                error!("Backtest failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Some("serve") | None => serve(build_state()).await,
+        Some(other) => {
+            eprintln!("Unknown command: {}\n\nUsage: deepseek-test [serve | eval <manifest> ... | generate ... | scan <ohlc> ... | backtest <ohlc> ...]", other);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn build_state() -> Arc<AppState> {
     let config = Config::from_env();
-    let port = config.port;
 
-    let patterns = load_patterns("candlestick_patterns.csv");
-    info!("Loaded {} candlestick patterns", patterns.len());
+    let taxonomies = taxonomy::load_taxonomies(
+        "candlestick_patterns.csv",
+        &config.taxonomy_dir,
+        &config.taxonomy_history_dir,
+    );
+    info!(
+        "Loaded {} taxonomies ({} default patterns)",
+        taxonomies.len(),
+        taxonomies[DEFAULT_TAXONOMY].patterns.len()
+    );
+    let translations = Translations::load(&config.locale_dir);
+    let prompts = PromptLibrary::load(&config.prompt_dir);
+    let experiments = experiments::load_experiments(&config.experiments_file, &prompts);
+    let store = AnalysisStore::load(&config.data_dir).expect("Failed to open analysis store");
+    let examples = Bm25Index::build(retrieval::labelled_examples(&store));
+    let tracker = Tracker::load(&config.data_dir).expect("Failed to load tracked symbols");
 
     let client = Client::builder()
         .timeout(std::time::Duration::from_secs(300))
         .build()
         .expect("Failed to create HTTP client");
 
-    let state = Arc::new(AppState {
+    Arc::new(AppState {
         config,
         client,
-        patterns,
+        taxonomies: RwLock::new(taxonomies),
+        translations,
+        prefilter_stats: RwLock::new(PrefilterStats::default()),
+        prompts: RwLock::new(prompts),
+        experiments,
+        store: RwLock::new(store),
+        examples: RwLock::new(examples),
+        tracker: RwLock::new(tracker),
         warmup: RwLock::new(WarmupStatus {
             state: "starting".to_string(),
             message: "server starting...".to_string(),
             elapsed_secs: 0,
         }),
-    });
+    })
+}
+
+async fn serve(state: Arc<AppState>) {
+    let port = state.config.port;
 
     // Spawn background warmup
     let warmup_state = state.clone();
@@ -77,7 +186,17 @@ async fn main() {
         .route("/", get(index_handler))
         .route("/analyze", post(analyze_handler))
         .route("/patterns", get(patterns_handler))
+        .route("/taxonomies", get(taxonomies_handler))
+        .route("/prompts", get(prompts_handler))
+        .route("/experiments", get(experiments_handler))
         .route("/warmup", get(warmup_handler))
+        .route("/metrics/prefilter", get(prefilter_metrics_handler))
+        .merge(feedback::router())
+        .merge(render::router())
+        .merge(backtest::router())
+        .merge(resample::router())
+        .merge(tracker::router())
+        .nest("/admin", admin::router(state.clone()))
         .nest_service("/static", ServeDir::new("static"))
         .with_state(state);
 
@@ -235,25 +354,6 @@ async fn run_warmup(state: Arc<AppState>) {
     error!("Warmup timed out");
 }
 
-fn load_patterns(path: &str) -> Vec<Pattern> {
-    let mut reader = csv::Reader::from_path(path).expect("Failed to open CSV");
-    let mut patterns = Vec::new();
-
-    for result in reader.records() {
-        let record = result.expect("Failed to read CSV record");
-        if record.len() >= 4 {
-            patterns.push(Pattern {
-                name: record[0].to_string(),
-                category: record[1].to_string(),
-                direction: record[2].to_string(),
-                description: record[3].to_string(),
-            });
-        }
-    }
-
-    patterns
-}
-
 async fn index_handler() -> impl IntoResponse {
     let html = include_str!("../static/index.html");
     Html(html)
@@ -264,34 +364,100 @@ async fn warmup_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse
     Json(w.clone())
 }
 
-async fn patterns_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    Json(state.patterns.clone())
+#[derive(Deserialize)]
+struct TaxonomyQuery {
+    taxonomy: Option<String>,
+    lang: Option<String>,
 }
 
-async fn analyze_handler(
-    State(state): State<Arc<AppState>>,
-    mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // Check warmup status
-    {
-        let w = state.warmup.read().await;
-        if w.state != "ready" {
+impl AppState {
+    async fn taxonomy(&self, name: Option<&str>) -> Result<Arc<Taxonomy>, ApiError> {
+        let name = name.unwrap_or(DEFAULT_TAXONOMY);
+        self.taxonomies
+            .read()
+            .await
+            .get(name)
+            .cloned()
+            .ok_or((StatusCode::NOT_FOUND, format!("Unknown taxonomy: {}", name)))
+    }
+
+    fn lang(&self, lang: Option<&str>) -> Result<String, ApiError> {
+        let lang = lang.map(i18n::normalize_lang).unwrap_or_else(|| DEFAULT_LANG.to_string());
+        if !self.translations.supports(&lang) {
             return Err((
-                StatusCode::SERVICE_UNAVAILABLE,
-                format!("Model not ready: {}", w.message),
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Unsupported language: {} (available: {})",
+                    lang,
+                    self.translations.languages().join(", ")
+                ),
             ));
         }
+        Ok(lang)
     }
+}
+
+async fn prompts_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.prompts.read().await.summaries())
+}
+
+async fn experiments_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let store = state.store.read().await;
+    let agreement = feedback::experiment_agreement(&store);
+    let metrics = ExperimentMetrics::from_store(&store);
+    Json(experiments::report(&state.experiments, &metrics, &agreement))
+}
+
+async fn prefilter_metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.prefilter_stats.read().await.clone())
+}
 
+async fn patterns_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TaxonomyQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let taxonomy = state.taxonomy(query.taxonomy.as_deref()).await?;
+    let lang = state.lang(query.lang.as_deref())?;
+    let patterns: Vec<PatternView> = taxonomy
+        .patterns
+        .iter()
+        .map(|p| PatternView {
+            pattern: p.clone(),
+            localized: state.translations.get(&lang, &p.name).cloned(),
+        })
+        .collect();
+    Ok(Json(patterns))
+}
+
+async fn taxonomies_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let summaries: Vec<TaxonomySummary> = state
+        .taxonomies
+        .read()
+        .await
+        .values()
+        .map(|t| t.summary())
+        .collect();
+    Json(summaries)
+}
+
+async fn analyze_handler(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
     let mut image_bytes: Option<Vec<u8>> = None;
+    let mut ohlc: Option<String> = None;
     let mut content_type = "image/png".to_string();
+    let mut options: HashMap<String, String> = HashMap::new();
 
     while let Some(field) = multipart
         .next_field()
         .await
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Multipart error: {}", e)))?
     {
-        if field.name() == Some("image") {
+        let Some(name) = field.name().map(str::to_string) else {
+            continue;
+        };
+        if name == "image" {
             if let Some(ct) = field.content_type() {
                 content_type = ct.to_string();
             }
@@ -300,79 +466,99 @@ async fn analyze_handler(
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read image: {}", e)))?;
             image_bytes = Some(bytes.to_vec());
+        } else if name == "ohlc" {
+            let text = field
+                .text()
+                .await
+                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read ohlc: {}", e)))?;
+            ohlc = Some(text);
+        } else {
+            let text = field
+                .text()
+                .await
+                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read {}: {}", name, e)))?;
+            if !text.trim().is_empty() {
+                options.insert(name, text.trim().to_string());
+            }
         }
     }
 
-    let image_bytes = image_bytes
-        .ok_or((StatusCode::BAD_REQUEST, "No image field in request".to_string()))?;
-
-    if image_bytes.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Empty image".to_string()));
-    }
-
-    info!(
-        "Received image: {} bytes, type: {}",
-        image_bytes.len(),
-        content_type
-    );
+    let mut plan = AnalysisPlan::resolve(&state, &options).await?;
+    // Only requests that reach the vision model wait for its warmup
+    plan.require_warm_vision = true;
+
+    let started = Instant::now();
+    let candles = match ohlc {
+        Some(ohlc) => {
+            let bad_request = |e: String| (StatusCode::BAD_REQUEST, e);
+            let import_options = ImportOptions {
+                source: options.get("ohlc_source").map(|s| s.parse()).transpose().map_err(bad_request)?,
+                utc_offset: options
+                    .get("utc_offset")
+                    .map(|s| import::parse_offset(s))
+                    .transpose()
+                    .map_err(bad_request)?
+                    .unwrap_or(0),
+            };
+            let imported = import::parse(&ohlc, &import_options).map_err(bad_request)?;
+            info!(
+                "Received OHLC: {} candles from a {} export ({} empty rows skipped)",
+                imported.candles.len(),
+                imported.source,
+                imported.skipped
+            );
+            Some(imported.candles)
+        }
+        None => None,
+    };
 
-    // Stage 1: Vision — get chart description
-    let vision_result = vision::describe_chart(
-        &state.client,
-        &state.config.replicate_api_token,
-        &image_bytes,
-        &content_type,
-    )
-    .await
-    .map_err(|e| {
-        error!("Vision stage failed: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, format!("Vision analysis failed: {}", e))
-    })?;
-
-    let vision_cost = vision_result.predict_seconds * REPLICATE_GPU_RATE;
-    info!(
-        "Vision: {:.1}s predict time — ${:.6}",
-        vision_result.predict_seconds, vision_cost
-    );
-    info!(
-        "Chart description: {}",
-        &vision_result.description[..vision_result.description.len().min(200)]
-    );
+    let result = match (image_bytes, candles) {
+        (Some(image_bytes), candles) => {
+            if image_bytes.is_empty() {
+                return Err((StatusCode::BAD_REQUEST, "Empty image".to_string()));
+            }
+            info!(
+                "Received image: {} bytes, type: {}",
+                image_bytes.len(),
+                content_type
+            );
+            pipeline::analyze_image(&state, &plan, &image_bytes, candles.as_deref()).await
+        }
+        (None, Some(candles)) => pipeline::analyze_candles(&state, &plan, &candles).await,
+        (None, None) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "No image or ohlc field in request".to_string(),
+            ))
+        }
+    };
 
-    // Stage 2: Pattern analysis
-    let analysis = analyzer::analyze_pattern(
-        &state.client,
-        &state.config.deepseek_api_key,
-        &vision_result.description,
-        &state.patterns,
-    )
-    .await
-    .map_err(|e| {
-        error!("Analysis stage failed: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, format!("Pattern analysis failed: {}", e))
-    })?;
-
-    let total_cost = vision_cost + analysis.cost_usd;
-    info!("Total cost: ${:.6} (vision ${:.6} + reasoner ${:.6})", total_cost, vision_cost, analysis.cost_usd);
-
-    let response = AnalyzeResponse {
-        pattern: analysis.pattern,
-        category: analysis.category,
-        direction: analysis.direction,
-        confidence: analysis.confidence,
-        reasoning: analysis.reasoning,
-        chain_of_thought: analysis.chain_of_thought,
-        chart_description: vision_result.description,
-        cost: CostBreakdown {
-            vision_seconds: vision_result.predict_seconds,
-            vision_cost_usd: vision_cost,
-            reasoner_prompt_tokens: analysis.prompt_tokens,
-            reasoner_completion_tokens: analysis.completion_tokens,
-            reasoner_reasoning_tokens: analysis.reasoning_tokens,
-            reasoner_cost_usd: analysis.cost_usd,
-            total_cost_usd: total_cost,
-        },
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let response = match (result, &plan.experiment) {
+        (Ok(response), _) => response,
+        (Err(e), Some(assignment)) => {
+            let failure = FailedAnalysis {
+                timestamp: store::now_secs(),
+                experiment: assignment.clone(),
+                parse_failure: e.reasoner == Some(analyzer::ErrorKind::Parse),
+                latency_ms,
+            };
+            if let Err(err) = state.store.write().await.insert_failure(failure) {
+                warn!("Failed to store failed analysis: {}", err);
+            }
+            return Err(e.into());
+        }
+        (Err(e), None) => return Err(e.into()),
     };
+    if let Err(e) = state
+        .store
+        .write()
+        .await
+        .insert(StoredAnalysis::from_response(&response, latency_ms))
+    {
+        // The caller still gets the result; only feedback on it is lost.
+        warn!("Failed to store analysis {}: {}", response.id, e);
+    }
 
     Ok(Json(response))
 }
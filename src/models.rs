@@ -1,31 +1,361 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::ensemble::EnsembleReport;
+use crate::experiments::Assignment;
+use crate::extract::LocalExtraction;
+use crate::i18n::PatternTranslation;
+use crate::prefilter::PrefilterReport;
+use crate::preprocess::PreprocessReport;
+use crate::prompts::PromptRef;
+use crate::resample::MultiTimeframe;
+use crate::retrieval::SelectedExample;
+use crate::rules::Verification;
+use crate::trend::TrendContext;
+use crate::volume::VolumeConfirmation;
+use std::str::FromStr;
 
 // --- Domain types ---
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PatternCategory {
+    Single,
+    Two,
+    Three,
+    Multi,
+    Continuation,
+    Special,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Bullish,
+    Bearish,
+    Neutral,
+    Both,
+    #[serde(rename = "Bullish Continuation")]
+    BullishContinuation,
+    #[serde(rename = "Bearish Continuation")]
+    BearishContinuation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+/// Lowercases and drops everything but letters and digits, so "Bullish-Continuation",
+/// " bullish continuation " and "BULLISH_CONTINUATION" all compare equal.
+fn normalize_label(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl PatternCategory {
+    pub const ALL: [PatternCategory; 6] = [
+        PatternCategory::Single,
+        PatternCategory::Two,
+        PatternCategory::Three,
+        PatternCategory::Multi,
+        PatternCategory::Continuation,
+        PatternCategory::Special,
+    ];
+
+    /// Strict counterpart of `from_str` for taxonomy files: only the canonical
+    /// labels are accepted, ignoring case.
+    pub fn parse_exact(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown pattern category: {:?}", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PatternCategory::Single => "Single",
+            PatternCategory::Two => "Two",
+            PatternCategory::Three => "Three",
+            PatternCategory::Multi => "Multi",
+            PatternCategory::Continuation => "Continuation",
+            PatternCategory::Special => "Special",
+        }
+    }
+}
+
+impl FromStr for PatternCategory {
+    type Err = String;
+
+    /// Lenient: accepts case/punctuation variants, digits and the
+    /// "...-candle" phrasing the reasoner sometimes produces.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = normalize_label(s);
+        let key = key
+            .strip_suffix("candles")
+            .or_else(|| key.strip_suffix("candle"))
+            .unwrap_or(&key);
+        match key {
+            "single" | "one" | "1" => Ok(PatternCategory::Single),
+            "two" | "double" | "2" => Ok(PatternCategory::Two),
+            "three" | "triple" | "3" => Ok(PatternCategory::Three),
+            "multi" | "multiple" => Ok(PatternCategory::Multi),
+            "continuation" => Ok(PatternCategory::Continuation),
+            "special" => Ok(PatternCategory::Special),
+            _ => Err(format!("unknown pattern category: {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PatternCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::Bullish,
+        Direction::Bearish,
+        Direction::Neutral,
+        Direction::Both,
+        Direction::BullishContinuation,
+        Direction::BearishContinuation,
+    ];
+
+    /// Strict counterpart of `from_str` for taxonomy files: only the canonical
+    /// labels are accepted, ignoring case, so typos like "Bulish" are errors.
+    pub fn parse_exact(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|d| d.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown direction: {:?}", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Bullish => "Bullish",
+            Direction::Bearish => "Bearish",
+            Direction::Neutral => "Neutral",
+            Direction::Both => "Both",
+            Direction::BullishContinuation => "Bullish Continuation",
+            Direction::BearishContinuation => "Bearish Continuation",
+        }
+    }
+
+    /// +1 for patterns expecting a rise, -1 for a fall, `None` without a side.
+    pub fn sign(&self) -> Option<f64> {
+        match self {
+            Direction::Bullish | Direction::BullishContinuation => Some(1.0),
+            Direction::Bearish | Direction::BearishContinuation => Some(-1.0),
+            Direction::Neutral | Direction::Both => None,
+        }
+    }
+}
+
+/// Spellings of the two sides the lenient parser accepts, after `normalize_label`.
+const BULLISH_SPELLINGS: &[&str] = &["bullish", "bull", "bulish", "bullsh", "bullisch"];
+const BEARISH_SPELLINGS: &[&str] = &["bearish", "bear", "bearsih", "bearsh", "berish", "bearisch"];
+
+impl FromStr for Direction {
+    type Err = String;
+
+    /// Lenient: accepts case/punctuation variants, a trailing "reversal" or
+    /// "continuation" and the common misspellings in `BULLISH_SPELLINGS`/`BEARISH_SPELLINGS`
+    /// ("Bulish", "Bearsih"). Meant for model output; taxonomy files go
+    /// through [`Direction::parse_exact`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = normalize_label(s);
+        let (side, continuation) = match key.find("continu") {
+            Some(i) => (&key[..i], true),
+            None => (key.as_str(), false),
+        };
+        let side = side.strip_suffix("reversal").unwrap_or(side);
+        if BULLISH_SPELLINGS.contains(&side) {
+            return Ok(if continuation {
+                Direction::BullishContinuation
+            } else {
+                Direction::Bullish
+            });
+        }
+        if BEARISH_SPELLINGS.contains(&side) {
+            return Ok(if continuation {
+                Direction::BearishContinuation
+            } else {
+                Direction::Bearish
+            });
+        }
+        match key.as_str() {
+            "neutral" | "indecision" | "none" => Ok(Direction::Neutral),
+            "both" | "either" | "bidirectional" => Ok(Direction::Both),
+            _ => Err(format!("unknown direction: {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Confidence {
+    /// One level lower, bottoming out at Low.
+    pub fn downgrade(self) -> Self {
+        match self {
+            Confidence::High => Confidence::Medium,
+            _ => Confidence::Low,
+        }
+    }
+
+    /// One level higher, topping out at High.
+    pub fn upgrade(self) -> Self {
+        match self {
+            Confidence::Low => Confidence::Medium,
+            _ => Confidence::High,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Confidence::Low => "Low",
+            Confidence::Medium => "Medium",
+            Confidence::High => "High",
+        }
+    }
+}
+
+impl FromStr for Confidence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match normalize_label(s).as_str() {
+            "high" | "veryhigh" | "strong" => Ok(Confidence::High),
+            "medium" | "med" | "moderate" => Ok(Confidence::Medium),
+            "low" | "verylow" | "weak" => Ok(Confidence::Low),
+            _ => Err(format!("unknown confidence: {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Pattern {
     pub name: String,
-    pub category: String,
-    pub direction: String,
+    pub category: PatternCategory,
+    pub direction: Direction,
     pub description: String,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AnalyzeResponse {
+    /// Key for `/analyses/{id}` and feedback submission.
+    pub id: String,
+    pub taxonomy: String,
+    pub lang: String,
     pub pattern: String,
-    pub category: String,
-    pub direction: String,
-    pub confidence: String,
+    /// Pattern name/description in `lang`; absent for English or untranslated patterns.
+    pub localized: Option<PatternTranslation>,
+    pub category: Option<PatternCategory>,
+    pub direction: Option<Direction>,
+    pub confidence: Confidence,
     pub reasoning: String,
+    /// Next-best taxonomy patterns, most likely first.
+    pub alternatives: Vec<String>,
+    /// How many of the most recent candles form the pattern.
+    pub candle_count: Option<usize>,
+    /// Every pattern found in the chart, most recent first; only with `multiple`.
+    pub occurrences: Option<Vec<PatternOccurrence>>,
+    /// Patterns per resampled timeframe; only for OHLC input with `timeframes`.
+    pub timeframes: Option<MultiTimeframe>,
     pub chain_of_thought: Option<String>,
+    /// Pattern `reasoning` and `chain_of_thought` argue for, when verification
+    /// replaced it with another.
+    pub reasoning_for: Option<String>,
     pub chart_description: String,
+    /// Trend leading into the most recent candles, measured or described.
+    pub trend_context: Option<TrendContext>,
+    /// PNG data URL of the chart with the pattern candles boxed, when `annotate` was requested.
+    pub annotated_image: Option<String>,
+    /// What was done to the upload before the vision stage; absent for text-only input.
+    pub preprocessing: Option<PreprocessReport>,
+    /// Candles read from the image locally; absent for text-only input or unreadable charts.
+    pub local_extraction: Option<LocalExtraction>,
+    /// Vote over reasoner samples, when more than one was requested.
+    pub ensemble: Option<EnsembleReport>,
+    /// Geometric checks of the pattern against OHLC or extracted candles.
+    pub verification: Option<Verification>,
+    /// Volume of the pattern against its recent average, when known.
+    pub volume: Option<VolumeConfirmation>,
+    pub prefilter: PrefilterReport,
+    /// Confirmed past analyses injected into the reasoner prompt.
+    pub few_shot_examples: Vec<SelectedExample>,
+    pub prompts: PromptVersions,
+    pub models: ModelVersions,
+    /// Experiment arm that served this analysis, if any.
+    pub experiment: Option<Assignment>,
     pub cost: CostBreakdown,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OccurrenceSource {
+    Reasoner,
+    /// Found by the geometric rules on OHLC or extracted candles.
+    Rules,
+}
+
+/// One pattern found somewhere in the chart.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternOccurrence {
+    pub pattern: String,
+    pub direction: Option<Direction>,
+    /// Inclusive candle indexes, 0 = leftmost candle.
+    pub start: usize,
+    pub end: usize,
+    /// The reasoner's confidence; absent for rule-only findings.
+    pub confidence: Option<Confidence>,
+    pub sources: Vec<OccurrenceSource>,
+    /// The most recent occurrence, the one to act on.
+    pub actionable: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelVersions {
+    /// Replicate model version used for the vision stage, or `local-cv`.
+    pub vision: String,
+    /// Comma-separated when an ensemble used several models.
+    pub reasoner: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PromptVersions {
+    pub vision: PromptRef,
+    pub reasoner: PromptRef,
+}
+
+/// A taxonomy entry as served by `/patterns`, with its translation when a
+/// `lang` is requested.
+#[derive(Debug, Serialize)]
+pub struct PatternView {
+    #[serde(flatten)]
+    pub pattern: Pattern,
+    pub localized: Option<PatternTranslation>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct CostBreakdown {
     pub vision_seconds: f64,
     pub vision_cost_usd: f64,
+    /// Reasoner requests made; token counts and cost below are summed over them.
+    pub reasoner_calls: usize,
     pub reasoner_prompt_tokens: u64,
     pub reasoner_completion_tokens: u64,
     pub reasoner_reasoning_tokens: u64,
@@ -108,3 +438,80 @@ pub struct DeepSeekUsage {
     #[serde(default)]
     pub prompt_cache_hit_tokens: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_parsing() {
+        for (text, category) in [
+            ("Single", PatternCategory::Single),
+            ("1-candle", PatternCategory::Single),
+            ("double", PatternCategory::Two),
+            ("Three Candles", PatternCategory::Three),
+            ("MULTIPLE", PatternCategory::Multi),
+            (" continuation ", PatternCategory::Continuation),
+        ] {
+            assert_eq!(text.parse(), Ok(category), "{}", text);
+        }
+        assert!("four".parse::<PatternCategory>().is_err());
+
+        assert_eq!(PatternCategory::parse_exact(" multi "), Ok(PatternCategory::Multi));
+        for text in ["2", "double", "Three Candles"] {
+            assert!(PatternCategory::parse_exact(text).is_err(), "{}", text);
+        }
+        for category in PatternCategory::ALL {
+            assert_eq!(PatternCategory::parse_exact(category.as_str()), Ok(category));
+            assert_eq!(category.to_string().parse(), Ok(category));
+        }
+    }
+
+    #[test]
+    fn direction_parsing() {
+        for (text, direction) in [
+            ("bullish", Direction::Bullish),
+            ("Bulish", Direction::Bullish),
+            ("BULL", Direction::Bullish),
+            ("Bearsih", Direction::Bearish),
+            ("Bearish Reversal", Direction::Bearish),
+            ("Bullish-Continuation", Direction::BullishContinuation),
+            ("BEARISH_CONTINUATION", Direction::BearishContinuation),
+            ("bearish (continuing)", Direction::BearishContinuation),
+            ("Indecision", Direction::Neutral),
+            ("either", Direction::Both),
+        ] {
+            assert_eq!(text.parse(), Ok(direction), "{}", text);
+        }
+        // Words that only share the first letters are not directions
+        for text in ["bullet", "beam", "bearing", "bulk continuation", "up"] {
+            assert!(text.parse::<Direction>().is_err(), "{}", text);
+        }
+
+        assert_eq!(Direction::parse_exact("bullish continuation"), Ok(Direction::BullishContinuation));
+        for text in ["Bulish", "Bullish-Continuation", "bull"] {
+            assert!(Direction::parse_exact(text).is_err(), "{}", text);
+        }
+        for direction in Direction::ALL {
+            assert_eq!(Direction::parse_exact(direction.as_str()), Ok(direction));
+            assert_eq!(direction.to_string().parse(), Ok(direction));
+        }
+    }
+
+    #[test]
+    fn confidence_parsing() {
+        for (text, confidence) in [
+            ("High", Confidence::High),
+            ("very high", Confidence::High),
+            ("MED", Confidence::Medium),
+            ("moderate", Confidence::Medium),
+            ("Weak", Confidence::Low),
+        ] {
+            assert_eq!(text.parse(), Ok(confidence), "{}", text);
+        }
+        assert!("certain".parse::<Confidence>().is_err());
+        for confidence in [Confidence::Low, Confidence::Medium, Confidence::High] {
+            assert_eq!(confidence.to_string().parse(), Ok(confidence));
+        }
+    }
+}
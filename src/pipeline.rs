@@ -0,0 +1,899 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use tracing::{error, info, warn};
+
+use crate::analyzer::{self, AnalyzerOptions};
+use crate::annotate::{self, Span};
+use crate::candles::{self, Candle};
+use crate::config;
+use crate::ensemble;
+use crate::experiments::{self, Assignment};
+use crate::extract::{self, VisionMode};
+use crate::models::{
+    AnalyzeResponse, CostBreakdown, ModelVersions, OccurrenceSource, Pattern, PatternCategory,
+    PatternOccurrence, PromptVersions,
+};
+use crate::prefilter::{self, ChartFeatures};
+use crate::preprocess;
+use crate::prompts::{PromptTemplate, Stage};
+use crate::render::{self, RenderOptions};
+use crate::resample::{self, MultiTimeframe, Session, Timeframe};
+use crate::retrieval::{self, FewShotConfig};
+use crate::rules::{self, CandleSource, Verdict, Verification};
+use crate::store;
+use crate::taxonomy::Taxonomy;
+use crate::text;
+use crate::trend::{self, TrendContext, TrendSource};
+use crate::vision::{self, VisionResult};
+use crate::volume::{self, VolumeConfirmation, VolumeSource};
+use crate::{ApiError, AppState};
+
+// Replicate DeepSeek-VL2 pricing: Nvidia A100 80GB @ $0.001400/sec
+const REPLICATE_GPU_RATE: f64 = 0.001400;
+/// OHLC input is charted from its most recent candles only; more would shrink
+/// below what the vision model and local extraction can tell apart.
+const CHART_CANDLES: usize = 60;
+
+/// Everything an analysis run needs besides the image, resolved up front from
+/// request options, experiment assignment and server defaults.
+pub struct AnalysisPlan {
+    pub taxonomy: Arc<Taxonomy>,
+    pub lang: String,
+    pub vision_prompt: Arc<PromptTemplate>,
+    pub vision_version: String,
+    pub reasoner_prompt: Arc<PromptTemplate>,
+    pub reasoner_model: String,
+    pub few_shot: FewShotConfig,
+    pub experiment: Option<Assignment>,
+    /// Return the chart with the detected pattern boxed.
+    pub annotate: bool,
+    /// Trim whitespace around the chart before sending it to the vision model.
+    pub autocrop: bool,
+    /// Vision model, local candle extraction, or local when it segments cleanly.
+    pub vision_mode: VisionMode,
+    /// Reasoner calls to vote over; 1 is a single plain call.
+    pub samples: usize,
+    /// Models the samples cycle through; empty uses `reasoner_model`.
+    pub ensemble_models: Vec<String>,
+    /// Check the answer against structured candles when there are any.
+    pub verify: bool,
+    /// Report every pattern occurrence in the chart, not just the best match.
+    pub multiple: bool,
+    /// Extra timeframes to resample OHLC input to and scan; empty for none.
+    pub timeframes: Vec<Timeframe>,
+    /// Trading hours the resampled bars respect.
+    pub session: Session,
+    /// Refuse with 503 instead of calling a vision model that is still warming
+    /// up. The server sets this; eval waits out a cold start instead.
+    pub require_warm_vision: bool,
+}
+
+impl AnalysisPlan {
+    /// Explicit prompt/model options win; otherwise the active experiment (or
+    /// the one named by `experiment`) picks the variant. `experiment=none`
+    /// opts out of experiments entirely.
+    pub async fn resolve(
+        state: &AppState,
+        options: &HashMap<String, String>,
+    ) -> Result<Self, ApiError> {
+        let option = |key: &str| options.get(key).map(String::as_str);
+        let bad_request = |e: String| (StatusCode::BAD_REQUEST, e);
+
+        let taxonomy = state.taxonomy(option("taxonomy")).await?;
+        let lang = state.lang(option("lang"))?;
+
+        let pinned = ["vision_prompt", "reasoner_prompt", "reasoner_model"]
+            .iter()
+            .any(|k| options.contains_key(*k));
+        let assigned = match option("experiment") {
+            Some("none") => None,
+            None if pinned => None,
+            requested => experiments::assign(&state.experiments, requested).map_err(bad_request)?,
+        };
+        let (experiment, variant) = match assigned {
+            Some((assignment, variant)) => (Some(assignment), Some(variant)),
+            None => (None, None),
+        };
+
+        let vision_prompt_name = option("vision_prompt")
+            .or_else(|| variant.and_then(|v| v.vision_prompt.as_deref()));
+        let reasoner_prompt_name = option("reasoner_prompt")
+            .or_else(|| variant.and_then(|v| v.reasoner_prompt.as_deref()));
+        let reasoner_model = option("reasoner_model")
+            .or_else(|| variant.and_then(|v| v.reasoner_model.as_deref()))
+            .unwrap_or(analyzer::DEFAULT_MODEL);
+
+        let (vision_prompt, reasoner_prompt) = {
+            let prompts = state.prompts.read().await;
+            (
+                prompts.get(Stage::Vision, vision_prompt_name).map_err(bad_request)?,
+                prompts.get(Stage::Reasoner, reasoner_prompt_name).map_err(bad_request)?,
+            )
+        };
+
+        let mut few_shot = state.config.few_shot.clone();
+        if let Some(n) = option("examples") {
+            few_shot.max_examples = n
+                .parse()
+                .map_err(|_| bad_request(format!("examples must be a number, got {}", n)))?;
+        }
+
+        let samples = match option("samples") {
+            Some(n) => n
+                .parse()
+                .ok()
+                .filter(|n| (1..=ensemble::MAX_SAMPLES).contains(n))
+                .ok_or_else(|| {
+                    bad_request(format!("samples must be between 1 and {}, got {}", ensemble::MAX_SAMPLES, n))
+                })?,
+            None => state.config.reasoner_samples,
+        };
+
+        let flag = |key: &str| option(key).map(|v| matches!(v, "true" | "1" | "on" | "yes"));
+
+        Ok(AnalysisPlan {
+            taxonomy,
+            lang,
+            vision_prompt,
+            vision_version: variant
+                .and_then(|v| v.vision_version.clone())
+                .unwrap_or_else(|| vision::VL2_VERSION.to_string()),
+            reasoner_prompt,
+            reasoner_model: reasoner_model.to_string(),
+            few_shot,
+            experiment,
+            annotate: flag("annotate").unwrap_or(false),
+            autocrop: flag("autocrop").unwrap_or(state.config.preprocess.autocrop),
+            vision_mode: match option("vision") {
+                Some(mode) => mode.parse().map_err(bad_request)?,
+                None => state.config.vision_mode,
+            },
+            samples,
+            ensemble_models: option("ensemble_models")
+                .map(config::models_list)
+                .unwrap_or_else(|| state.config.ensemble_models.clone()),
+            verify: flag("verify").unwrap_or(true),
+            multiple: flag("multiple").unwrap_or(false),
+            timeframes: option("timeframes")
+                .map(resample::parse_timeframes)
+                .transpose()
+                .map_err(bad_request)?
+                .unwrap_or_default(),
+            session: option("session")
+                .map(str::parse)
+                .transpose()
+                .map_err(bad_request)?
+                .unwrap_or_default(),
+            require_warm_vision: false,
+        })
+    }
+}
+
+/// A failed analysis as the HTTP error to return, plus the reasoner's error
+/// kind when that is the stage that failed.
+#[derive(Debug)]
+pub struct AnalysisError {
+    pub status: StatusCode,
+    pub message: String,
+    pub reasoner: Option<analyzer::ErrorKind>,
+}
+
+impl From<ApiError> for AnalysisError {
+    fn from((status, message): ApiError) -> Self {
+        AnalysisError { status, message, reasoner: None }
+    }
+}
+
+impl From<AnalysisError> for ApiError {
+    fn from(e: AnalysisError) -> Self {
+        (e.status, e.message)
+    }
+}
+
+/// Runs preprocess -> vision -> prefilter -> reasoner -> verification for one
+/// chart image. `ohlc`, when sent along, is the series the chart shows and is
+/// used for verification, trend, volume and timeframes.
+pub async fn analyze_image(
+    state: &AppState,
+    plan: &AnalysisPlan,
+    image_bytes: &[u8],
+    ohlc: Option<&[Candle]>,
+) -> Result<AnalyzeResponse, AnalysisError> {
+    let timeframes = match ohlc {
+        Some(candles) => multi_timeframe(plan, candles)?,
+        None if plan.timeframes.is_empty() => None,
+        None => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "timeframes needs OHLC input to resample".to_string(),
+            ).into())
+        }
+    };
+    let mut response = analyze_chart(state, plan, image_bytes, ohlc).await?;
+    response.timeframes = timeframes;
+    Ok(response)
+}
+
+/// `ohlc` is the series the image shows, when known; it is
+/// verified against instead of candles extracted from the image.
+async fn analyze_chart(
+    state: &AppState,
+    plan: &AnalysisPlan,
+    image_bytes: &[u8],
+    ohlc: Option<&[Candle]>,
+) -> Result<AnalyzeResponse, AnalysisError> {
+    // Stage 0: decode, validate and normalize the upload
+    let format = preprocess::detect_format(image_bytes)
+        .map_err(|e| (StatusCode::UNSUPPORTED_MEDIA_TYPE, e))?;
+    let (bytes, config, autocrop) = (image_bytes.to_vec(), state.config.preprocess.clone(), plan.autocrop);
+    let image = blocking(move || preprocess::prepare(&bytes, format, &config, autocrop))
+        .await?
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let report = &image.report;
+    info!(
+        "Preprocess: {} {}x{} ({} bytes) -> {}x{} ({} bytes){}{}{}",
+        report.original_format,
+        report.original_width,
+        report.original_height,
+        report.original_bytes,
+        report.width,
+        report.height,
+        report.bytes,
+        if report.rotated { ", rotated" } else { "" },
+        if report.cropped { ", cropped" } else { "" },
+        if report.downscaled { ", downscaled" } else { "" },
+    );
+
+    // Stage 1a: local candle extraction — replaces the vision model for clean
+    // charts and cross-checks it otherwise
+    let bytes = image.bytes.clone();
+    let mut extraction = blocking(move || {
+        image::load_from_memory(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|img| extract::extract_candles(&img.to_rgb8()))
+    })
+    .await?;
+    match &extraction {
+        Ok(local) => info!(
+            "Local extraction: {} candles, spacing CV {:.3}{}",
+            local.candles.len(),
+            local.spacing_cv,
+            if local.reliable { "" } else { " (unreliable)" }
+        ),
+        Err(e) => info!("Local extraction failed: {}", e),
+    }
+    let use_local = match (plan.vision_mode, &extraction) {
+        (VisionMode::Local, Err(e)) => {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Local candle extraction failed: {}", e),
+            ).into())
+        }
+        (VisionMode::Local, Ok(_)) => true,
+        (VisionMode::Auto, Ok(local)) => local.reliable,
+        _ => false,
+    };
+
+    // Stage 1b: Vision — get chart description
+    let vision_result = match &mut extraction {
+        Ok(local) if use_local => {
+            local.used_for_analysis = true;
+            VisionResult {
+                description: candles::describe(&local.candles),
+                predict_seconds: 0.0,
+            }
+        }
+        _ => {
+            if plan.require_warm_vision {
+                let warmup = state.warmup.read().await;
+                if warmup.state != "ready" {
+                    return Err((
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        format!("Model not ready: {}", warmup.message),
+                    ).into());
+                }
+            }
+            let result = vision::describe_chart(
+                &state.client,
+                &state.config.replicate_api_token,
+                &image.bytes,
+                image.content_type,
+                &plan.vision_prompt.system,
+                &plan.vision_version,
+            )
+            .await
+            .map_err(|e| {
+                error!("Vision stage failed: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("Vision analysis failed: {}", e))
+            })?;
+            if let Ok(local) = &mut extraction {
+                local.cross_check(&result.description);
+            }
+            result
+        }
+    };
+
+    let vision_cost = vision_result.predict_seconds * REPLICATE_GPU_RATE;
+    info!(
+        "Vision: {:.1}s predict time — ${:.6}",
+        vision_result.predict_seconds, vision_cost
+    );
+    info!(
+        "Chart description: {}",
+        text::truncate(&vision_result.description, 200)
+    );
+
+    // Prior trend from structured candles beats whatever the description says
+    let trend_context = match (ohlc, &extraction) {
+        (Some(candles), _) => trend::measure(candles, TrendSource::Ohlc),
+        (None, Ok(local)) if local.reliable => trend::measure(&local.candles, TrendSource::Extracted),
+        _ => None,
+    };
+
+    let mut response = analyze_description(state, plan, vision_result, trend_context).await?;
+    let candles = match (ohlc, &extraction) {
+        (Some(candles), _) => Some((candles, CandleSource::Ohlc)),
+        (None, Ok(local)) if local.reliable => Some((local.candles.as_slice(), CandleSource::Extracted)),
+        _ => None,
+    };
+    if let Some((candles, source)) = candles {
+        if plan.verify {
+            verify(state, plan, &mut response, candles, source);
+        }
+        if let Some(occurrences) = response.occurrences.take() {
+            response.occurrences = Some(merge_detections(plan, occurrences, candles, &response.pattern));
+        }
+    }
+    confirm_volume(state, &mut response, ohlc);
+    if plan.annotate {
+        response.annotated_image = annotated_image(image.bytes.clone(), &response).await?;
+    }
+    if use_local {
+        response.models.vision = extract::LOCAL_MODEL.to_string();
+    }
+    response.preprocessing = Some(image.report);
+    response.local_extraction = extraction.ok();
+    Ok(response)
+}
+
+/// Pattern length for boxing: the reasoner's count, else what the category implies.
+fn pattern_candles(response: &AnalyzeResponse) -> usize {
+    response.candle_count.unwrap_or(match response.category {
+        Some(PatternCategory::Single) => 1,
+        Some(PatternCategory::Two) => 2,
+        Some(PatternCategory::Three) => 3,
+        _ => 5,
+    })
+}
+
+/// Runs CPU-bound image work on the blocking pool, off the async executor.
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Image processing failed: {}", e)))
+}
+
+/// Annotation is best effort: a chart we cannot segment still gets its analysis.
+/// The actionable occurrence is boxed when there is one, else the last
+/// candles of the final answer.
+async fn annotated_image(image_bytes: Vec<u8>, response: &AnalyzeResponse) -> Result<Option<String>, ApiError> {
+    let actionable = response.occurrences.iter().flatten().find(|o| o.actionable);
+    let (pattern, direction, span) = match actionable {
+        Some(occurrence) => (
+            occurrence.pattern.clone(),
+            occurrence.direction,
+            Span::Range(occurrence.start, occurrence.end),
+        ),
+        None => (response.pattern.clone(), response.direction, Span::Last(pattern_candles(response))),
+    };
+    let annotated = blocking(move || annotate::annotate(&image_bytes, &pattern, direction, span)).await?;
+    Ok(match annotated {
+        Ok(png) => Some(format!("data:image/png;base64,{}", BASE64.encode(png))),
+        Err(e) => {
+            warn!("Failed to annotate chart: {}", e);
+            None
+        }
+    })
+}
+
+/// Renders the last `CHART_CANDLES` of OHLC input with the default theme so
+/// the vision model sees the same chart style for every candle series, then
+/// analyzes it like an upload. Occurrence indexes are shifted back to count
+/// from the first submitted candle. With `timeframes`, the whole series is
+/// also resampled and scanned per timeframe.
+pub async fn analyze_candles(
+    state: &AppState,
+    plan: &AnalysisPlan,
+    candles: &[Candle],
+) -> Result<AnalyzeResponse, AnalysisError> {
+    // Resample first so bad timestamps fail before any model is paid for
+    let timeframes = multi_timeframe(plan, candles)?;
+
+    let skipped = candles.len().saturating_sub(CHART_CANDLES);
+    let shown = &candles[skipped..];
+    let png = render::render_png(shown, &RenderOptions::default())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    info!(
+        "Rendered the last {} of {} candles to a {} byte chart",
+        shown.len(),
+        candles.len(),
+        png.len()
+    );
+    let mut response = analyze_chart(state, plan, &png, Some(shown)).await?;
+    for occurrence in response.occurrences.iter_mut().flatten() {
+        occurrence.start += skipped;
+        occurrence.end += skipped;
+    }
+    response.timeframes = timeframes;
+    Ok(response)
+}
+
+/// Resamples and scans `candles` per requested timeframe; `None` without any.
+fn multi_timeframe(plan: &AnalysisPlan, candles: &[Candle]) -> Result<Option<MultiTimeframe>, ApiError> {
+    if plan.timeframes.is_empty() {
+        return Ok(None);
+    }
+    let analysis = resample::analyze(candles, &plan.timeframes, plan.session, &plan.taxonomy)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    info!(
+        "Timeframes: {} bullish, {} bearish{}",
+        analysis.alignment.bullish.len(),
+        analysis.alignment.bearish.len(),
+        if analysis.alignment.aligned { " (aligned)" } else { "" }
+    );
+    Ok(Some(analysis))
+}
+
+/// Checks the reasoner's pattern geometrically. On failure the first
+/// alternative that passes takes its place; with none, confidence drops a level.
+fn verify(
+    state: &AppState,
+    plan: &AnalysisPlan,
+    response: &mut AnalyzeResponse,
+    candles: &[Candle],
+    source: CandleSource,
+) {
+    let (verification, replacement) = check_answer(
+        &plan.taxonomy,
+        &response.pattern,
+        &response.alternatives,
+        response.occurrences.as_deref().unwrap_or_default(),
+        candles,
+        source,
+    );
+    if matches!(verification.verdict, Verdict::Downgraded | Verdict::Replaced) {
+        let original = std::mem::take(&mut response.pattern);
+        response.confidence = response.confidence.downgrade();
+        match replacement {
+            Some(pattern) => {
+                info!("Verification: {} failed, replaced by {}", original, pattern.name);
+                response.alternatives.retain(|name| *name != pattern.name);
+                response.alternatives.insert(0, original.clone());
+                response.localized = state.translations.get(&plan.lang, &pattern.name).cloned();
+                response.category = Some(pattern.category);
+                response.direction = Some(pattern.direction);
+                response.candle_count = verification.checked.last().map(|c| c.candles);
+                response.pattern = pattern.name.clone();
+                response.reasoning_for = Some(original);
+            }
+            None => {
+                info!("Verification: {} failed, confidence lowered", original);
+                response.pattern = original;
+            }
+        }
+    }
+    response.verification = Some(verification);
+}
+
+/// The verification of `pattern` and, when it failed, the first alternative
+/// in `taxonomy` that passes to replace it.
+fn check_answer<'a>(
+    taxonomy: &'a Taxonomy,
+    pattern: &str,
+    alternatives: &[String],
+    occurrences: &[PatternOccurrence],
+    candles: &[Candle],
+    source: CandleSource,
+) -> (Verification, Option<&'a Pattern>) {
+    // A pattern the reasoner placed earlier in the chart is checked where it
+    // said, not against the last candles.
+    let through = |name: &str| {
+        let end = latest_occurrence(occurrences, name, candles.len()).map(|o| o.end);
+        &candles[..end.map_or(candles.len(), |end| end + 1)]
+    };
+
+    let Some(primary) = rules::verify(pattern, through(pattern)) else {
+        let verification = Verification {
+            source,
+            verdict: Verdict::Unchecked,
+            checked: Vec::new(),
+            original_pattern: None,
+        };
+        return (verification, None);
+    };
+
+    let mut verification = Verification {
+        source,
+        verdict: if primary.passed { Verdict::Confirmed } else { Verdict::Downgraded },
+        checked: vec![primary],
+        original_pattern: None,
+    };
+    if verification.verdict == Verdict::Confirmed {
+        return (verification, None);
+    }
+    let replacement = alternatives
+        .iter()
+        .find_map(|name| {
+            let checks = rules::verify(name, through(name))?;
+            let passed = checks.passed;
+            verification.checked.push(checks);
+            passed.then_some(name)
+        })
+        .and_then(|name| taxonomy.find(name));
+    if replacement.is_some() {
+        verification.verdict = Verdict::Replaced;
+        verification.original_pattern = Some(pattern.to_string());
+    }
+    (verification, replacement)
+}
+
+/// Latest occurrence of `pattern` that ends within `len` candles.
+fn latest_occurrence<'a>(occurrences: &'a [PatternOccurrence], pattern: &str, len: usize) -> Option<&'a PatternOccurrence> {
+    occurrences
+        .iter()
+        .filter(|o| o.pattern.eq_ignore_ascii_case(pattern) && o.end < len)
+        .max_by_key(|o| o.end)
+}
+
+/// Relative volume from OHLC at the pattern's reported occurrence (else its
+/// last candles), else volume wording in the description, and the confidence
+/// adjustment it implies for the final pattern.
+fn confirm_volume(state: &AppState, response: &mut AnalyzeResponse, ohlc: Option<&[Candle]>) {
+    let config = &state.config.volume;
+    let required = volume::requires_volume(&response.pattern);
+    let measured = ohlc.and_then(|series| {
+        let occurrences = response.occurrences.as_deref().unwrap_or_default();
+        let (end, candles) = match latest_occurrence(occurrences, &response.pattern, series.len()) {
+            Some(o) => (o.end, o.end.saturating_sub(o.start) + 1),
+            None => (series.len().checked_sub(1)?, pattern_candles(response)),
+        };
+        volume::relative_volume(series, end, candles, config.lookback)
+    });
+    let (source, relative_volume, average_over, confirmed, below_average) = match measured {
+        Some((ratio, over)) => (VolumeSource::Ohlc, Some(ratio), Some(over), ratio >= config.confirm_ratio, ratio < 1.0),
+        None => match volume::from_description(&response.chart_description) {
+            Some(high) => (VolumeSource::Description, None, None, high, !high),
+            None => return,
+        },
+    };
+
+    let original_confidence = response.confidence;
+    response.confidence = volume::adjust(original_confidence, required, confirmed, below_average);
+    if response.confidence != original_confidence {
+        info!(
+            "Volume: {} {} on {} volume, confidence {} -> {}",
+            response.pattern,
+            if confirmed { "confirmed" } else { "unconfirmed" },
+            relative_volume.map_or("described".to_string(), |r| format!("{:.2}x", r)),
+            original_confidence.as_str(),
+            response.confidence.as_str()
+        );
+    }
+    response.volume = Some(VolumeConfirmation {
+        source,
+        relative_volume,
+        average_over,
+        required,
+        confirmed,
+        original_confidence,
+    });
+}
+
+/// Orders occurrences most recent first (longer patterns first among those
+/// ending on the same candle), merges duplicates and marks the actionable one:
+/// the final answer if it ends on the last occurrence candle, else the first.
+fn rank_occurrences(mut occurrences: Vec<PatternOccurrence>, primary: &str) -> Vec<PatternOccurrence> {
+    occurrences.sort_by(|a, b| {
+        b.end
+            .cmp(&a.end)
+            .then(a.start.cmp(&b.start))
+            .then(a.pattern.cmp(&b.pattern))
+    });
+    occurrences.dedup_by(|duplicate, kept| {
+        if duplicate.pattern != kept.pattern || duplicate.start != kept.start || duplicate.end != kept.end {
+            return false;
+        }
+        for source in &duplicate.sources {
+            if !kept.sources.contains(source) {
+                kept.sources.push(*source);
+            }
+        }
+        kept.confidence = kept.confidence.max(duplicate.confidence);
+        true
+    });
+    let latest = occurrences.first().map(|o| o.end);
+    let actionable = occurrences
+        .iter()
+        .position(|o| Some(o.end) == latest && o.pattern == primary)
+        .unwrap_or(0);
+    for (i, occurrence) in occurrences.iter_mut().enumerate() {
+        occurrence.actionable = i == actionable;
+    }
+    occurrences
+}
+
+/// Adds what the geometric rules find in `candles` to the reasoner's
+/// occurrences, dropping reasoner ranges that run past the last candle.
+fn merge_detections(
+    plan: &AnalysisPlan,
+    mut occurrences: Vec<PatternOccurrence>,
+    candles: &[Candle],
+    primary: &str,
+) -> Vec<PatternOccurrence> {
+    occurrences.retain(|o| o.end < candles.len());
+    for detection in rules::scan(candles) {
+        let Some(pattern) = plan.taxonomy.find(detection.pattern) else {
+            continue;
+        };
+        occurrences.push(PatternOccurrence {
+            pattern: pattern.name.clone(),
+            direction: Some(pattern.direction),
+            start: detection.start,
+            end: detection.end,
+            confidence: None,
+            sources: vec![OccurrenceSource::Rules],
+            actionable: false,
+        });
+    }
+    rank_occurrences(occurrences, primary)
+}
+
+/// Runs prefilter -> reasoner on an existing chart description.
+/// `vision_result` carries the description and what producing it cost (zero when it did not
+/// come from the vision model). `trend_context` is the measured prior trend, if any; without
+/// one the description's trend is used.
+pub async fn analyze_description(
+    state: &AppState,
+    plan: &AnalysisPlan,
+    vision_result: VisionResult,
+    trend_context: Option<TrendContext>,
+) -> Result<AnalyzeResponse, AnalysisError> {
+    let vision_cost = vision_result.predict_seconds * REPLICATE_GPU_RATE;
+
+    let mut features = ChartFeatures::from_description(&vision_result.description);
+    let trend_context = trend_context.or_else(|| trend::from_description(&features));
+    if let Some(context) = &trend_context {
+        info!("Prior trend: {} ({:?})", context.trend.label(), context.source);
+        features.trend = Some(context.trend);
+        features.trend_measured = context.measured();
+    }
+    let trend_line = trend_context.as_ref().map(TrendContext::prompt_line).unwrap_or_default();
+
+    // Narrow the taxonomy before paying for it in the reasoner prompt
+    let (candidates, prefilter_report) =
+        prefilter::filter(&plan.taxonomy, &features, &state.config.prefilter);
+    let prefilter_saved_usd = prefilter_report.estimated_tokens_saved as f64 / 1_000_000.0
+        * analyzer::REASONER_INPUT_PRICE;
+    info!(
+        "Prefilter: {}/{} patterns ({}), ~{} tokens saved",
+        prefilter_report.candidates,
+        prefilter_report.total,
+        prefilter_report.reason,
+        prefilter_report.estimated_tokens_saved
+    );
+    state
+        .prefilter_stats
+        .write()
+        .await
+        .record(&prefilter_report, prefilter_saved_usd);
+
+    // Few-shot examples from past analyses the users confirmed
+    let (examples_text, few_shot_examples) = retrieval::select_examples(
+        &*state.examples.read().await,
+        &vision_result.description,
+        &candidates,
+        &plan.few_shot,
+    );
+    if !few_shot_examples.is_empty() {
+        info!("Few-shot: {} examples selected", few_shot_examples.len());
+    }
+
+    // Stage 2: Pattern analysis, voted over several samples when asked
+    let stage_error = |e: analyzer::AnalyzerError| {
+        error!("Analysis stage failed: {}", e);
+        AnalysisError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("Pattern analysis failed: {}", e),
+            reasoner: Some(e.kind),
+        }
+    };
+    let (analysis, ensemble) = if plan.samples > 1 {
+        let (analysis, report) =
+            ensemble::run(state, plan, &vision_result.description, &candidates, &examples_text, &trend_line)
+                .await
+                .map_err(stage_error)?;
+        (analysis, Some(report))
+    } else {
+        let analysis = analyzer::analyze_pattern(
+            &state.client,
+            &state.config.deepseek_api_key,
+            &vision_result.description,
+            &candidates,
+            &AnalyzerOptions {
+                model: &plan.reasoner_model,
+                lang: &plan.lang,
+                template: &plan.reasoner_prompt,
+                examples: &examples_text,
+                trend_context: &trend_line,
+                occurrences: plan.multiple,
+            },
+        )
+        .await
+        .map_err(stage_error)?;
+        (analysis, None)
+    };
+    let reasoner_models = match &ensemble {
+        Some(report) => {
+            let mut models: Vec<&str> = Vec::new();
+            for model in report.votes.iter().flat_map(|v| &v.models) {
+                if !models.contains(&model.as_str()) {
+                    models.push(model);
+                }
+            }
+            models.join(",")
+        }
+        None => plan.reasoner_model.clone(),
+    };
+    let reasoner_calls = ensemble.as_ref().map_or(1, |e| e.samples);
+
+    let occurrences = plan
+        .multiple
+        .then(|| rank_occurrences(analysis.occurrences, &analysis.pattern));
+
+    let total_cost = vision_cost + analysis.cost_usd;
+    info!("Total cost: ${:.6} (vision ${:.6} + reasoner ${:.6})", total_cost, vision_cost, analysis.cost_usd);
+
+    Ok(AnalyzeResponse {
+        id: store::new_id(),
+        taxonomy: plan.taxonomy.name.clone(),
+        localized: state.translations.get(&plan.lang, &analysis.pattern).cloned(),
+        lang: plan.lang.clone(),
+        pattern: analysis.pattern,
+        category: analysis.category,
+        direction: analysis.direction,
+        confidence: analysis.confidence,
+        reasoning: analysis.reasoning,
+        alternatives: analysis.alternatives,
+        candle_count: analysis.candle_count,
+        occurrences,
+        timeframes: None,
+        chain_of_thought: analysis.chain_of_thought,
+        reasoning_for: None,
+        chart_description: vision_result.description,
+        trend_context,
+        annotated_image: None,
+        preprocessing: None,
+        local_extraction: None,
+        ensemble,
+        verification: None,
+        volume: None,
+        prefilter: prefilter_report,
+        few_shot_examples,
+        prompts: PromptVersions {
+            vision: plan.vision_prompt.reference(),
+            reasoner: plan.reasoner_prompt.reference(),
+        },
+        models: ModelVersions {
+            vision: plan.vision_version.clone(),
+            reasoner: reasoner_models,
+        },
+        experiment: plan.experiment.clone(),
+        cost: CostBreakdown {
+            reasoner_calls,
+            vision_seconds: vision_result.predict_seconds,
+            vision_cost_usd: vision_cost,
+            reasoner_prompt_tokens: analysis.prompt_tokens,
+            reasoner_completion_tokens: analysis.completion_tokens,
+            reasoner_reasoning_tokens: analysis.reasoning_tokens,
+            reasoner_cost_usd: analysis.cost_usd,
+            total_cost_usd: total_cost,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Direction;
+    use crate::taxonomy::{self, DEFAULT_TAXONOMY};
+
+    fn default_taxonomy() -> Taxonomy {
+        let path = taxonomy::csv_path(DEFAULT_TAXONOMY);
+        Taxonomy {
+            name: DEFAULT_TAXONOMY.to_string(),
+            patterns: taxonomy::load_patterns(&path).unwrap(),
+            path,
+            version: 0,
+        }
+    }
+
+    /// Six rising candles, then a hammer-shaped candle: a Hanging Man, not a Hammer.
+    fn hanging_man() -> Vec<Candle> {
+        let mut candles: Vec<Candle> = (0..6)
+            .map(|i| {
+                let open = 94.0 + i as f64;
+                Candle { time: None, open, high: open + 1.0, low: open - 0.2, close: open + 0.8, volume: None }
+            })
+            .collect();
+        candles.push(Candle { time: None, open: 100.0, high: 100.6, low: 98.0, close: 100.5, volume: None });
+        candles
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn failing_answer_is_replaced_by_the_first_passing_alternative() {
+        let taxonomy = default_taxonomy();
+        let (verification, replacement) = check_answer(
+            &taxonomy,
+            "Hammer",
+            &names(&["Morning Star", "Hanging Man", "Shooting Star"]),
+            &[],
+            &hanging_man(),
+            CandleSource::Ohlc,
+        );
+        assert_eq!(verification.verdict, Verdict::Replaced);
+        assert_eq!(verification.original_pattern.as_deref(), Some("Hammer"));
+        let checked: Vec<&str> = verification.checked.iter().map(|c| c.pattern.as_str()).collect();
+        assert_eq!(checked, ["Hammer", "Morning Star", "Hanging Man"]);
+        let pattern = replacement.unwrap();
+        assert_eq!((pattern.name.as_str(), pattern.direction), ("Hanging Man", Direction::Bearish));
+    }
+
+    #[test]
+    fn failing_answer_without_a_passing_alternative_is_downgraded() {
+        let taxonomy = default_taxonomy();
+        let (verification, replacement) = check_answer(
+            &taxonomy,
+            "Hammer",
+            &names(&["Head and Shoulders", "Shooting Star"]),
+            &[],
+            &hanging_man(),
+            CandleSource::Ohlc,
+        );
+        assert_eq!(verification.verdict, Verdict::Downgraded);
+        assert!(replacement.is_none());
+        assert!(verification.original_pattern.is_none());
+        // Patterns without rules are skipped rather than reported
+        assert_eq!(verification.checked.len(), 2);
+    }
+
+    #[test]
+    fn answer_is_checked_at_its_reported_occurrence() {
+        let taxonomy = default_taxonomy();
+        let mut candles = hanging_man();
+        // Two more rising candles after the pattern
+        candles.push(Candle { time: None, open: 100.5, high: 101.6, low: 100.3, close: 101.4, volume: None });
+        candles.push(Candle { time: None, open: 101.4, high: 102.6, low: 101.2, close: 102.4, volume: None });
+        let occurrence = PatternOccurrence {
+            pattern: "Hanging Man".to_string(),
+            direction: Some(Direction::Bearish),
+            start: 6,
+            end: 6,
+            confidence: None,
+            sources: vec![OccurrenceSource::Reasoner],
+            actionable: true,
+        };
+
+        let at_end = check_answer(&taxonomy, "Hanging Man", &[], &[], &candles, CandleSource::Ohlc).0;
+        assert_eq!(at_end.verdict, Verdict::Downgraded);
+        let placed = check_answer(&taxonomy, "Hanging Man", &[], &[occurrence], &candles, CandleSource::Ohlc).0;
+        assert_eq!(placed.verdict, Verdict::Confirmed);
+        assert!(check_answer(&taxonomy, "Head and Shoulders", &[], &[], &candles, CandleSource::Ohlc).0.checked.is_empty());
+    }
+}
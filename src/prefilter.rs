@@ -0,0 +1,417 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::models::{Direction, Pattern, PatternCategory};
+use crate::rules;
+use crate::taxonomy::Taxonomy;
+
+// Rough English tokenizer ratio used to estimate prompt savings without
+// calling a tokenizer.
+const CHARS_PER_TOKEN: f64 = 4.0;
+
+// Words too generic to tell patterns apart.
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "the", "of", "to", "at", "in", "on", "with", "by", "then", "than", "that",
+    "or", "no", "is", "its", "it", "all", "each", "one", "two", "three", "four", "five", "prior",
+    "first", "second", "third", "candle", "candles", "body", "followed", "showing", "signaling",
+    "price", "same", "between", "within", "but", "where", "into", "from", "after", "before",
+    "small", "large", "long", "little", "very", "level", "pattern", "bullish", "bearish",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trend {
+    Up,
+    Down,
+    Sideways,
+}
+
+impl Trend {
+    pub fn label(self) -> &'static str {
+        match self {
+            Trend::Up => "uptrend",
+            Trend::Down => "downtrend",
+            Trend::Sideways => "sideways",
+        }
+    }
+}
+
+/// Signals extracted from the chart before prompting. Built from the vision
+/// description; a trend measured from candle data replaces the described one.
+#[derive(Debug, Default)]
+pub struct ChartFeatures {
+    pub candle_count: Option<usize>,
+    pub trend: Option<Trend>,
+    /// `trend` was measured from prices, so it can rule patterns out.
+    pub trend_measured: bool,
+    pub terms: HashSet<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefilterConfig {
+    pub enabled: bool,
+    pub max_candidates: usize,
+    /// Below this confidence (0..1) the full taxonomy is sent instead.
+    pub min_confidence: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefilterReport {
+    pub applied: bool,
+    pub reason: String,
+    pub confidence: f64,
+    pub candidates: usize,
+    pub total: usize,
+    pub estimated_tokens_saved: u64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PrefilterStats {
+    pub requests: u64,
+    pub applied: u64,
+    pub fallbacks: u64,
+    pub estimated_tokens_saved: u64,
+    pub estimated_cost_saved_usd: f64,
+}
+
+impl PrefilterStats {
+    pub fn record(&mut self, report: &PrefilterReport, cost_saved_usd: f64) {
+        self.requests += 1;
+        if report.applied {
+            self.applied += 1;
+        } else {
+            self.fallbacks += 1;
+        }
+        self.estimated_tokens_saved += report.estimated_tokens_saved;
+        self.estimated_cost_saved_usd += cost_saved_usd;
+    }
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '-')
+        .map(|w| w.trim_matches('-').to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+fn parse_count(word: &str) -> Option<usize> {
+    let n = match word {
+        "one" | "single" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        other => other.parse().ok()?,
+    };
+    (1..=500).contains(&n).then_some(n)
+}
+
+impl ChartFeatures {
+    pub fn from_description(description: &str) -> Self {
+        let words = tokenize(description);
+
+        // "5 candles", "five candlesticks", "number of candles visible: 5"
+        let candle_count = words.iter().enumerate().find_map(|(i, w)| {
+            if !w.starts_with("candle") {
+                return None;
+            }
+            let before = i.checked_sub(1).and_then(|j| parse_count(&words[j]));
+            let after = words
+                .iter()
+                .skip(i + 1)
+                .take(2)
+                .find_map(|w| w.parse::<usize>().ok())
+                .filter(|n| (1..=500).contains(n));
+            before.or(after)
+        });
+
+        let text = description.to_lowercase();
+        let up = ["uptrend", "upward trend", "rising trend", "trending up", "bullish trend"]
+            .iter()
+            .filter(|k| text.contains(*k))
+            .count();
+        let down = ["downtrend", "downward trend", "falling trend", "trending down", "bearish trend"]
+            .iter()
+            .filter(|k| text.contains(*k))
+            .count();
+        let sideways = ["sideways", "range-bound", "ranging", "consolidat", "no clear trend"]
+            .iter()
+            .any(|k| text.contains(*k));
+        let trend = match up.cmp(&down) {
+            std::cmp::Ordering::Greater => Some(Trend::Up),
+            std::cmp::Ordering::Less => Some(Trend::Down),
+            std::cmp::Ordering::Equal if sideways => Some(Trend::Sideways),
+            std::cmp::Ordering::Equal => None,
+        };
+
+        ChartFeatures {
+            candle_count,
+            trend,
+            trend_measured: false,
+            terms: words.into_iter().filter(|w| !STOPWORDS.contains(&w.as_str())).collect(),
+        }
+    }
+}
+
+fn min_candles(category: PatternCategory) -> usize {
+    match category {
+        PatternCategory::Single | PatternCategory::Special => 1,
+        PatternCategory::Two | PatternCategory::Continuation => 2,
+        PatternCategory::Three => 3,
+        PatternCategory::Multi => 4,
+    }
+}
+
+fn score(pattern: &Pattern, features: &ChartFeatures) -> f64 {
+    let text = format!("{} {}", pattern.name, pattern.description);
+    let terms: HashSet<String> = tokenize(&text)
+        .into_iter()
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .collect();
+    let overlap = terms.iter().filter(|t| features.terms.contains(*t)).count() as f64;
+
+    let trend_bonus = match (features.trend, pattern.direction) {
+        (Some(Trend::Down), Direction::Bullish) | (Some(Trend::Up), Direction::Bearish) => 1.0,
+        (Some(Trend::Up), Direction::BullishContinuation)
+        | (Some(Trend::Down), Direction::BearishContinuation) => 1.0,
+        _ => 0.0,
+    };
+
+    overlap + trend_bonus
+}
+
+fn prompt_tokens(pattern: &Pattern) -> u64 {
+    let line = format!(
+        "- {} | Category: {} | Direction: {} | {}\n",
+        pattern.name, pattern.category, pattern.direction, pattern.description
+    );
+    (line.len() as f64 / CHARS_PER_TOKEN).ceil() as u64
+}
+
+/// Patterns defined by a prior trend other than the measured one cannot apply.
+fn fits_trend(pattern: &Pattern, features: &ChartFeatures) -> bool {
+    !features.trend_measured
+        || rules::required_trend(&pattern.name).is_none_or(|t| Some(t) == features.trend)
+}
+
+/// `taxonomy` restricted to `kept`, with the report describing it.
+fn narrow(
+    taxonomy: &Taxonomy,
+    kept: &HashSet<&str>,
+    reason: String,
+    confidence: f64,
+) -> (Taxonomy, PrefilterReport) {
+    let patterns: Vec<Pattern> = taxonomy
+        .patterns
+        .iter()
+        .filter(|p| kept.contains(p.name.as_str()))
+        .cloned()
+        .collect();
+    let estimated_tokens_saved = taxonomy
+        .patterns
+        .iter()
+        .filter(|p| !kept.contains(p.name.as_str()))
+        .map(prompt_tokens)
+        .sum();
+
+    let report = PrefilterReport {
+        applied: patterns.len() < taxonomy.patterns.len(),
+        reason,
+        confidence,
+        candidates: patterns.len(),
+        total: taxonomy.patterns.len(),
+        estimated_tokens_saved,
+    };
+    (
+        Taxonomy {
+            patterns,
+            ..taxonomy.clone()
+        },
+        report,
+    )
+}
+
+/// Narrows `taxonomy` to the patterns plausible for `features`. Falls back to
+/// the full taxonomy (and says why) when disabled or not confident enough;
+/// otherwise a measured prior trend also excludes patterns that need another one.
+pub fn filter(
+    taxonomy: &Taxonomy,
+    features: &ChartFeatures,
+    config: &PrefilterConfig,
+) -> (Taxonomy, PrefilterReport) {
+    let total = taxonomy.patterns.len();
+    if !config.enabled {
+        return (
+            taxonomy.clone(),
+            PrefilterReport {
+                applied: false,
+                reason: "disabled".to_string(),
+                confidence: 0.0,
+                candidates: total,
+                total,
+                estimated_tokens_saved: 0,
+            },
+        );
+    }
+
+    let in_context: Vec<&Pattern> =
+        taxonomy.patterns.iter().filter(|p| fits_trend(p, features)).collect();
+    let trend_note = match (total - in_context.len(), features.trend) {
+        (0, _) | (_, None) => String::new(),
+        (n, Some(trend)) => format!(", {} ruled out by the measured {}", n, trend.label()),
+    };
+
+    // A known, short chart rules out patterns needing more candles than shown.
+    // Longer charts can contain any pattern, so the count says nothing.
+    let count_limit = features.candle_count.filter(|n| *n <= 5);
+
+    let mut scored: Vec<(f64, &Pattern)> = in_context
+        .iter()
+        .filter(|p| count_limit.is_none_or(|n| min_candles(p.category) <= n))
+        .map(|p| (score(p, features), *p))
+        .collect();
+
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    // Patterns tied with the last one that fits stay too, so taxonomy order
+    // never decides what is cut.
+    let cut = match scored.get(config.max_candidates.max(1) - 1) {
+        Some(&(floor, _)) => scored.iter().position(|(s, _)| *s < floor).unwrap_or(scored.len()),
+        None => scored.len(),
+    };
+
+    // Confidence that the score cut keeps the right pattern: how strong the
+    // best keyword/trend match is, and how far the kept scores sit above the
+    // first dropped one. Exclusions by candle count are certain.
+    let best = scored.first().map_or(0.0, |(s, _)| *s);
+    let confidence = match scored.get(cut) {
+        _ if scored.is_empty() => 0.0,
+        None => 1.0,
+        Some(&(next, _)) => {
+            let strength = (best / 3.0).min(1.0);
+            let separation = if best > 0.0 { ((scored[cut - 1].0 - next) / best).min(1.0) } else { 0.0 };
+            0.5 * strength + 0.5 * separation
+        }
+    };
+
+    if confidence < config.min_confidence {
+        let all: HashSet<&str> = taxonomy.patterns.iter().map(|p| p.name.as_str()).collect();
+        let reason = format!(
+            "confidence {:.2} below threshold {:.2}, full taxonomy sent",
+            confidence, config.min_confidence
+        );
+        return narrow(taxonomy, &all, reason, confidence);
+    }
+
+    scored.truncate(cut);
+
+    let kept: HashSet<&str> = scored.iter().map(|(_, p)| p.name.as_str()).collect();
+    let reason = if kept.len() == total {
+        "filter kept every pattern".to_string()
+    } else {
+        match count_limit {
+            Some(n) => format!("{} candles, top {} by keyword/trend match{}", n, kept.len(), trend_note),
+            None => format!("top {} by keyword/trend match{}", kept.len(), trend_note),
+        }
+    };
+    narrow(taxonomy, &kept, reason, confidence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn pattern(name: &str, category: PatternCategory, description: &str) -> Pattern {
+        Pattern {
+            name: name.to_string(),
+            category,
+            direction: Direction::Neutral,
+            description: description.to_string(),
+        }
+    }
+
+    /// Scores against `features()`: Alpha 3, Beta 2, Cee 2, Dee 1, Eee 0.
+    fn taxonomy() -> Taxonomy {
+        Taxonomy {
+            name: "test".to_string(),
+            patterns: vec![
+                pattern("Alpha", PatternCategory::Single, "gamma delta epsilon"),
+                pattern("Beta", PatternCategory::Two, "zeta eta"),
+                pattern("Cee", PatternCategory::Single, "gamma eta"),
+                pattern("Dee", PatternCategory::Three, "epsilon"),
+                pattern("Eee", PatternCategory::Multi, "omega"),
+            ],
+            path: PathBuf::new(),
+            version: 0,
+        }
+    }
+
+    fn features() -> ChartFeatures {
+        ChartFeatures {
+            terms: ["gamma", "delta", "epsilon", "zeta", "eta"].iter().map(|t| t.to_string()).collect(),
+            ..ChartFeatures::default()
+        }
+    }
+
+    fn config(max_candidates: usize, min_confidence: f64) -> PrefilterConfig {
+        PrefilterConfig { enabled: true, max_candidates, min_confidence }
+    }
+
+    fn names(taxonomy: &Taxonomy) -> Vec<&str> {
+        taxonomy.patterns.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn cut_keeps_patterns_tied_with_the_last_one() {
+        let (kept, report) = filter(&taxonomy(), &features(), &config(2, 0.5));
+        assert_eq!(names(&kept), ["Alpha", "Beta", "Cee"]);
+        assert!(report.applied);
+        assert_eq!((report.candidates, report.total), (3, 5));
+        assert!(report.estimated_tokens_saved > 0);
+    }
+
+    #[test]
+    fn confidence_weighs_best_score_and_separation() {
+        // strength min(3 / 3, 1) = 1, separation (2 - 1) / 3
+        let (_, report) = filter(&taxonomy(), &features(), &config(2, 0.5));
+        assert!((report.confidence - (0.5 + 0.5 / 3.0)).abs() < 1e-9, "{}", report.confidence);
+
+        // Nothing cut by score: certain
+        let (kept, report) = filter(&taxonomy(), &features(), &config(10, 0.5));
+        assert_eq!(report.confidence, 1.0);
+        assert_eq!(kept.patterns.len(), 5);
+        assert_eq!(report.reason, "filter kept every pattern");
+    }
+
+    #[test]
+    fn unsure_filter_sends_the_full_taxonomy() {
+        let (kept, report) = filter(&taxonomy(), &features(), &config(2, 0.9));
+        assert_eq!(kept.patterns.len(), 5);
+        assert!(!report.applied);
+        assert_eq!(report.estimated_tokens_saved, 0);
+        assert!(report.reason.ends_with("full taxonomy sent"), "{}", report.reason);
+    }
+
+    #[test]
+    fn short_chart_excludes_longer_patterns() {
+        let features = ChartFeatures { candle_count: Some(2), ..features() };
+        let (kept, report) = filter(&taxonomy(), &features, &config(10, 0.5));
+        assert_eq!(names(&kept), ["Alpha", "Beta", "Cee"]);
+        assert_eq!(report.confidence, 1.0);
+        assert!(report.reason.starts_with("2 candles"), "{}", report.reason);
+    }
+
+    #[test]
+    fn disabled_filter_sends_everything() {
+        let config = PrefilterConfig { enabled: false, ..config(2, 0.5) };
+        let (kept, report) = filter(&taxonomy(), &features(), &config);
+        assert_eq!(kept.patterns.len(), 5);
+        assert_eq!(report.reason, "disabled");
+    }
+}
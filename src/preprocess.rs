@@ -0,0 +1,177 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use serde::Serialize;
+
+use crate::extract;
+
+/// Decoding refuses anything larger, before allocating pixels.
+const MAX_DECODE_DIMENSION: u32 = 20_000;
+const MIN_DIMENSION: u32 = 32;
+/// Border rows/columns within this colour distance of the background are trimmed.
+const CROP_TOLERANCE: u32 = 40;
+const CROP_MARGIN: u32 = 8;
+const JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+}
+
+impl OutputFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PreprocessConfig {
+    /// Longest side after downscaling.
+    pub max_dimension: u32,
+    /// Default for the per-request `autocrop` option.
+    pub autocrop: bool,
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreprocessReport {
+    pub original_format: String,
+    pub original_bytes: usize,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: usize,
+    pub format: OutputFormat,
+    /// EXIF orientation was applied.
+    pub rotated: bool,
+    pub cropped: bool,
+    pub downscaled: bool,
+}
+
+pub struct PreparedImage {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+    pub report: PreprocessReport,
+}
+
+/// Sniffs the real format from the bytes, ignoring whatever the client claimed.
+pub fn detect_format(bytes: &[u8]) -> Result<ImageFormat, String> {
+    match image::guess_format(bytes) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif)) => {
+            Ok(format)
+        }
+        Ok(other) => Err(format!(
+            "Unsupported image format {:?} (expected PNG, JPEG, WebP or GIF)",
+            other
+        )),
+        Err(_) => Err("Upload is not a PNG, JPEG, WebP or GIF image".to_string()),
+    }
+}
+
+/// Bounding box of everything that differs from the border colour, padded by
+/// `CROP_MARGIN`; `None` when there is nothing worth trimming.
+fn content_bounds(img: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    let rgb = img.to_rgb8();
+    let (w, h) = rgb.dimensions();
+    let background = extract::dominant(
+        (0..w)
+            .flat_map(|x| [(x, 0), (x, h - 1)])
+            .chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]))
+            .map(|(x, y)| rgb.get_pixel(x, y)),
+    );
+    let content = |x: u32, y: u32| extract::distance(rgb.get_pixel(x, y), &background) > CROP_TOLERANCE;
+
+    let top = (0..h).find(|&y| (0..w).any(|x| content(x, y)))?;
+    let bottom = (0..h).rev().find(|&y| (0..w).any(|x| content(x, y)))?;
+    let left = (0..w).find(|&x| (top..=bottom).any(|y| content(x, y)))?;
+    let right = (0..w).rev().find(|&x| (top..=bottom).any(|y| content(x, y)))?;
+
+    let x0 = left.saturating_sub(CROP_MARGIN);
+    let y0 = top.saturating_sub(CROP_MARGIN);
+    let x1 = (right + CROP_MARGIN).min(w - 1);
+    let y1 = (bottom + CROP_MARGIN).min(h - 1);
+    let (cw, ch) = (x1 - x0 + 1, y1 - y0 + 1);
+    if (cw, ch) == (w, h) || cw < MIN_DIMENSION || ch < MIN_DIMENSION {
+        return None;
+    }
+    Some((x0, y0, cw, ch))
+}
+
+/// Decodes, orients, optionally crops, downscales and re-encodes an upload.
+pub fn prepare(
+    bytes: &[u8],
+    format: ImageFormat,
+    config: &PreprocessConfig,
+    autocrop: bool,
+) -> Result<PreparedImage, String> {
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODE_DIMENSION);
+    reader.limits(limits);
+
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| format!("Invalid {:?} image: {}", format, e))?;
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("Invalid {:?} image: {}", format, e))?;
+    let (original_width, original_height) = (img.width(), img.height());
+    if original_width < MIN_DIMENSION || original_height < MIN_DIMENSION {
+        return Err(format!(
+            "Image is too small ({}x{}, minimum {}x{})",
+            original_width, original_height, MIN_DIMENSION, MIN_DIMENSION
+        ));
+    }
+
+    let rotated = orientation != image::metadata::Orientation::NoTransforms;
+    img.apply_orientation(orientation);
+
+    let crop = if autocrop { content_bounds(&img) } else { None };
+    if let Some((x, y, w, h)) = crop {
+        img = img.crop_imm(x, y, w, h);
+    }
+
+    let downscaled = img.width().max(img.height()) > config.max_dimension;
+    if downscaled {
+        img = img.resize(config.max_dimension, config.max_dimension, FilterType::Triangle);
+    }
+
+    let img = DynamicImage::ImageRgb8(img.to_rgb8());
+    let mut encoded = Vec::new();
+    match config.format {
+        OutputFormat::Png => img.write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png),
+        OutputFormat::Jpeg => {
+            img.write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY))
+        }
+    }
+    .map_err(|e| format!("Failed to re-encode image: {}", e))?;
+
+    Ok(PreparedImage {
+        content_type: config.format.content_type(),
+        report: PreprocessReport {
+            original_format: format!("{:?}", format).to_lowercase(),
+            original_bytes: bytes.len(),
+            original_width,
+            original_height,
+            width: img.width(),
+            height: img.height(),
+            bytes: encoded.len(),
+            format: config.format,
+            rotated,
+            cropped: crop.is_some(),
+            downscaled,
+        },
+        bytes: encoded,
+    })
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+pub const DEFAULT_PROMPT: &str = "default";
+
+// Built-in copies of prompts/*/default.txt, used when the directory is missing
+// so the binary still runs outside the container layout.
+const BUILTIN_VISION: &str = include_str!("../prompts/vision/default.txt");
+const BUILTIN_REASONER: &str = include_str!("../prompts/reasoner/default.txt");
+
+const SYSTEM_MARKER: &str = "### system";
+const USER_MARKER: &str = "### user";
+
+const VISION_PLACEHOLDERS: &[&str] = &[];
+const REASONER_PLACEHOLDERS: &[&str] = &[
+    "taxonomy",
+    "taxonomy_name",
+    "pattern_count",
+    "chart_description",
+    "examples",
+    "trend_context",
+    "occurrences_instruction",
+    "language",
+    "language_instruction",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Vision,
+    Reasoner,
+}
+
+impl Stage {
+    fn dir(&self) -> &'static str {
+        match self {
+            Stage::Vision => "vision",
+            Stage::Reasoner => "reasoner",
+        }
+    }
+
+    fn placeholders(&self) -> &'static [&'static str] {
+        match self {
+            Stage::Vision => VISION_PLACEHOLDERS,
+            Stage::Reasoner => REASONER_PLACEHOLDERS,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PromptTemplate {
+    pub name: String,
+    /// First 12 hex chars of the SHA-256 of the file contents.
+    pub version: String,
+    /// Whole file for vision templates; the `### system` section for reasoner ones.
+    pub system: String,
+    /// The `### user` section of reasoner templates; empty for vision.
+    pub user: String,
+}
+
+/// Identifies the exact template that produced a result.
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptRef {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PromptSummary {
+    pub stage: Stage,
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Default)]
+pub struct PromptLibrary {
+    vision: BTreeMap<String, Arc<PromptTemplate>>,
+    reasoner: BTreeMap<String, Arc<PromptTemplate>>,
+}
+
+impl PromptTemplate {
+    fn parse(stage: Stage, name: &str, text: &str) -> Result<Self, String> {
+        let version = hash_version(text);
+
+        let (system, user) = match stage {
+            Stage::Vision => (text.trim().to_string(), String::new()),
+            Stage::Reasoner => {
+                let body = text
+                    .trim_start()
+                    .strip_prefix(SYSTEM_MARKER)
+                    .ok_or_else(|| format!("must start with a {:?} line", SYSTEM_MARKER))?;
+                let (system, user) = body
+                    .split_once(&format!("\n{}\n", USER_MARKER))
+                    .ok_or_else(|| format!("missing a {:?} line", USER_MARKER))?;
+                (system.trim().to_string() + "\n", user.trim().to_string())
+            }
+        };
+
+        for placeholder in find_placeholders(&system).chain(find_placeholders(&user)) {
+            if !stage.placeholders().contains(&placeholder) {
+                warn!(
+                    "Prompt {}/{} uses unknown placeholder {{{{{}}}}}",
+                    stage.dir(),
+                    name,
+                    placeholder
+                );
+            }
+        }
+
+        Ok(PromptTemplate {
+            name: name.to_string(),
+            version,
+            system,
+            user,
+        })
+    }
+
+    pub fn reference(&self) -> PromptRef {
+        PromptRef {
+            name: self.name.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+fn hash_version(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
+}
+
+fn find_placeholders(text: &str) -> impl Iterator<Item = &str> {
+    text.split("{{")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("}}").map(|(name, _)| name.trim()))
+}
+
+/// Substitutes the template's own `{{key}}` tokens in one pass, so `{{...}}`
+/// inside substituted values is never expanded; unknown keys are left untouched.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        let Some(close) = rest[open + 2..].find("}}") else {
+            break;
+        };
+        let token = &rest[open..open + 2 + close + 2];
+        let key = token[2..token.len() - 2].trim();
+        out.push_str(&rest[..open]);
+        match values.iter().find(|(k, _)| *k == key) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(token),
+        }
+        rest = &rest[open + token.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+impl PromptLibrary {
+    /// Loads `<dir>/vision/*.txt` and `<dir>/reasoner/*.txt`, keyed by file stem.
+    /// A stage without a readable `default` template gets the built-in one.
+    pub fn load(dir: &str) -> Self {
+        let mut library = PromptLibrary::default();
+
+        for stage in [Stage::Vision, Stage::Reasoner] {
+            let stage_dir = Path::new(dir).join(stage.dir());
+            let templates = match stage {
+                Stage::Vision => &mut library.vision,
+                Stage::Reasoner => &mut library.reasoner,
+            };
+
+            if let Ok(entries) = std::fs::read_dir(&stage_dir) {
+                for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+                    if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+                        continue;
+                    }
+                    let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                        continue;
+                    };
+                    let parsed = std::fs::read_to_string(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| PromptTemplate::parse(stage, name, &text));
+                    match parsed {
+                        Ok(template) => {
+                            info!(
+                                "Loaded {} prompt {:?} ({})",
+                                stage.dir(),
+                                name,
+                                template.version
+                            );
+                            templates.insert(name.to_string(), Arc::new(template));
+                        }
+                        Err(e) => warn!("Skipping prompt {}: {}", path.display(), e),
+                    }
+                }
+            } else {
+                warn!("Prompt directory {} not readable", stage_dir.display());
+            }
+
+            if !templates.contains_key(DEFAULT_PROMPT) {
+                let builtin = match stage {
+                    Stage::Vision => BUILTIN_VISION,
+                    Stage::Reasoner => BUILTIN_REASONER,
+                };
+                let template = PromptTemplate::parse(stage, DEFAULT_PROMPT, builtin)
+                    .expect("built-in prompt template is valid");
+                warn!("Using built-in {} prompt ({})", stage.dir(), template.version);
+                templates.insert(DEFAULT_PROMPT.to_string(), Arc::new(template));
+            }
+        }
+
+        library
+    }
+
+    pub fn get(&self, stage: Stage, name: Option<&str>) -> Result<Arc<PromptTemplate>, String> {
+        let name = name.unwrap_or(DEFAULT_PROMPT);
+        let templates = match stage {
+            Stage::Vision => &self.vision,
+            Stage::Reasoner => &self.reasoner,
+        };
+        templates
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown {} prompt: {}", stage.dir(), name))
+    }
+
+    pub fn summaries(&self) -> Vec<PromptSummary> {
+        let vision = self.vision.values().map(|t| (Stage::Vision, t));
+        let reasoner = self.reasoner.values().map(|t| (Stage::Reasoner, t));
+        vision
+            .chain(reasoner)
+            .map(|(stage, t)| PromptSummary {
+                stage,
+                name: t.name.clone(),
+                version: t.version.clone(),
+            })
+            .collect()
+    }
+}
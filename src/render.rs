@@ -0,0 +1,326 @@
+use std::fmt::Write as _;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use axum::{
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use image::{ImageFormat, RgbImage};
+use serde::Deserialize;
+
+use crate::candles::Candle;
+use crate::import::OhlcInput;
+use crate::{ApiError, AppState};
+
+type Color = [u8; 3];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    pub grid: Color,
+    pub bullish: Color,
+    pub bearish: Color,
+    /// Body outline and wick colour; `None` draws them in the body colour.
+    pub outline: Option<Color>,
+    pub highlight: Color,
+}
+
+pub const THEMES: &[Theme] = &[
+    Theme {
+        name: "light",
+        background: [255, 255, 255],
+        grid: [230, 232, 236],
+        bullish: [38, 166, 154],
+        bearish: [239, 83, 80],
+        outline: None,
+        highlight: [255, 193, 7],
+    },
+    Theme {
+        name: "dark",
+        background: [19, 23, 34],
+        grid: [42, 46, 57],
+        bullish: [8, 153, 129],
+        bearish: [242, 54, 69],
+        outline: None,
+        highlight: [255, 214, 0],
+    },
+    Theme {
+        name: "classic",
+        background: [255, 255, 255],
+        grid: [220, 220, 220],
+        bullish: [255, 255, 255],
+        bearish: [0, 0, 0],
+        outline: Some([0, 0, 0]),
+        highlight: [66, 133, 244],
+    },
+    Theme {
+        name: "contrast",
+        background: [250, 250, 245],
+        grid: [225, 225, 215],
+        bullish: [33, 99, 214],
+        bearish: [242, 140, 40],
+        outline: None,
+        highlight: [120, 200, 80],
+    },
+];
+
+pub fn theme(name: &str) -> Option<&'static Theme> {
+    THEMES.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions<'a> {
+    pub theme: &'a Theme,
+    pub width: u32,
+    pub height: u32,
+    pub gridlines: bool,
+    /// Draws a volume pane under the candles when they carry volume.
+    pub volume: bool,
+    /// Inclusive candle index ranges shaded behind the candles.
+    pub highlights: &'a [[usize; 2]],
+}
+
+impl Default for RenderOptions<'_> {
+    fn default() -> Self {
+        RenderOptions {
+            theme: &THEMES[0],
+            width: 1024,
+            height: 640,
+            gridlines: true,
+            volume: true,
+            highlights: &[],
+        }
+    }
+}
+
+const PADDING: f64 = 20.0;
+const GRID_LINES: u32 = 6;
+const VOLUME_PANE: f64 = 0.2;
+const MIN_SIZE: u32 = 200;
+const MAX_SIZE: u32 = 4000;
+
+/// Pixel-aligned filled rectangle; `x1`/`y1` are exclusive.
+struct Rect {
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+    color: Color,
+    opacity: f64,
+}
+
+fn rect(x0: f64, y0: f64, x1: f64, y1: f64, color: Color) -> Rect {
+    Rect { x0: x0.round(), y0: y0.round(), x1: x1.round(), y1: y1.round(), color, opacity: 1.0 }
+}
+
+/// Lays the chart out as rectangles so PNG and SVG output stay identical.
+fn scene(candles: &[Candle], options: &RenderOptions) -> Result<Vec<Rect>, String> {
+    if candles.is_empty() {
+        return Err("No candles to render".to_string());
+    }
+    for (w, what) in [(options.width, "width"), (options.height, "height")] {
+        if !(MIN_SIZE..=MAX_SIZE).contains(&w) {
+            return Err(format!("Chart {} must be between {} and {}", what, MIN_SIZE, MAX_SIZE));
+        }
+    }
+    for &[start, end] in options.highlights {
+        if start > end || end >= candles.len() {
+            return Err(format!(
+                "Highlight [{}, {}] is outside the {} candles",
+                start,
+                end,
+                candles.len()
+            ));
+        }
+    }
+
+    let theme = options.theme;
+    let (width, height) = (options.width as f64, options.height as f64);
+    let mut shapes = vec![rect(0.0, 0.0, width, height, theme.background)];
+
+    let max_volume = candles.iter().filter_map(|c| c.volume).fold(0.0, f64::max);
+    let with_volume = options.volume && max_volume > 0.0;
+
+    let left = PADDING;
+    let right = width - PADDING;
+    let top = PADDING;
+    let bottom = height - PADDING;
+    let price_bottom = if with_volume {
+        bottom - (bottom - top) * VOLUME_PANE - PADDING / 2.0
+    } else {
+        bottom
+    };
+
+    if options.gridlines {
+        for i in 0..=GRID_LINES {
+            let f = i as f64 / GRID_LINES as f64;
+            let y = top + (price_bottom - top) * f;
+            shapes.push(rect(left, y, right, y + 1.0, theme.grid));
+            let x = left + (right - left) * f;
+            shapes.push(rect(x, top, x + 1.0, bottom, theme.grid));
+        }
+    }
+
+    let slot = (right - left) / candles.len() as f64;
+    for &[start, end] in options.highlights {
+        let mut shade = rect(
+            left + slot * start as f64,
+            top,
+            left + slot * (end + 1) as f64,
+            bottom,
+            theme.highlight,
+        );
+        shade.opacity = 0.25;
+        shapes.push(shade);
+    }
+
+    let high = candles.iter().map(|c| c.high).fold(f64::MIN, f64::max);
+    let low = candles.iter().map(|c| c.low).fold(f64::MAX, f64::min);
+    let margin = ((high - low) * 0.05).max(high.abs() * 1e-4).max(1e-9);
+    let (high, low) = (high + margin, low - margin);
+    let y_of = |price: f64| (top + (high - price) / (high - low) * (price_bottom - top)).round();
+    let half_body = (slot * 0.35).floor();
+
+    for (i, candle) in candles.iter().enumerate() {
+        let x = (left + slot * (i as f64 + 0.5)).round();
+        let body = if candle.is_bullish() { theme.bullish } else { theme.bearish };
+        let line = theme.outline.unwrap_or(body);
+
+        shapes.push(rect(x, y_of(candle.high), x + 1.0, y_of(candle.low) + 1.0, line));
+
+        let body_top = y_of(candle.open.max(candle.close));
+        let body_bottom = y_of(candle.open.min(candle.close)).max(body_top) + 1.0;
+        shapes.push(rect(x - half_body, body_top, x + half_body + 1.0, body_bottom, line));
+        if theme.outline.is_some() && half_body > 0.0 && body_bottom - body_top > 2.0 {
+            shapes.push(rect(x - half_body + 1.0, body_top + 1.0, x + half_body, body_bottom - 1.0, body));
+        }
+
+        if let (true, Some(volume)) = (with_volume, candle.volume) {
+            let bar_top = bottom - (bottom - price_bottom - PADDING / 2.0) * volume / max_volume;
+            // Outlined themes have a body colour that can match the background.
+            let color = if theme.outline.is_some() { line } else { body };
+            let mut bar = rect(x - half_body, bar_top, x + half_body + 1.0, bottom, color);
+            bar.opacity = 0.6;
+            shapes.push(bar);
+        }
+    }
+
+    Ok(shapes)
+}
+
+/// Renders candles left to right into a PNG, scaled to fill the canvas.
+pub fn render_png(candles: &[Candle], options: &RenderOptions) -> Result<Vec<u8>, String> {
+    let shapes = scene(candles, options)?;
+    let mut img = RgbImage::new(options.width, options.height);
+    let (w, h) = (options.width as i64, options.height as i64);
+
+    for shape in &shapes {
+        for y in (shape.y0 as i64).max(0)..(shape.y1 as i64).min(h) {
+            for x in (shape.x0 as i64).max(0)..(shape.x1 as i64).min(w) {
+                let pixel = img.get_pixel_mut(x as u32, y as u32);
+                for (channel, &target) in pixel.0.iter_mut().zip(&shape.color) {
+                    *channel = (*channel as f64 * (1.0 - shape.opacity)
+                        + target as f64 * shape.opacity)
+                        .round() as u8;
+                }
+            }
+        }
+    }
+
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(png)
+}
+
+pub fn render_svg(candles: &[Candle], options: &RenderOptions) -> Result<String, String> {
+    let shapes = scene(candles, options)?;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" shape-rendering="crispEdges">"#,
+        w = options.width,
+        h = options.height
+    );
+    for s in shapes.iter().filter(|s| s.x1 > s.x0 && s.y1 > s.y0) {
+        let [r, g, b] = s.color;
+        let _ = write!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#{:02x}{:02x}{:02x}""##,
+            s.x0,
+            s.y0,
+            s.x1 - s.x0,
+            s.y1 - s.y0,
+            r,
+            g,
+            b
+        );
+        if s.opacity < 1.0 {
+            let _ = write!(svg, r#" fill-opacity="{}""#, s.opacity);
+        }
+        svg.push_str("/>");
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+#[derive(Deserialize)]
+struct RenderRequest {
+    #[serde(flatten)]
+    ohlc: OhlcInput,
+    #[serde(default)]
+    format: Format,
+    theme: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    gridlines: Option<bool>,
+    #[serde(default)]
+    volume: bool,
+    #[serde(default)]
+    highlights: Vec<[usize; 2]>,
+}
+
+async fn render_handler(Json(request): Json<RenderRequest>) -> Result<impl IntoResponse, ApiError> {
+    let bad_request = |e: String| (StatusCode::BAD_REQUEST, e);
+    let candles = request.ohlc.into_candles().map_err(bad_request)?;
+
+    let defaults = RenderOptions::default();
+    let theme = match request.theme.as_deref() {
+        Some(name) => theme(name).ok_or_else(|| {
+            let names: Vec<&str> = THEMES.iter().map(|t| t.name).collect();
+            bad_request(format!("Unknown theme {} (available: {})", name, names.join(", ")))
+        })?,
+        None => defaults.theme,
+    };
+    let options = RenderOptions {
+        theme,
+        width: request.width.unwrap_or(defaults.width),
+        height: request.height.unwrap_or(defaults.height),
+        gridlines: request.gridlines.unwrap_or(defaults.gridlines),
+        volume: request.volume,
+        highlights: &request.highlights,
+    };
+
+    let (content_type, body) = match request.format {
+        Format::Png => ("image/png", render_png(&candles, &options).map_err(bad_request)?),
+        Format::Svg => (
+            "image/svg+xml",
+            render_svg(&candles, &options).map_err(bad_request)?.into_bytes(),
+        ),
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body))
+}
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new().route("/render", post(render_handler))
+}
@@ -0,0 +1,468 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::candles::Candle;
+use crate::import::OhlcInput;
+use crate::models::{Direction, OccurrenceSource, PatternOccurrence};
+use crate::prefilter::Trend;
+use crate::rules;
+use crate::taxonomy::Taxonomy;
+use crate::trend::{self, TrendSource};
+use crate::{ApiError, AppState};
+
+const DAY: i64 = 86_400;
+const WEEK: i64 = 7 * DAY;
+/// Day number of the first Monday after the epoch (a Thursday); weeks start on Monday.
+const FIRST_MONDAY: i64 = 4;
+/// Upper bound on timeframes per request; each is resampled and scanned.
+pub const MAX_TIMEFRAMES: usize = 6;
+
+/// Bar length, written like `15m`, `4h`, `1d` or `1w`. Lengths of a day or
+/// more must be whole days, so bars never split a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeframe {
+    seconds: i64,
+}
+
+impl FromStr for Timeframe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let invalid = || format!("Invalid timeframe {} (expected e.g. 15m, 4h, 1d or 1w)", s);
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let count: i64 = s[..split].parse().map_err(|_| invalid())?;
+        let unit = match &s[split..] {
+            "s" => 1,
+            "m" | "min" => 60,
+            "h" => 3_600,
+            "d" => DAY,
+            "w" => WEEK,
+            _ => return Err(invalid()),
+        };
+        let seconds = count.checked_mul(unit).filter(|&s| s > 0).ok_or_else(invalid)?;
+        if seconds > DAY && seconds % DAY != 0 {
+            return Err(format!("Timeframe {} must be a whole number of days", s));
+        }
+        Ok(Timeframe { seconds })
+    }
+}
+
+impl fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (size, unit) = [(WEEK, "w"), (DAY, "d"), (3_600, "h"), (60, "m")]
+            .into_iter()
+            .find(|(size, _)| self.seconds % size == 0)
+            .unwrap_or((1, "s"));
+        write!(f, "{}{}", self.seconds / size, unit)
+    }
+}
+
+impl Serialize for Timeframe {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Trading hours in UTC, written `HH:MM-HH:MM`; may run past midnight.
+/// Intraday bars restart at the session open and daily bars are whole sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    /// Seconds after midnight UTC.
+    start: i64,
+    length: i64,
+}
+
+impl Default for Session {
+    /// The whole UTC day.
+    fn default() -> Self {
+        Session { start: 0, length: DAY }
+    }
+}
+
+fn time_of_day(s: &str) -> Option<i64> {
+    let (hours, minutes) = s.trim().split_once(':')?;
+    let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+    ((0..24).contains(&hours) && (0..60).contains(&minutes)).then_some(hours * 3_600 + minutes * 60)
+}
+
+impl FromStr for Session {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .and_then(|(start, end)| Some((time_of_day(start)?, time_of_day(end)?)))
+            .ok_or_else(|| format!("Invalid session {} (expected HH:MM-HH:MM in UTC)", s))?;
+        let length = (end - start).rem_euclid(DAY);
+        Ok(Session {
+            start,
+            length: if length == 0 { DAY } else { length },
+        })
+    }
+}
+
+impl Session {
+    /// Open of the session containing `time`; `None` outside trading hours.
+    fn open(&self, time: i64) -> Option<i64> {
+        let offset = (time - self.start).rem_euclid(DAY);
+        (offset < self.length).then_some(time - offset)
+    }
+
+    /// Day a session is counted on: the day it closes, so an overnight
+    /// session opening Sunday evening belongs to Monday.
+    fn day(&self, open: i64) -> i64 {
+        (open + self.length - 1).div_euclid(DAY)
+    }
+
+    /// Open of the session counted on `day`.
+    fn open_of(&self, day: i64) -> i64 {
+        day * DAY + (self.start + self.length - 1).rem_euclid(DAY) + 1 - self.length
+    }
+
+    /// Start and end of the `timeframe` bar containing `time`.
+    fn bucket(&self, time: i64, timeframe: Timeframe) -> Option<(i64, i64)> {
+        let open = self.open(time)?;
+        let close = open + self.length;
+        if timeframe.seconds < DAY {
+            let start = open + (time - open) / timeframe.seconds * timeframe.seconds;
+            return Some((start, (start + timeframe.seconds).min(close)));
+        }
+        let days = timeframe.seconds / DAY;
+        let anchor = if timeframe.seconds % WEEK == 0 { FIRST_MONDAY } else { 0 };
+        let first = (self.day(open) - anchor).div_euclid(days) * days + anchor;
+        Some((self.open_of(first), self.open_of(first + days - 1) + self.length))
+    }
+}
+
+/// Bars aggregated to a longer timeframe. Buckets without input bars are
+/// left out rather than filled, so gaps in the data stay gaps.
+#[derive(Debug, Serialize)]
+pub struct Resampled {
+    pub timeframe: Timeframe,
+    pub candles: Vec<Candle>,
+    /// Input bars outside the session, ignored.
+    pub dropped: usize,
+    /// The last bar's period had not ended by the last input bar.
+    pub partial_last: bool,
+}
+
+/// Aggregates timestamped bars (or ticks as flat bars) into `timeframe` bars
+/// stamped with their period's start. Input must be in time order.
+pub fn resample(candles: &[Candle], timeframe: Timeframe, session: Session) -> Result<Resampled, String> {
+    let mut bars: Vec<Candle> = Vec::new();
+    let mut last: Option<(i64, i64)> = None;
+    let mut previous_time = None;
+    let mut step = None;
+    let mut dropped = 0;
+
+    for (i, candle) in candles.iter().enumerate() {
+        let time = candle
+            .time
+            .ok_or_else(|| format!("Candle {} has no time; resampling needs timestamps", i))?;
+        if let Some(previous) = previous_time {
+            if time < previous {
+                return Err(format!("Candle {} is out of time order", i));
+            }
+            if time > previous {
+                step = Some(step.map_or(time - previous, |s: i64| s.min(time - previous)));
+            }
+        }
+        previous_time = Some(time);
+
+        let Some((start, end)) = session.bucket(time, timeframe) else {
+            dropped += 1;
+            continue;
+        };
+        match bars.last_mut() {
+            Some(bar) if last.is_some_and(|(s, _)| s == start) => {
+                bar.high = bar.high.max(candle.high);
+                bar.low = bar.low.min(candle.low);
+                bar.close = candle.close;
+                bar.volume = match (bar.volume, candle.volume) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+            }
+            _ => bars.push(Candle { time: Some(start), ..*candle }),
+        }
+        last = Some((start, end));
+    }
+
+    if bars.is_empty() {
+        return Err("No candles inside the session".to_string());
+    }
+    let partial_last = match (last, previous_time) {
+        (Some((_, end)), Some(time)) => time + step.unwrap_or(0) < end,
+        _ => false,
+    };
+    Ok(Resampled { timeframe, candles: bars, dropped, partial_last })
+}
+
+/// What one timeframe shows at its latest bar.
+#[derive(Debug, Serialize)]
+pub struct TimeframeAnalysis {
+    pub timeframe: Timeframe,
+    pub bars: usize,
+    pub partial_last: bool,
+    /// Trend leading into the latest bar.
+    pub trend: Option<Trend>,
+    /// Rule-detected taxonomy patterns completing on the latest bar.
+    pub patterns: Vec<PatternOccurrence>,
+    /// Net direction of those patterns, else of the trend.
+    pub bias: Option<Direction>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Alignment {
+    pub bullish: Vec<Timeframe>,
+    pub bearish: Vec<Timeframe>,
+    pub neutral: Vec<Timeframe>,
+    /// Bias of most timeframes; `None` on a tie.
+    pub direction: Option<Direction>,
+    /// At least two timeframes lean one way and none the other.
+    pub aligned: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MultiTimeframe {
+    pub timeframes: Vec<TimeframeAnalysis>,
+    pub alignment: Alignment,
+}
+
+fn analyze_timeframe(resampled: Resampled, taxonomy: &Taxonomy) -> TimeframeAnalysis {
+    let series = &resampled.candles;
+    let trend = trend::measure(series, TrendSource::Ohlc).map(|context| context.trend);
+    let patterns: Vec<PatternOccurrence> = rules::detect(series)
+        .into_iter()
+        .filter_map(|detection| {
+            let pattern = taxonomy.find(detection.pattern)?;
+            Some(PatternOccurrence {
+                pattern: pattern.name.clone(),
+                direction: Some(pattern.direction),
+                start: detection.start,
+                end: detection.end,
+                confidence: None,
+                sources: vec![OccurrenceSource::Rules],
+                actionable: true,
+            })
+        })
+        .collect();
+
+    let signs: Vec<f64> = patterns.iter().filter_map(|p| p.direction?.sign()).collect();
+    let bias = if signs.is_empty() {
+        trend.map(|trend| match trend {
+            Trend::Up => Direction::Bullish,
+            Trend::Down => Direction::Bearish,
+            Trend::Sideways => Direction::Neutral,
+        })
+    } else {
+        let net: f64 = signs.iter().sum();
+        Some(if net > 0.0 {
+            Direction::Bullish
+        } else if net < 0.0 {
+            Direction::Bearish
+        } else {
+            Direction::Neutral
+        })
+    };
+
+    TimeframeAnalysis {
+        timeframe: resampled.timeframe,
+        bars: series.len(),
+        partial_last: resampled.partial_last,
+        trend,
+        patterns,
+        bias,
+    }
+}
+
+/// Resamples `candles` to every timeframe, reads patterns and trend off the
+/// latest bar of each, and summarizes whether they point the same way.
+pub fn analyze(
+    candles: &[Candle],
+    timeframes: &[Timeframe],
+    session: Session,
+    taxonomy: &Taxonomy,
+) -> Result<MultiTimeframe, String> {
+    let timeframes = timeframes
+        .iter()
+        .map(|&timeframe| {
+            resample(candles, timeframe, session)
+                .map(|resampled| analyze_timeframe(resampled, taxonomy))
+                .map_err(|e| format!("{}: {}", timeframe, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let with_bias = |bias: Direction| -> Vec<Timeframe> {
+        timeframes
+            .iter()
+            .filter(|t| t.bias == Some(bias))
+            .map(|t| t.timeframe)
+            .collect()
+    };
+    let (bullish, bearish, neutral) = (
+        with_bias(Direction::Bullish),
+        with_bias(Direction::Bearish),
+        with_bias(Direction::Neutral),
+    );
+    let direction = match bullish.len().cmp(&bearish.len()) {
+        std::cmp::Ordering::Greater => Some(Direction::Bullish),
+        std::cmp::Ordering::Less => Some(Direction::Bearish),
+        std::cmp::Ordering::Equal => None,
+    };
+    let aligned = bullish.len() + bearish.len() >= 2 && (bullish.is_empty() || bearish.is_empty());
+
+    Ok(MultiTimeframe {
+        timeframes,
+        alignment: Alignment { bullish, bearish, neutral, direction, aligned },
+    })
+}
+
+/// Comma-separated timeframes, as in the `timeframes` analysis option.
+pub fn parse_timeframes(value: &str) -> Result<Vec<Timeframe>, String> {
+    let timeframes = value
+        .split(',')
+        .filter(|t| !t.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<Timeframe>, String>>()?;
+    if timeframes.len() > MAX_TIMEFRAMES {
+        return Err(format!("At most {} timeframes, got {}", MAX_TIMEFRAMES, timeframes.len()));
+    }
+    Ok(timeframes)
+}
+
+#[derive(Deserialize)]
+struct ResampleRequest {
+    #[serde(flatten)]
+    ohlc: OhlcInput,
+    timeframe: String,
+    session: Option<String>,
+}
+
+async fn resample_handler(Json(request): Json<ResampleRequest>) -> Result<Json<Resampled>, ApiError> {
+    let bad_request = |e: String| (StatusCode::BAD_REQUEST, e);
+    let candles = request.ohlc.into_candles().map_err(bad_request)?;
+    let timeframe = request.timeframe.parse().map_err(bad_request)?;
+    let session = match request.session.as_deref() {
+        Some(session) => session.parse().map_err(bad_request)?,
+        None => Session::default(),
+    };
+    resample(&candles, timeframe, session)
+        .map(Json)
+        .map_err(bad_request)
+}
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new().route("/resample", post(resample_handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3_600;
+
+    fn tf(s: &str) -> Timeframe {
+        s.parse().unwrap()
+    }
+
+    fn bar(time: i64, open: f64, high: f64, low: f64, close: f64, volume: f64) -> Candle {
+        Candle { time: Some(time), open, high, low, close, volume: Some(volume) }
+    }
+
+    fn hourly(hours: &[i64]) -> Vec<Candle> {
+        hours.iter().map(|&h| bar(h * HOUR, 1.0, 2.0, 0.5, 1.5, 10.0)).collect()
+    }
+
+    #[test]
+    fn timeframes_parse_and_print_in_their_largest_unit() {
+        assert_eq!(tf("15m").seconds, 900);
+        assert_eq!(tf(" 4H ").seconds, 4 * HOUR);
+        assert_eq!(tf("90min").seconds, 5_400);
+        for (input, shown) in [("15m", "15m"), ("60m", "1h"), ("48h", "2d"), ("7d", "1w"), ("90s", "90s"), ("120s", "2m")] {
+            assert_eq!(tf(input).to_string(), shown);
+        }
+        for bad in ["", "m", "0h", "4", "1y", "-1d"] {
+            assert!(bad.parse::<Timeframe>().is_err(), "{:?} parsed", bad);
+        }
+        assert_eq!("25h".parse::<Timeframe>(), Err("Timeframe 25h must be a whole number of days".to_string()));
+        assert_eq!(parse_timeframes("1h,,4h").unwrap(), [tf("1h"), tf("4h")]);
+        assert!(parse_timeframes("1m,5m,15m,1h,4h,1d,1w").is_err());
+    }
+
+    #[test]
+    fn overnight_session_belongs_to_the_day_it_closes() {
+        // Sunday 22:00 to Monday 21:00 UTC; day 4 is Monday 1970-01-05
+        let session: Session = "22:00-21:00".parse().unwrap();
+        assert_eq!(session, Session { start: 22 * HOUR, length: 23 * HOUR });
+        let sunday_open = 3 * DAY + 22 * HOUR;
+        let monday = 4 * DAY;
+
+        assert_eq!(session.open(3 * DAY + 23 * HOUR), Some(sunday_open));
+        assert_eq!(session.open(monday + 20 * HOUR), Some(sunday_open));
+        assert_eq!(session.open(monday + 21 * HOUR + 1_800), None);
+        assert_eq!(session.day(sunday_open), 4);
+        assert_eq!(session.open_of(4), sunday_open);
+
+        // Intraday bars count from the open and the last one stops at the close
+        assert_eq!(session.bucket(monday + HOUR + 1_800, tf("4h")), Some((sunday_open, monday + 2 * HOUR)));
+        assert_eq!(session.bucket(monday + 20 * HOUR, tf("4h")), Some((monday + 18 * HOUR, monday + 21 * HOUR)));
+        assert_eq!(session.bucket(monday + 10 * HOUR, tf("1d")), Some((sunday_open, monday + 21 * HOUR)));
+        assert_eq!(session.bucket(monday + 21 * HOUR + 1_800, tf("1h")), None);
+
+        let whole: Session = "00:00-00:00".parse().unwrap();
+        assert_eq!(whole, Session::default());
+        assert!("25:00-01:00".parse::<Session>().is_err());
+    }
+
+    #[test]
+    fn weeks_start_on_monday_across_the_epoch() {
+        let session = Session::default();
+        // Saturday 1970-01-03 falls in the week of Monday 1969-12-29
+        assert_eq!(session.bucket(2 * DAY + HOUR, tf("1w")), Some((-3 * DAY, 4 * DAY)));
+        assert_eq!(session.bucket(4 * DAY, tf("1w")), Some((4 * DAY, 11 * DAY)));
+        assert_eq!(session.bucket(-4 * DAY, tf("1w")), Some((-10 * DAY, -3 * DAY)));
+        // Other multi-day bars count from the epoch
+        assert_eq!(session.bucket(3 * DAY, tf("2d")), Some((2 * DAY, 4 * DAY)));
+    }
+
+    #[test]
+    fn resample_aggregates_bars_and_keeps_gaps() {
+        let candles = vec![
+            bar(0, 10.0, 12.0, 9.0, 11.0, 5.0),
+            bar(HOUR, 11.0, 13.0, 10.0, 12.0, 7.0),
+            // Hours 2 to 5 are missing
+            bar(6 * HOUR, 12.0, 12.5, 11.0, 11.5, 1.0),
+            bar(7 * HOUR, 11.5, 14.0, 11.4, 13.0, 2.0),
+        ];
+        let resampled = resample(&candles, tf("2h"), Session::default()).unwrap();
+        let times: Vec<Option<i64>> = resampled.candles.iter().map(|c| c.time).collect();
+        assert_eq!(times, [Some(0), Some(6 * HOUR)]);
+        let first = &resampled.candles[0];
+        assert_eq!((first.open, first.high, first.low, first.close, first.volume), (10.0, 13.0, 9.0, 12.0, Some(12.0)));
+        assert!(!resampled.partial_last);
+        assert_eq!(resampled.dropped, 0);
+    }
+
+    #[test]
+    fn partial_last_compares_the_next_bar_with_the_period_end() {
+        let session = Session::default();
+        assert!(resample(&hourly(&[0, 1, 2]), tf("4h"), session).unwrap().partial_last);
+        assert!(!resample(&hourly(&[0, 1, 2, 3]), tf("4h"), session).unwrap().partial_last);
+
+        // A bar cut short by the session close is complete once the session is
+        let evening: Session = "08:00-18:00".parse().unwrap();
+        let resampled = resample(&hourly(&[7, 15, 16, 17]), tf("4h"), evening).unwrap();
+        let times: Vec<Option<i64>> = resampled.candles.iter().map(|c| c.time).collect();
+        assert_eq!(times, [Some(12 * HOUR), Some(16 * HOUR)]);
+        assert_eq!(resampled.dropped, 1);
+        assert!(!resampled.partial_last);
+
+        assert!(resample(&hourly(&[1, 0]), tf("4h"), session).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::prefilter::tokenize;
+use crate::store::AnalysisStore;
+use crate::taxonomy::Taxonomy;
+use crate::text::truncate;
+
+// Standard Okapi BM25 parameters.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+const CHARS_PER_TOKEN: usize = 4;
+// Vision descriptions can run to thousands of characters; one example must
+// not eat the whole budget.
+const MAX_EXAMPLE_CHARS: usize = 2400;
+
+#[derive(Debug, Clone)]
+pub struct FewShotConfig {
+    /// Maximum examples per prompt; 0 disables retrieval.
+    pub max_examples: usize,
+    pub token_budget: usize,
+}
+
+/// A past chart description whose pattern was confirmed by user feedback.
+#[derive(Debug, Clone)]
+pub struct LabelledExample {
+    pub id: String,
+    pub pattern: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectedExample {
+    pub id: String,
+    pub pattern: String,
+    pub score: f64,
+}
+
+struct Doc {
+    example: LabelledExample,
+    tf: HashMap<String, u32>,
+    len: usize,
+}
+
+/// Built once from the store at startup and kept current as feedback arrives.
+pub struct Bm25Index {
+    docs: Vec<Doc>,
+    df: HashMap<String, u32>,
+    total_len: usize,
+}
+
+impl Doc {
+    fn new(example: LabelledExample) -> Self {
+        let tokens = tokenize(&example.description);
+        let mut tf: HashMap<String, u32> = HashMap::new();
+        for t in &tokens {
+            *tf.entry(t.clone()).or_default() += 1;
+        }
+        Doc {
+            example,
+            tf,
+            len: tokens.len(),
+        }
+    }
+}
+
+impl Bm25Index {
+    pub fn build(examples: Vec<LabelledExample>) -> Self {
+        let mut index = Bm25Index {
+            docs: Vec::new(),
+            df: HashMap::new(),
+            total_len: 0,
+        };
+        for example in examples {
+            let doc = Doc::new(example);
+            index.count(&doc, true);
+            index.docs.push(doc);
+        }
+        index
+    }
+
+    /// Adds or removes `doc`'s terms and length from the corpus statistics.
+    fn count(&mut self, doc: &Doc, add: bool) {
+        for t in doc.tf.keys() {
+            let df = self.df.entry(t.clone()).or_default();
+            if add {
+                *df += 1;
+            } else {
+                *df -= 1;
+                if *df == 0 {
+                    self.df.remove(t);
+                }
+            }
+        }
+        if add {
+            self.total_len += doc.len;
+        } else {
+            self.total_len -= doc.len;
+        }
+    }
+
+    /// Adds an example, or replaces the one with the same id when feedback
+    /// relabels an analysis.
+    pub fn upsert(&mut self, example: LabelledExample) {
+        let doc = Doc::new(example);
+        self.count(&doc, true);
+        match self.docs.iter().position(|d| d.example.id == doc.example.id) {
+            Some(i) => {
+                let old = std::mem::replace(&mut self.docs[i], doc);
+                self.count(&old, false);
+            }
+            None => self.docs.push(doc),
+        }
+    }
+
+    /// Every document with a positive score, best first.
+    pub fn search(&self, query: &str) -> Vec<(f64, &LabelledExample)> {
+        let n = self.docs.len() as f64;
+        let avg_len = if self.docs.is_empty() { 0.0 } else { self.total_len as f64 / n };
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scored: Vec<(f64, &LabelledExample)> = self
+            .docs
+            .iter()
+            .map(|doc| {
+                let score = terms
+                    .iter()
+                    .filter_map(|t| {
+                        let tf = *doc.tf.get(t)? as f64;
+                        let df = *self.df.get(t)? as f64;
+                        let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        let norm = K1 * (1.0 - B + B * doc.len as f64 / avg_len.max(1.0));
+                        Some(idf * tf * (K1 + 1.0) / (tf + norm))
+                    })
+                    .sum::<f64>();
+                (score, &doc.example)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+    }
+}
+
+/// Stored analyses labelled with the pattern their latest feedback confirmed.
+pub fn labelled_examples(store: &AnalysisStore) -> Vec<LabelledExample> {
+    store
+        .with_feedback()
+        .map(|(analysis, feedback)| LabelledExample {
+            id: analysis.id.clone(),
+            pattern: feedback.correct_pattern.clone(),
+            description: analysis.chart_description.clone(),
+        })
+        .collect()
+}
+
+/// Picks the most similar examples whose label is in `candidates`, within the
+/// example count and token budget, and renders them for the `{{examples}}`
+/// placeholder.
+pub fn select_examples(
+    index: &Bm25Index,
+    description: &str,
+    candidates: &Taxonomy,
+    config: &FewShotConfig,
+) -> (String, Vec<SelectedExample>) {
+    if config.max_examples == 0 {
+        return (String::new(), Vec::new());
+    }
+
+    let mut text = String::new();
+    let mut selected = Vec::new();
+    let mut used_tokens = 0;
+
+    for (score, example) in index.search(description) {
+        if selected.len() >= config.max_examples {
+            break;
+        }
+        if candidates.find(&example.pattern).is_none() {
+            continue;
+        }
+
+        let block = format!(
+            "\nExample {} — confirmed pattern: {}\nChart description:\n{}\n",
+            selected.len() + 1,
+            example.pattern,
+            truncate(example.description.trim(), MAX_EXAMPLE_CHARS)
+        );
+        let tokens = block.len().div_ceil(CHARS_PER_TOKEN);
+        if used_tokens + tokens > config.token_budget {
+            continue;
+        }
+
+        used_tokens += tokens;
+        text.push_str(&block);
+        selected.push(SelectedExample {
+            id: example.id.clone(),
+            pattern: example.pattern.clone(),
+            score,
+        });
+    }
+
+    if selected.is_empty() {
+        return (String::new(), selected);
+    }
+
+    (
+        format!(
+            "\nCONFIRMED EXAMPLES (similar past charts with user-verified labels):{}",
+            text
+        ),
+        selected,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(id: &str, pattern: &str, description: &str) -> LabelledExample {
+        LabelledExample {
+            id: id.to_string(),
+            pattern: pattern.to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    fn results(index: &Bm25Index, query: &str) -> Vec<(f64, String, String)> {
+        index
+            .search(query)
+            .into_iter()
+            .map(|(score, e)| (score, e.id.clone(), e.pattern.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn upserts_match_a_fresh_build() {
+        let mut index = Bm25Index::build(vec![
+            example("a", "Hammer", "small body with a long lower wick after a downtrend"),
+            example("b", "Bearish Engulfing", "large red candle engulfing the green candle after an uptrend"),
+        ]);
+        // Feedback relabels "a" and confirms a new analysis "c"
+        index.upsert(example("a", "Hanging Man", "small body with a long lower wick after an uptrend"));
+        index.upsert(example("c", "Doji (Standard)", "open and close equal with long wicks on both sides"));
+
+        let fresh = Bm25Index::build(vec![
+            example("a", "Hanging Man", "small body with a long lower wick after an uptrend"),
+            example("b", "Bearish Engulfing", "large red candle engulfing the green candle after an uptrend"),
+            example("c", "Doji (Standard)", "open and close equal with long wicks on both sides"),
+        ]);
+        for query in ["long lower wick uptrend", "downtrend", "long wicks both sides"] {
+            assert_eq!(results(&index, query), results(&fresh, query), "{}", query);
+        }
+        assert!(index.search("downtrend").is_empty());
+        assert_eq!(index.docs.len(), 3);
+    }
+}
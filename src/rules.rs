@@ -0,0 +1,1195 @@
+use serde::Serialize;
+
+use crate::candles::Candle;
+use crate::prefilter::Trend;
+use crate::trend::{self, CONTEXT_CANDLES};
+
+/// "Equal" prices may differ by this fraction of the recent average range.
+const TOLERANCE: f64 = 0.05;
+
+/// One objective criterion of a pattern definition.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// A pattern's checks against the most recent candles.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternChecks {
+    pub pattern: String,
+    /// Most recent candles the pattern spans.
+    pub candles: usize,
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+struct Rule {
+    pattern: &'static str,
+    /// Candles at the end of the series the checks look at.
+    candles: usize,
+    /// Trend that must precede the pattern, for shapes only context tells apart.
+    prior: Option<Trend>,
+    checks: fn(&[Candle], f64) -> Vec<Check>,
+}
+
+fn check(name: impl Into<String>, passed: bool, detail: String) -> Check {
+    Check { name: name.into(), passed, detail }
+}
+
+fn price(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn side(up: bool) -> &'static str {
+    if up {
+        "above"
+    } else {
+        "below"
+    }
+}
+
+/// `a` beyond `b` in the pattern's direction: above for bullish, below for bearish.
+fn beyond(a: f64, b: f64, up: bool) -> bool {
+    if up {
+        a > b
+    } else {
+        a < b
+    }
+}
+
+fn color(n: usize, c: &Candle, up: bool) -> Check {
+    check(
+        format!("candle {} {}", n, if up { "bullish" } else { "bearish" }),
+        if up { c.close > c.open } else { c.close < c.open },
+        format!("open {} close {}", price(c.open), price(c.close)),
+    )
+}
+
+fn share(c: &Candle, part: f64) -> f64 {
+    if c.range() > 0.0 {
+        part / c.range()
+    } else {
+        0.0
+    }
+}
+
+fn body_at_least(n: usize, c: &Candle, fraction: f64) -> Check {
+    check(
+        format!("candle {} body >= {:.0}% of range", n, fraction * 100.0),
+        share(c, c.body()) >= fraction,
+        format!("{:.0}%", share(c, c.body()) * 100.0),
+    )
+}
+
+fn doji(n: usize, c: &Candle) -> Check {
+    check(
+        format!("candle {} doji (body <= 10% of range)", n),
+        c.range() > 0.0 && share(c, c.body()) <= 0.1,
+        format!("{:.0}%", share(c, c.body()) * 100.0),
+    )
+}
+
+fn wick_share(n: usize, c: &Candle, upper: bool, at_least: bool, fraction: f64) -> Check {
+    let wick = if upper { c.upper_wick() } else { c.lower_wick() };
+    let value = share(c, wick);
+    check(
+        format!(
+            "candle {} {} wick {} {:.0}% of range",
+            n,
+            if upper { "upper" } else { "lower" },
+            if at_least { ">=" } else { "<=" },
+            fraction * 100.0
+        ),
+        if at_least { value >= fraction } else { value <= fraction },
+        format!("{:.0}%", value * 100.0),
+    )
+}
+
+fn ratio(name: &str, value: f64, base: f64, at_least: f64) -> Check {
+    let detail = if base > 0.0 {
+        format!("{:.1}x", value / base)
+    } else {
+        format!("{} vs {}", price(value), price(base))
+    };
+    check(name, value >= at_least * base, detail)
+}
+
+fn near(name: &str, a: f64, b: f64, tolerance: f64) -> Check {
+    check(name, (a - b).abs() <= tolerance, format!("{} vs {}", price(a), price(b)))
+}
+
+fn compare(name: &str, a: f64, b: f64, up: bool) -> Check {
+    check(name, beyond(a, b, up), format!("{} vs {}", price(a), price(b)))
+}
+
+fn body_inside(outer_n: usize, outer: &Candle, inner_n: usize, inner: &Candle) -> Check {
+    check(
+        format!("candle {} body inside candle {} body", inner_n, outer_n),
+        inner.body_top() <= outer.body_top() && inner.body_bottom() >= outer.body_bottom(),
+        format!(
+            "{}-{} within {}-{}",
+            price(inner.body_bottom()),
+            price(inner.body_top()),
+            price(outer.body_bottom()),
+            price(outer.body_top())
+        ),
+    )
+}
+
+fn within_range(outer_n: usize, outer: &Candle, inner_n: usize, inner: &Candle) -> Check {
+    check(
+        format!("candle {} within candle {} range", inner_n, outer_n),
+        inner.high <= outer.high && inner.low >= outer.low,
+        format!(
+            "{}-{} within {}-{}",
+            price(inner.low),
+            price(inner.high),
+            price(outer.low),
+            price(outer.high)
+        ),
+    )
+}
+
+fn engulfs(outer_n: usize, outer: &Candle, inner_n: usize, inner: &Candle) -> Check {
+    check(
+        format!("candle {} body engulfs candle {} body", outer_n, inner_n),
+        outer.body_top() >= inner.body_top()
+            && outer.body_bottom() <= inner.body_bottom()
+            && outer.body() > inner.body(),
+        format!(
+            "{}-{} around {}-{}",
+            price(outer.body_bottom()),
+            price(outer.body_top()),
+            price(inner.body_bottom()),
+            price(inner.body_top())
+        ),
+    )
+}
+
+fn body_mid(c: &Candle) -> f64 {
+    (c.open + c.close) / 2.0
+}
+
+// --- Single candles ---
+
+/// Hammer / Hanging Man (`lower` leg) and Inverted Hammer / Shooting Star.
+fn long_leg(w: &[Candle], lower: bool) -> Vec<Check> {
+    let c = &w[0];
+    let (leg, other) = if lower { (c.lower_wick(), c.upper_wick()) } else { (c.upper_wick(), c.lower_wick()) };
+    let side = if lower { "lower" } else { "upper" };
+    vec![
+        ratio(&format!("{} wick >= 2x body", side), leg, c.body(), 2.0),
+        check(
+            format!("{} wick <= 10% of range", if lower { "upper" } else { "lower" }),
+            share(c, other) <= 0.1,
+            format!("{:.0}%", share(c, other) * 100.0),
+        ),
+    ]
+}
+
+fn one_sided_doji(w: &[Candle], lower: bool) -> Vec<Check> {
+    let c = &w[0];
+    vec![doji(1, c), wick_share(1, c, lower, false, 0.1), wick_share(1, c, !lower, true, 0.6)]
+}
+
+fn long_legged_doji(w: &[Candle]) -> Vec<Check> {
+    let c = &w[0];
+    vec![doji(1, c), wick_share(1, c, true, true, 0.3), wick_share(1, c, false, true, 0.3)]
+}
+
+fn rickshaw_man(w: &[Candle]) -> Vec<Check> {
+    let c = &w[0];
+    let mut checks = long_legged_doji(w);
+    let offset = share(c, (body_mid(c) - (c.high + c.low) / 2.0).abs());
+    checks.push(check(
+        "body centred within 10% of range",
+        offset <= 0.1,
+        format!("{:.0}% off centre", offset * 100.0),
+    ));
+    checks
+}
+
+fn four_price_doji(w: &[Candle], tolerance: f64) -> Vec<Check> {
+    vec![near("high equals low", w[0].high, w[0].low, tolerance)]
+}
+
+fn marubozu(w: &[Candle], up: bool) -> Vec<Check> {
+    vec![color(1, &w[0], up), body_at_least(1, &w[0], 0.9)]
+}
+
+/// The Bullish/Bearish variants differ only in the prior trend.
+fn spinning_top(w: &[Candle]) -> Vec<Check> {
+    let c = &w[0];
+    vec![
+        check(
+            "body 5-40% of range",
+            (0.05..=0.4).contains(&share(c, c.body())),
+            format!("{:.0}%", share(c, c.body()) * 100.0),
+        ),
+        ratio("upper wick >= body", c.upper_wick(), c.body(), 1.0),
+        ratio("lower wick >= body", c.lower_wick(), c.body(), 1.0),
+    ]
+}
+
+fn belt_hold(w: &[Candle], up: bool) -> Vec<Check> {
+    let c = &w[0];
+    vec![
+        color(1, c, up),
+        // Opens on its extreme: no wick on the opening side.
+        wick_share(1, c, !up, false, 0.05),
+        body_at_least(1, c, 0.6),
+    ]
+}
+
+// --- Two candles ---
+
+fn engulfing(w: &[Candle], up: bool) -> Vec<Check> {
+    vec![color(1, &w[0], !up), color(2, &w[1], up), engulfs(2, &w[1], 1, &w[0])]
+}
+
+/// Piercing Line (up) and Dark Cloud Cover.
+fn piercing(w: &[Candle], up: bool) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    vec![
+        color(1, a, !up),
+        body_at_least(1, a, 0.5),
+        color(2, b, up),
+        compare(&format!("candle 2 opens {} candle 1 close", side(!up)), b.open, a.close, !up),
+        compare(&format!("candle 2 closes {} candle 1 body midpoint", side(up)), b.close, body_mid(a), up),
+        compare(&format!("candle 2 closes {} candle 1 open", side(!up)), b.close, a.open, !up),
+    ]
+}
+
+fn harami(w: &[Candle], up: bool) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    vec![
+        color(1, a, !up),
+        body_at_least(1, a, 0.5),
+        body_inside(1, a, 2, b),
+        ratio("candle 1 body >= 2x candle 2 body", a.body(), b.body(), 2.0),
+    ]
+}
+
+/// Tweezer Bottom (up: matching lows) and Tweezer Top (matching highs).
+fn tweezer(w: &[Candle], up: bool, tolerance: f64) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    let level = if up {
+        near("matching lows", a.low, b.low, tolerance)
+    } else {
+        near("matching highs", a.high, b.high, tolerance)
+    };
+    vec![color(1, a, !up), color(2, b, up), level]
+}
+
+fn kicker(w: &[Candle], up: bool) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    vec![
+        color(1, a, !up),
+        color(2, b, up),
+        compare(&format!("candle 2 opens {} candle 1 open", side(up)), b.open, a.open, up),
+    ]
+}
+
+fn counterattack(w: &[Candle], up: bool, tolerance: f64) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    vec![
+        color(1, a, !up),
+        color(2, b, up),
+        compare(&format!("candle 2 opens {} candle 1 close", side(!up)), b.open, a.close, !up),
+        near("closes at the same level", a.close, b.close, tolerance),
+    ]
+}
+
+/// On-Neck, In-Neck and Thrusting Line: a bullish candle opening below a
+/// bearish one's low and closing at, just into, or well into its body.
+fn neck(w: &[Candle], kind: &str, tolerance: f64) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    let mut checks = vec![
+        color(1, a, false),
+        color(2, b, true),
+        compare("candle 2 opens below candle 1 low", b.open, a.low, false),
+    ];
+    checks.push(match kind {
+        "on" => near("candle 2 closes at candle 1 low", b.close, a.low, tolerance),
+        "in" => check(
+            "candle 2 closes just inside candle 1 body",
+            b.close >= a.close - tolerance && b.close <= a.close + a.body() * 0.15,
+            format!("{} vs close {}", price(b.close), price(a.close)),
+        ),
+        _ => check(
+            "candle 2 closes into the lower half of candle 1 body",
+            b.close > a.close + a.body() * 0.15 && b.close < body_mid(a),
+            format!("{} vs body {}-{}", price(b.close), price(a.close), price(a.open)),
+        ),
+    });
+    checks
+}
+
+fn separating_lines(w: &[Candle], up: bool, tolerance: f64) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    vec![
+        color(1, a, !up),
+        color(2, b, up),
+        near("opens at the same level", a.open, b.open, tolerance),
+    ]
+}
+
+/// Matching Low (up: two bearish closes level) and Matching High.
+fn matching(w: &[Candle], up: bool, tolerance: f64) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    vec![
+        color(1, a, !up),
+        color(2, b, !up),
+        near("closes at the same level", a.close, b.close, tolerance),
+    ]
+}
+
+fn window(w: &[Candle], up: bool) -> Vec<Check> {
+    let (a, b) = (&w[0], &w[1]);
+    if up {
+        vec![compare("candle 2 low above candle 1 high", b.low, a.high, true)]
+    } else {
+        vec![compare("candle 2 high below candle 1 low", b.high, a.low, false)]
+    }
+}
+
+// --- Three candles ---
+
+/// Morning/Evening (Doji) Star.
+fn star(w: &[Candle], up: bool, doji_star: bool) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    vec![
+        color(1, a, !up),
+        body_at_least(1, a, 0.5),
+        if doji_star {
+            check(
+                "candle 2 doji",
+                b.body() <= a.body() * 0.1,
+                format!("{:.0}% of candle 1 body", b.body() / a.body().max(f64::EPSILON) * 100.0),
+            )
+        } else {
+            check(
+                "candle 2 body <= 1/3 of candle 1 body",
+                b.body() <= a.body() / 3.0,
+                format!("{:.0}%", b.body() / a.body().max(f64::EPSILON) * 100.0),
+            )
+        },
+        check(
+            format!("candle 2 body gaps {} candle 1 body", side(!up)),
+            if up { b.body_top() < a.body_bottom() } else { b.body_bottom() > a.body_top() },
+            format!(
+                "{}-{} vs {}-{}",
+                price(b.body_bottom()),
+                price(b.body_top()),
+                price(a.body_bottom()),
+                price(a.body_top())
+            ),
+        ),
+        color(3, c, up),
+        compare(&format!("candle 3 closes {} candle 1 body midpoint", side(up)), c.close, body_mid(a), up),
+    ]
+}
+
+/// Three White Soldiers (up) and Three Black Crows.
+fn soldiers(w: &[Candle], up: bool) -> Vec<Check> {
+    let mut checks: Vec<Check> = w.iter().enumerate().map(|(i, c)| color(i + 1, c, up)).collect();
+    for i in 1..3 {
+        let (prev, c) = (&w[i - 1], &w[i]);
+        checks.push(compare(
+            &format!("candle {} closes {} candle {}", i + 1, side(up), i),
+            c.close,
+            prev.close,
+            up,
+        ));
+        checks.push(check(
+            format!("candle {} opens inside candle {} body", i + 1, i),
+            c.open >= prev.body_bottom() && c.open <= prev.body_top(),
+            format!("{} vs {}-{}", price(c.open), price(prev.body_bottom()), price(prev.body_top())),
+        ));
+    }
+    for (i, c) in w.iter().enumerate() {
+        let closing_wick = if up { c.upper_wick() } else { c.lower_wick() };
+        checks.push(check(
+            format!("candle {} closes near its extreme", i + 1),
+            closing_wick <= c.body() * 0.3,
+            format!("wick {:.0}% of body", closing_wick / c.body().max(f64::EPSILON) * 100.0),
+        ));
+    }
+    checks
+}
+
+fn identical_crows(w: &[Candle], tolerance: f64) -> Vec<Check> {
+    let mut checks: Vec<Check> = w.iter().enumerate().map(|(i, c)| color(i + 1, c, false)).collect();
+    for i in 1..3 {
+        checks.push(near(
+            &format!("candle {} opens at candle {} close", i + 1, i),
+            w[i].open,
+            w[i - 1].close,
+            tolerance,
+        ));
+    }
+    checks
+}
+
+/// Three Inside Up/Down: a harami confirmed by a close beyond the first open.
+fn inside(w: &[Candle], up: bool) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    vec![
+        color(1, a, !up),
+        body_at_least(1, a, 0.5),
+        color(2, b, up),
+        body_inside(1, a, 2, b),
+        color(3, c, up),
+        compare(&format!("candle 3 closes {} candle 1 open", side(up)), c.close, a.open, up),
+    ]
+}
+
+/// Three Outside Up/Down: an engulfing confirmed by a further close.
+fn outside(w: &[Candle], up: bool) -> Vec<Check> {
+    let mut checks = engulfing(w, up);
+    checks.push(color(3, &w[2], up));
+    checks.push(compare(
+        &format!("candle 3 closes {} candle 2 close", side(up)),
+        w[2].close,
+        w[1].close,
+        up,
+    ));
+    checks
+}
+
+fn abandoned_baby(w: &[Candle], up: bool) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    let gap = |name: &str, first: &Candle, second: &Candle, down: bool| {
+        // `down`: second entirely below first.
+        if down {
+            compare(name, second.high, first.low, false)
+        } else {
+            compare(name, second.low, first.high, true)
+        }
+    };
+    vec![
+        color(1, a, !up),
+        check(
+            "candle 2 doji",
+            b.body() <= a.body() * 0.1 || share(b, b.body()) <= 0.1,
+            format!("{:.0}% of range", share(b, b.body()) * 100.0),
+        ),
+        gap(&format!("candle 2 gaps {} candle 1", side(!up)), a, b, up),
+        color(3, c, up),
+        gap(&format!("candle 3 gaps {} candle 2", side(up)), b, c, !up),
+    ]
+}
+
+/// Advance Block (up) and Descent Block: soldiers losing momentum.
+fn block(w: &[Candle], up: bool) -> Vec<Check> {
+    let mut checks: Vec<Check> = w.iter().enumerate().map(|(i, c)| color(i + 1, c, up)).collect();
+    for i in 1..3 {
+        checks.push(compare(
+            &format!("candle {} closes {} candle {}", i + 1, side(up), i),
+            w[i].close,
+            w[i - 1].close,
+            up,
+        ));
+        checks.push(check(
+            format!("candle {} body smaller than candle {}", i + 1, i),
+            w[i].body() < w[i - 1].body(),
+            format!("{} vs {}", price(w[i].body()), price(w[i - 1].body())),
+        ));
+    }
+    let wick = |c: &Candle| if up { c.upper_wick() } else { c.lower_wick() };
+    checks.push(compare(
+        "candle 3 closing wick longer than candle 1",
+        wick(&w[2]),
+        wick(&w[0]),
+        true,
+    ));
+    checks
+}
+
+fn deliberation(w: &[Candle], tolerance: f64) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    vec![
+        color(1, a, true),
+        body_at_least(1, a, 0.5),
+        color(2, b, true),
+        body_at_least(2, b, 0.5),
+        compare("candle 2 closes above candle 1", b.close, a.close, true),
+        check(
+            "candle 3 body <= 1/3 of candle 2 body",
+            c.body() <= b.body() / 3.0,
+            format!("{:.0}%", c.body() / b.body().max(f64::EPSILON) * 100.0),
+        ),
+        check(
+            "candle 3 opens at or above candle 2 close",
+            c.open >= b.close - tolerance,
+            format!("{} vs {}", price(c.open), price(b.close)),
+        ),
+    ]
+}
+
+fn unique_three_river(w: &[Candle]) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    vec![
+        color(1, a, false),
+        body_at_least(1, a, 0.5),
+        color(2, b, false),
+        body_inside(1, a, 2, b),
+        ratio("candle 2 lower wick >= 2x body (hammer-like)", b.lower_wick(), b.body(), 2.0),
+        within_range(1, a, 2, b),
+        color(3, c, true),
+        check(
+            "candle 3 body <= 1/3 of candle 1 body",
+            c.body() <= a.body() / 3.0,
+            format!("{:.0}%", c.body() / a.body().max(f64::EPSILON) * 100.0),
+        ),
+        compare("candle 3 body below candle 2 open", c.body_top(), b.open, false),
+        within_range(1, a, 3, c),
+    ]
+}
+
+fn stick_sandwich(w: &[Candle], tolerance: f64) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    vec![
+        color(1, a, false),
+        color(2, b, true),
+        compare("candle 2 closes above candle 1 close", b.close, a.close, true),
+        color(3, c, false),
+        near("candles 1 and 3 close at the same level", a.close, c.close, tolerance),
+    ]
+}
+
+fn tasuki(w: &[Candle], up: bool) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    let (gap_low, gap_high) = if up { (a.high, b.low) } else { (b.high, a.low) };
+    vec![
+        color(1, a, up),
+        color(2, b, up),
+        check(
+            format!("candle 2 gaps {} candle 1", side(up)),
+            gap_high > gap_low,
+            format!("gap {}-{}", price(gap_low), price(gap_high)),
+        ),
+        color(3, c, !up),
+        check(
+            "candle 3 opens inside candle 2 body",
+            c.open >= b.body_bottom() && c.open <= b.body_top(),
+            format!("{} vs {}-{}", price(c.open), price(b.body_bottom()), price(b.body_top())),
+        ),
+        check(
+            "candle 3 closes inside the gap",
+            c.close > gap_low && c.close < gap_high,
+            format!("{} vs gap {}-{}", price(c.close), price(gap_low), price(gap_high)),
+        ),
+    ]
+}
+
+fn side_by_side_white(w: &[Candle], tolerance: f64) -> Vec<Check> {
+    let (a, b, c) = (&w[0], &w[1], &w[2]);
+    vec![
+        color(1, a, true),
+        color(2, b, true),
+        compare("candle 2 gaps above candle 1", b.low, a.high, true),
+        color(3, c, true),
+        near("candles 2 and 3 open at the same level", b.open, c.open, tolerance),
+        check(
+            "candles 2 and 3 bodies within 20%",
+            (b.body() - c.body()).abs() <= b.body() * 0.2,
+            format!("{} vs {}", price(b.body()), price(c.body())),
+        ),
+    ]
+}
+
+// --- Four and more candles ---
+
+/// Rising/Falling Three Methods: a long candle, three small counter-moves
+/// inside its range, then a close beyond it.
+fn three_methods(w: &[Candle], up: bool) -> Vec<Check> {
+    let (first, last) = (&w[0], &w[4]);
+    let mut checks = vec![color(1, first, up), body_at_least(1, first, 0.5)];
+    for (i, c) in w[1..4].iter().enumerate() {
+        checks.push(check(
+            format!("candle {} small and inside candle 1 range", i + 2),
+            c.body() <= first.body() / 2.0 && c.high <= first.high && c.low >= first.low,
+            format!("{}-{} within {}-{}", price(c.low), price(c.high), price(first.low), price(first.high)),
+        ));
+    }
+    checks.push(color(5, last, up));
+    checks.push(compare(
+        &format!("candle 5 closes {} candle 1 close", side(up)),
+        last.close,
+        first.close,
+        up,
+    ));
+    checks
+}
+
+fn mat_hold(w: &[Candle]) -> Vec<Check> {
+    let (first, last) = (&w[0], &w[4]);
+    let middle_high = w[1..4].iter().map(|c| c.high).fold(f64::MIN, f64::max);
+    let mut checks = vec![
+        color(1, first, true),
+        body_at_least(1, first, 0.5),
+        compare("candle 2 opens above candle 1 close", w[1].open, first.close, true),
+    ];
+    for (i, c) in w[1..4].iter().enumerate() {
+        checks.push(compare(
+            &format!("candle {} holds above candle 1 body", i + 2),
+            c.low,
+            first.body_bottom(),
+            true,
+        ));
+    }
+    checks.push(color(5, last, true));
+    checks.push(compare("candle 5 closes above the pullback highs", last.close, middle_high, true));
+    checks
+}
+
+/// Three-Line Strike: three trend candles wiped out by one closing past the
+/// first open. Bullish after soldiers, as in the taxonomy's definition.
+fn three_line_strike(w: &[Candle], up: bool) -> Vec<Check> {
+    let mut checks: Vec<Check> = w[..3].iter().enumerate().map(|(i, c)| color(i + 1, c, up)).collect();
+    for i in 1..3 {
+        checks.push(compare(
+            &format!("candle {} closes {} candle {}", i + 1, side(up), i),
+            w[i].close,
+            w[i - 1].close,
+            up,
+        ));
+    }
+    checks.push(color(4, &w[3], !up));
+    checks.push(compare(
+        &format!("candle 4 closes {} candle 1 open", side(!up)),
+        w[3].close,
+        w[0].open,
+        !up,
+    ));
+    checks
+}
+
+fn concealing_baby_swallow(w: &[Candle]) -> Vec<Check> {
+    let (a, b, c, d) = (&w[0], &w[1], &w[2], &w[3]);
+    vec![
+        color(1, a, false),
+        body_at_least(1, a, 0.9),
+        color(2, b, false),
+        body_at_least(2, b, 0.9),
+        color(3, c, false),
+        compare("candle 3 opens below candle 2 close", c.open, b.close, false),
+        compare("candle 3 upper wick reaches into candle 2 body", c.high, b.close, true),
+        color(4, d, false),
+        check(
+            "candle 4 engulfs candle 3 including wicks",
+            d.open >= c.high && d.close <= c.low,
+            format!("{}-{} around {}-{}", price(d.close), price(d.open), price(c.low), price(c.high)),
+        ),
+    ]
+}
+
+fn ladder_bottom(w: &[Candle]) -> Vec<Check> {
+    let mut checks: Vec<Check> = w[..4].iter().enumerate().map(|(i, c)| color(i + 1, c, false)).collect();
+    for i in 1..3 {
+        checks.push(compare(
+            &format!("candle {} closes below candle {}", i + 1, i),
+            w[i].close,
+            w[i - 1].close,
+            false,
+        ));
+    }
+    checks.push(ratio("candle 4 upper wick >= body", w[3].upper_wick(), w[3].body(), 1.0));
+    checks.push(color(5, &w[4], true));
+    checks.push(compare("candle 5 opens above candle 4 open", w[4].open, w[3].open, true));
+    checks.push(compare("candle 5 closes above candle 4 high", w[4].close, w[3].high, true));
+    checks
+}
+
+fn breakaway(w: &[Candle], up: bool, tolerance: f64) -> Vec<Check> {
+    let (first, second, last) = (&w[0], &w[1], &w[4]);
+    let mut checks = vec![
+        color(1, first, !up),
+        body_at_least(1, first, 0.5),
+        check(
+            format!("candle 2 gaps {} candle 1 body", side(!up)),
+            if up { second.body_top() < first.body_bottom() } else { second.body_bottom() > first.body_top() },
+            format!("{} vs {}", price(second.open), price(first.close)),
+        ),
+    ];
+    for i in 2..4 {
+        checks.push(compare(
+            &format!("candle {} closes {} candle {}", i + 1, side(!up), i),
+            w[i].close,
+            w[i - 1].close,
+            !up,
+        ));
+    }
+    checks.push(color(5, last, up));
+    checks.push(check(
+        "candle 5 closes inside the gap",
+        if up {
+            last.close > second.open && last.close <= first.close + tolerance
+        } else {
+            last.close < second.open && last.close >= first.close - tolerance
+        },
+        format!("{} vs gap {}-{}", price(last.close), price(second.open), price(first.close)),
+    ));
+    checks
+}
+
+/// Tower Top: one or two tall bullish candles, at least three small candles
+/// holding in the upper half of the rise, then one or two tall bearish candles
+/// giving back at least half of it. Reports whichever split fits best.
+fn tower_top(w: &[Candle]) -> Vec<Check> {
+    (1..=2)
+        .flat_map(|rising| (1..=2).map(move |falling| (rising, falling)))
+        .filter(|(rising, falling)| w.len() >= rising + falling + 3)
+        .map(|(rising, falling)| tower_split(w, rising, falling))
+        .max_by_key(|checks| checks.iter().filter(|c| c.passed).count())
+        .unwrap_or_default()
+}
+
+fn tower_split(w: &[Candle], rising: usize, falling: usize) -> Vec<Check> {
+    let n = w.len();
+    let middle = &w[rising..n - falling];
+    let widest = middle.iter().map(Candle::body).fold(0.0, f64::max);
+    let top = &w[rising - 1];
+    let midpoint = (top.open + top.close) / 2.0;
+    let tall = |i: usize, up: bool| {
+        [
+            color(i + 1, &w[i], up),
+            ratio(&format!("candle {} body >= 2x largest middle body", i + 1), w[i].body(), widest, 2.0),
+        ]
+    };
+
+    let mut checks: Vec<Check> = (0..rising).flat_map(|i| tall(i, true)).collect();
+    checks.extend(middle.iter().enumerate().map(|(i, c)| {
+        compare(
+            &format!("candle {} body above candle {} midpoint", rising + i + 1, rising),
+            c.body_bottom(),
+            midpoint,
+            true,
+        )
+    }));
+    checks.extend((n - falling..n).flat_map(|i| tall(i, false)));
+    checks.push(compare(
+        &format!("candle {} closes below the middle of the rise", n),
+        w[n - 1].close,
+        (w[0].open + top.close) / 2.0,
+        false,
+    ));
+    checks
+}
+
+/// Hikkake: a mother candle, one or more inside bars each within the one
+/// before, a false break of the last inside bar on one side, then a close back
+/// beyond its other side. Either direction.
+fn hikkake(w: &[Candle]) -> Vec<Check> {
+    let n = w.len();
+    let (inside, breakout, reversal) = (&w[n - 3], &w[n - 2], &w[n - 1]);
+    let mut checks: Vec<Check> = (1..n - 2)
+        .map(|i| {
+            check(
+                format!("candle {} inside candle {}", i + 1, i),
+                w[i].high < w[i - 1].high && w[i].low > w[i - 1].low,
+                format!(
+                    "{}-{} within {}-{}",
+                    price(w[i].low),
+                    price(w[i].high),
+                    price(w[i - 1].low),
+                    price(w[i - 1].high)
+                ),
+            )
+        })
+        .collect();
+    let down = breakout.high < inside.high && breakout.low < inside.low;
+    let up = breakout.high > inside.high && breakout.low > inside.low;
+    checks.push(check(
+        format!("candle {} breaks candle {} on one side", n - 1, n - 2),
+        down || up,
+        format!(
+            "{}-{} vs {}-{}",
+            price(breakout.low),
+            price(breakout.high),
+            price(inside.low),
+            price(inside.high)
+        ),
+    ));
+    // The reversal runs against the break: above the inside bar after a break down.
+    checks.push(compare(
+        &format!("candle {} closes {} candle {}", n, side(!up), n - 2),
+        reversal.close,
+        if up { inside.low } else { inside.high },
+        !up,
+    ));
+    checks
+}
+
+// Patterns of varying length have one entry per length, longest first.
+const RULES: &[Rule] = &[
+    Rule { pattern: "Hammer", candles: 1, prior: Some(Trend::Down), checks: |w, _| long_leg(w, true) },
+    Rule { pattern: "Hanging Man", candles: 1, prior: Some(Trend::Up), checks: |w, _| long_leg(w, true) },
+    Rule { pattern: "Inverted Hammer", candles: 1, prior: Some(Trend::Down), checks: |w, _| long_leg(w, false) },
+    Rule { pattern: "Shooting Star", candles: 1, prior: Some(Trend::Up), checks: |w, _| long_leg(w, false) },
+    Rule { pattern: "Dragonfly Doji", candles: 1, prior: None, checks: |w, _| one_sided_doji(w, true) },
+    Rule { pattern: "Gravestone Doji", candles: 1, prior: None, checks: |w, _| one_sided_doji(w, false) },
+    Rule { pattern: "Doji (Standard)", candles: 1, prior: None, checks: |w, _| vec![doji(1, &w[0])] },
+    Rule { pattern: "Long-legged Doji", candles: 1, prior: None, checks: |w, _| long_legged_doji(w) },
+    Rule { pattern: "Rickshaw Man", candles: 1, prior: None, checks: |w, _| rickshaw_man(w) },
+    Rule { pattern: "Four-Price Doji", candles: 1, prior: None, checks: four_price_doji },
+    Rule { pattern: "Marubozu (Bullish)", candles: 1, prior: None, checks: |w, _| marubozu(w, true) },
+    Rule { pattern: "Marubozu (Bearish)", candles: 1, prior: None, checks: |w, _| marubozu(w, false) },
+    Rule { pattern: "Spinning Top (Bullish)", candles: 1, prior: Some(Trend::Down), checks: |w, _| spinning_top(w) },
+    Rule { pattern: "Spinning Top (Bearish)", candles: 1, prior: Some(Trend::Up), checks: |w, _| spinning_top(w) },
+    Rule { pattern: "Belt Hold (Bullish)", candles: 1, prior: None, checks: |w, _| belt_hold(w, true) },
+    Rule { pattern: "Belt Hold (Bearish)", candles: 1, prior: None, checks: |w, _| belt_hold(w, false) },
+    Rule { pattern: "Bullish Engulfing", candles: 2, prior: None, checks: |w, _| engulfing(w, true) },
+    Rule { pattern: "Bearish Engulfing", candles: 2, prior: None, checks: |w, _| engulfing(w, false) },
+    Rule { pattern: "Piercing Line", candles: 2, prior: None, checks: |w, _| piercing(w, true) },
+    Rule { pattern: "Dark Cloud Cover", candles: 2, prior: None, checks: |w, _| piercing(w, false) },
+    Rule { pattern: "Bullish Harami", candles: 2, prior: None, checks: |w, _| harami(w, true) },
+    Rule { pattern: "Bearish Harami", candles: 2, prior: None, checks: |w, _| harami(w, false) },
+    Rule { pattern: "Tweezer Bottom", candles: 2, prior: None, checks: |w, t| tweezer(w, true, t) },
+    Rule { pattern: "Tweezer Top", candles: 2, prior: None, checks: |w, t| tweezer(w, false, t) },
+    Rule { pattern: "Bullish Kicker", candles: 2, prior: None, checks: |w, _| kicker(w, true) },
+    Rule { pattern: "Bearish Kicker", candles: 2, prior: None, checks: |w, _| kicker(w, false) },
+    Rule { pattern: "Bullish Counterattack Line", candles: 2, prior: None, checks: |w, t| counterattack(w, true, t) },
+    Rule { pattern: "Bearish Counterattack Line", candles: 2, prior: None, checks: |w, t| counterattack(w, false, t) },
+    Rule { pattern: "On-Neck Line", candles: 2, prior: None, checks: |w, t| neck(w, "on", t) },
+    Rule { pattern: "In-Neck Line", candles: 2, prior: None, checks: |w, t| neck(w, "in", t) },
+    Rule { pattern: "Thrusting Line", candles: 2, prior: None, checks: |w, t| neck(w, "thrusting", t) },
+    Rule { pattern: "Separating Lines (Bullish)", candles: 2, prior: None, checks: |w, t| separating_lines(w, true, t) },
+    Rule { pattern: "Separating Lines (Bearish)", candles: 2, prior: None, checks: |w, t| separating_lines(w, false, t) },
+    Rule { pattern: "Matching Low", candles: 2, prior: None, checks: |w, t| matching(w, true, t) },
+    Rule { pattern: "Matching High", candles: 2, prior: None, checks: |w, t| matching(w, false, t) },
+    Rule { pattern: "Rising Window", candles: 2, prior: None, checks: |w, _| window(w, true) },
+    Rule { pattern: "Falling Window", candles: 2, prior: None, checks: |w, _| window(w, false) },
+    Rule { pattern: "Morning Star", candles: 3, prior: None, checks: |w, _| star(w, true, false) },
+    Rule { pattern: "Morning Doji Star", candles: 3, prior: None, checks: |w, _| star(w, true, true) },
+    Rule { pattern: "Evening Star", candles: 3, prior: None, checks: |w, _| star(w, false, false) },
+    Rule { pattern: "Evening Doji Star", candles: 3, prior: None, checks: |w, _| star(w, false, true) },
+    Rule { pattern: "Three White Soldiers", candles: 3, prior: None, checks: |w, _| soldiers(w, true) },
+    Rule { pattern: "Three Black Crows", candles: 3, prior: None, checks: |w, _| soldiers(w, false) },
+    Rule { pattern: "Identical Three Crows", candles: 3, prior: None, checks: identical_crows },
+    Rule { pattern: "Three Inside Up", candles: 3, prior: None, checks: |w, _| inside(w, true) },
+    Rule { pattern: "Three Inside Down", candles: 3, prior: None, checks: |w, _| inside(w, false) },
+    Rule { pattern: "Three Outside Up", candles: 3, prior: None, checks: |w, _| outside(w, true) },
+    Rule { pattern: "Three Outside Down", candles: 3, prior: None, checks: |w, _| outside(w, false) },
+    Rule { pattern: "Bullish Abandoned Baby", candles: 3, prior: None, checks: |w, _| abandoned_baby(w, true) },
+    Rule { pattern: "Bearish Abandoned Baby", candles: 3, prior: None, checks: |w, _| abandoned_baby(w, false) },
+    Rule { pattern: "Advance Block", candles: 3, prior: None, checks: |w, _| block(w, true) },
+    Rule { pattern: "Descent Block", candles: 3, prior: None, checks: |w, _| block(w, false) },
+    Rule { pattern: "Deliberation Pattern", candles: 3, prior: None, checks: deliberation },
+    Rule { pattern: "Unique Three River Bottom", candles: 3, prior: None, checks: |w, _| unique_three_river(w) },
+    Rule { pattern: "Stick Sandwich (Bullish)", candles: 3, prior: None, checks: stick_sandwich },
+    Rule { pattern: "Upside Tasuki Gap", candles: 3, prior: None, checks: |w, _| tasuki(w, true) },
+    Rule { pattern: "Downside Tasuki Gap", candles: 3, prior: None, checks: |w, _| tasuki(w, false) },
+    Rule { pattern: "Side-by-Side White Lines", candles: 3, prior: None, checks: side_by_side_white },
+    Rule { pattern: "Bullish Three-Line Strike", candles: 4, prior: None, checks: |w, _| three_line_strike(w, true) },
+    Rule { pattern: "Bearish Three-Line Strike", candles: 4, prior: None, checks: |w, _| three_line_strike(w, false) },
+    Rule { pattern: "Concealing Baby Swallow", candles: 4, prior: None, checks: |w, _| concealing_baby_swallow(w) },
+    Rule { pattern: "Rising Three Methods", candles: 5, prior: None, checks: |w, _| three_methods(w, true) },
+    Rule { pattern: "Falling Three Methods", candles: 5, prior: None, checks: |w, _| three_methods(w, false) },
+    Rule { pattern: "Mat Hold (Bullish)", candles: 5, prior: None, checks: |w, _| mat_hold(w) },
+    Rule { pattern: "Ladder Bottom", candles: 5, prior: None, checks: |w, _| ladder_bottom(w) },
+    Rule { pattern: "Breakaway (Bullish)", candles: 5, prior: None, checks: |w, t| breakaway(w, true, t) },
+    Rule { pattern: "Breakaway (Bearish)", candles: 5, prior: None, checks: |w, t| breakaway(w, false, t) },
+    Rule { pattern: "Tower Top", candles: 8, prior: None, checks: |w, _| tower_top(w) },
+    Rule { pattern: "Tower Top", candles: 7, prior: None, checks: |w, _| tower_top(w) },
+    Rule { pattern: "Tower Top", candles: 6, prior: None, checks: |w, _| tower_top(w) },
+    Rule { pattern: "Tower Top", candles: 5, prior: None, checks: |w, _| tower_top(w) },
+    Rule { pattern: "Hikkake Pattern", candles: 4, prior: None, checks: |w, _| hikkake(w) },
+    Rule { pattern: "Hikkake Modified", candles: 6, prior: None, checks: |w, _| hikkake(w) },
+    Rule { pattern: "Hikkake Modified", candles: 5, prior: None, checks: |w, _| hikkake(w) },
+];
+
+fn rules_for(pattern: &str) -> impl Iterator<Item = &'static Rule> + '_ {
+    RULES.iter().filter(move |r| r.pattern.eq_ignore_ascii_case(pattern))
+}
+
+fn rule(pattern: &str) -> Option<&'static Rule> {
+    rules_for(pattern).next()
+}
+
+/// Whether `pattern` has a geometric definition here.
+pub fn defined(pattern: &str) -> bool {
+    rule(pattern).is_some()
+}
+
+/// Trend a pattern's definition requires before it, for shapes that only
+/// context tells apart (Hammer vs Hanging Man).
+pub fn required_trend(pattern: &str) -> Option<Trend> {
+    rule(pattern)?.prior
+}
+
+/// Checks `pattern` against the most recent candles of `series`: the first
+/// length that passes, else the longest. `None` when the pattern has no
+/// geometric definition here (e.g. chart formations).
+pub fn verify(pattern: &str, series: &[Candle]) -> Option<PatternChecks> {
+    let mut results = rules_for(pattern).map(|rule| verify_rule(rule, series));
+    let longest = results.next()?;
+    if longest.passed {
+        return Some(longest);
+    }
+    Some(results.find(|checks| checks.passed).unwrap_or(longest))
+}
+
+fn verify_rule(rule: &Rule, series: &[Candle]) -> PatternChecks {
+    let mut checks = Vec::new();
+
+    if series.len() < rule.candles {
+        checks.push(check(
+            format!("at least {} candles", rule.candles),
+            false,
+            format!("chart has {}", series.len()),
+        ));
+    } else {
+        let start = series.len() - rule.candles;
+        let recent = &series[series.len().saturating_sub(20)..];
+        let average_range = recent.iter().map(Candle::range).sum::<f64>() / recent.len() as f64;
+        let tolerance = (average_range * TOLERANCE).max(f64::EPSILON);
+
+        if let Some(required) = rule.prior {
+            let context = &series[start.saturating_sub(CONTEXT_CANDLES)..start];
+            // Too little history to judge the trend is not held against the pattern.
+            if context.len() >= 3 {
+                let found = trend::classify(context);
+                check_trend(&mut checks, required, found, context.len());
+            }
+        }
+        checks.extend((rule.checks)(&series[start..], tolerance));
+    }
+
+    PatternChecks {
+        pattern: rule.pattern.to_string(),
+        candles: rule.candles,
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+    }
+}
+
+/// A pattern found by [`scan`]; indexes are inclusive, 0 = first candle.
+#[derive(Debug, Clone, Copy)]
+pub struct Detection {
+    pub pattern: &'static str,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Every rule-defined pattern completing on the last candle of `series`.
+/// Trend-defined patterns need three candles of context here, since without
+/// it Hammer and Hanging Man would both match.
+pub fn detect(series: &[Candle]) -> Vec<Detection> {
+    let Some(end) = series.len().checked_sub(1) else {
+        return Vec::new();
+    };
+    let mut detections: Vec<Detection> = Vec::new();
+    for rule in RULES {
+        // Only the longest form of a variable-length pattern is reported.
+        if detections.iter().any(|d| d.pattern == rule.pattern) {
+            continue;
+        }
+        let Some(start) = series.len().checked_sub(rule.candles) else {
+            continue;
+        };
+        if rule.prior.is_some() && start < 3 {
+            continue;
+        }
+        if verify_rule(rule, series).passed {
+            detections.push(Detection { pattern: rule.pattern, start, end });
+        }
+    }
+    detections
+}
+
+/// [`detect`] at every candle of `series`, in order of completion.
+pub fn scan(series: &[Candle]) -> Vec<Detection> {
+    (1..=series.len()).flat_map(|n| detect(&series[..n])).collect()
+}
+
+fn check_trend(checks: &mut Vec<Check>, required: Trend, found: Option<Trend>, candles: usize) {
+    checks.push(check(
+        format!("prior {}", required.label()),
+        found == Some(required),
+        format!(
+            "{} over the previous {} candles",
+            found.map_or("unknown", Trend::label),
+            candles
+        ),
+    ));
+}
+
+/// Where the verified candles came from.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CandleSource {
+    /// OHLC submitted with the request.
+    Ohlc,
+    /// Candles extracted from the uploaded image.
+    Extracted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// The reasoner's pattern passed every check.
+    Confirmed,
+    /// It failed and no alternative passed; confidence was lowered.
+    Downgraded,
+    /// It failed and the first passing alternative replaced it.
+    Replaced,
+    /// No geometric definition exists for the pattern.
+    Unchecked,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Verification {
+    pub source: CandleSource,
+    pub verdict: Verdict,
+    /// The reasoner's pattern first, then each alternative tried.
+    pub checked: Vec<PatternChecks>,
+    /// What the reasoner answered, when the verdict replaced it.
+    pub original_pattern: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle { time: None, open, high, low, close, volume: None }
+    }
+
+    /// `n` candles stepping by `step` per candle, ending just before `end`.
+    fn trend_into(n: usize, end: f64, step: f64) -> Vec<Candle> {
+        (0..n)
+            .map(|i| {
+                let close = end - step * (n - i) as f64;
+                let open = close - step * 0.8;
+                c(open, open.max(close) + 0.2, open.min(close) - 0.2, close)
+            })
+            .collect()
+    }
+
+    fn with(mut series: Vec<Candle>, tail: &[Candle]) -> Vec<Candle> {
+        series.extend_from_slice(tail);
+        series
+    }
+
+    fn failed(checks: &PatternChecks) -> Vec<&str> {
+        checks.checks.iter().filter(|c| !c.passed).map(|c| c.name.as_str()).collect()
+    }
+
+    const HAMMER: Candle = Candle { time: None, open: 100.0, high: 100.6, low: 98.0, close: 100.5, volume: None };
+
+    #[test]
+    fn hammer_needs_a_lower_wick_of_twice_its_body() {
+        let down = trend_into(6, 100.0, -1.0);
+        let passing = verify("Hammer", &with(down.clone(), &[HAMMER])).unwrap();
+        assert!(passing.passed, "{:?}", failed(&passing));
+
+        // Lower wick 1.5 against a body of 1.0
+        let short_leg = verify("Hammer", &with(down, &[c(100.0, 101.05, 98.5, 101.0)])).unwrap();
+        assert_eq!(failed(&short_leg), ["lower wick >= 2x body"]);
+    }
+
+    #[test]
+    fn prior_trend_tells_hammer_from_hanging_man() {
+        let up = with(trend_into(6, 100.0, 1.0), &[HAMMER]);
+        let hammer = verify("Hammer", &up).unwrap();
+        assert_eq!(failed(&hammer), ["prior downtrend"]);
+        assert!(verify("Hanging Man", &up).unwrap().passed);
+
+        // Under three candles of context the trend is not held against it
+        let short = verify("Hammer", &[c(99.0, 99.5, 98.5, 99.2), HAMMER]).unwrap();
+        assert!(short.passed, "{:?}", failed(&short));
+        // ...but detect needs the context to tell the two apart
+        assert!(detect(&[c(99.0, 99.5, 98.5, 99.2), HAMMER]).iter().all(|d| d.pattern != "Hammer"));
+        assert!(detect(&up).iter().any(|d| d.pattern == "Hanging Man"));
+        assert!(detect(&up).iter().all(|d| d.pattern != "Hammer"));
+    }
+
+    #[test]
+    fn engulfing_needs_containment() {
+        let bearish = c(101.0, 101.2, 99.8, 100.0);
+        let engulfing = verify("Bullish Engulfing", &[bearish, c(99.8, 102.2, 99.6, 102.0)]).unwrap();
+        assert!(engulfing.passed, "{:?}", failed(&engulfing));
+
+        // Bigger body, but it opens above the prior close
+        let shifted = verify("Bullish Engulfing", &[bearish, c(100.2, 102.7, 100.1, 102.5)]).unwrap();
+        assert_eq!(failed(&shifted), ["candle 2 body engulfs candle 1 body"]);
+        // Wrong colour on the first candle
+        let same_colour = verify("Bullish Engulfing", &[c(100.0, 101.2, 99.8, 101.0), c(99.8, 102.2, 99.6, 102.0)]).unwrap();
+        assert_eq!(failed(&same_colour), ["candle 1 bearish"]);
+    }
+
+    #[test]
+    fn equal_levels_allow_the_tolerance() {
+        // Average range here is about 1.9, so lows may differ by about 0.09
+        let bearish = c(101.5, 101.9, 100.0, 100.4);
+        let within = verify("Tweezer Bottom", &[bearish, c(100.4, 101.95, 100.08, 101.5)]).unwrap();
+        assert!(within.passed, "{:?}", failed(&within));
+        let beyond = verify("Tweezer Bottom", &[bearish, c(100.4, 102.2, 100.3, 101.5)]).unwrap();
+        assert_eq!(failed(&beyond), ["matching lows"]);
+    }
+
+    #[test]
+    fn too_short_a_series_fails_instead_of_panicking() {
+        let checks = verify("Morning Star", &[HAMMER]).unwrap();
+        assert!(!checks.passed);
+        assert_eq!(failed(&checks), ["at least 3 candles"]);
+        assert!(verify("Head and Shoulders", &[HAMMER]).is_none());
+        assert!(detect(&[]).is_empty());
+    }
+
+    /// Seven-candle Tower Top behind an unrelated candle: the 8-candle form
+    /// fails, so the 7-candle one is the single detection.
+    #[test]
+    fn tower_top_reports_the_longest_passing_length_once() {
+        let series = [
+            c(100.5, 101.0, 99.8, 100.2),
+            c(100.0, 110.5, 99.5, 110.0),
+            c(109.5, 110.5, 108.8, 109.0),
+            c(109.0, 110.0, 108.5, 109.6),
+            c(109.6, 110.8, 109.0, 110.2),
+            c(110.2, 110.6, 109.3, 109.4),
+            c(109.4, 109.6, 104.0, 104.4),
+            c(104.4, 104.6, 99.0, 99.5),
+        ];
+        let checks = verify("Tower Top", &series).unwrap();
+        assert!(checks.passed, "{:?}", failed(&checks));
+        assert_eq!(checks.candles, 7);
+
+        let towers: Vec<Detection> = detect(&series).into_iter().filter(|d| d.pattern == "Tower Top").collect();
+        assert_eq!(towers.len(), 1);
+        assert_eq!((towers[0].start, towers[0].end), (1, 7));
+        assert_eq!(scan(&series).iter().filter(|d| d.pattern == "Tower Top").count(), 1);
+
+        // Without the last bearish candle the rise is not given back
+        assert!(!verify("Tower Top", &series[..7]).unwrap().passed);
+    }
+
+    #[test]
+    fn hikkake_modified_prefers_the_length_that_fits() {
+        let series = [
+            c(90.0, 91.0, 89.0, 90.5),
+            c(100.0, 104.0, 96.0, 102.0),
+            c(101.0, 103.0, 98.0, 100.0),
+            c(100.5, 102.0, 99.0, 101.0),
+            c(100.0, 101.5, 97.0, 98.5),
+            c(98.5, 103.0, 98.0, 102.5),
+        ];
+        let checks = verify("Hikkake Modified", &series).unwrap();
+        assert!(checks.passed, "{:?}", failed(&checks));
+        assert_eq!(checks.candles, 5);
+        let found: Vec<(&str, usize)> = detect(&series).iter().map(|d| (d.pattern, d.start)).collect();
+        assert!(found.contains(&("Hikkake Modified", 1)), "{:?}", found);
+        assert_eq!(found.iter().filter(|(p, _)| *p == "Hikkake Modified").count(), 1);
+
+        // A reversal that stays inside the last inside bar is no Hikkake
+        let mut weak = series;
+        weak[5] = c(98.5, 101.8, 98.0, 101.5);
+        assert!(!verify("Hikkake Modified", &weak).unwrap().passed);
+        assert!(!verify("Hikkake Pattern", &weak).unwrap().passed);
+    }
+}
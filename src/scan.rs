@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::candles::{self, Candle};
+use crate::import::{self, ImportOptions};
+use crate::models::{Direction, Pattern, PatternCategory};
+use crate::rules;
+use crate::taxonomy::{self, DEFAULT_TAXONOMY};
+
+/// Help for the options `parse_args` handles itself, for `concat!` into the
+/// usage text of `scan` and `backtest`.
+macro_rules! input_options_help {
+    () => {
+        "  --taxonomy <name>      report patterns of this taxonomy (default: default)
+  --patterns <a,b,...>   only these patterns
+  --source <name>        CSV export format: generic, tradingview, metatrader,
+                         binance or yahoo (default: detected)
+  --utc-offset <offset>  offset of timestamps without one, e.g. +02:00 for
+                         MetaTrader server time (default UTC); one fixed
+                         offset, so server time that follows DST is an hour
+                         off across the change
+  --out <file>           write the output here instead of stdout"
+    };
+}
+pub(crate) use input_options_help;
+
+const USAGE: &str = concat!(
+    "\
+Usage: deepseek-test scan <ohlc>... [options]
+
+Slides the geometric pattern rules over OHLC history and writes a timeline
+of every detection, bar by bar. Inputs are CSV exports (generic, TradingView,
+MetaTrader, Binance or Yahoo) or, for .json/.jsonl files, candle objects.
+
+Options:
+",
+    input_options_help!(),
+    "
+  --format <csv|jsonl>   timeline format (default csv)"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Jsonl,
+}
+
+/// Inputs and the options `scan` and `backtest` share.
+pub struct InputArgs {
+    pub inputs: Vec<PathBuf>,
+    pub taxonomy: String,
+    pub patterns: Option<Vec<String>>,
+    pub import: ImportOptions,
+    pub out: Option<PathBuf>,
+}
+
+/// One detection in a scanned series.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineEntry {
+    /// File (or request) the series came from.
+    pub series: String,
+    pub pattern: String,
+    pub category: PatternCategory,
+    pub direction: Direction,
+    /// Inclusive bar indexes of the pattern, 0 = first bar of the series.
+    pub start: usize,
+    pub end: usize,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Close of the bar completing the pattern.
+    pub close: f64,
+}
+
+/// Parses input files and the shared options. Any other `--option value`
+/// goes to `extra`, which returns whether it knew the option.
+pub fn parse_args(
+    args: &[String],
+    usage: &str,
+    mut extra: impl FnMut(&str, &str) -> Result<bool, String>,
+) -> Result<InputArgs, String> {
+    let mut parsed = InputArgs {
+        inputs: Vec::new(),
+        taxonomy: DEFAULT_TAXONOMY.to_string(),
+        patterns: None,
+        import: ImportOptions::default(),
+        out: None,
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(usage.to_string());
+        }
+        if !arg.starts_with("--") {
+            parsed.inputs.push(PathBuf::from(arg));
+            continue;
+        }
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("{} needs a value\n\n{}", arg, usage))?;
+        match arg.as_str() {
+            "--taxonomy" => parsed.taxonomy = value.clone(),
+            "--patterns" => {
+                parsed.patterns = Some(value.split(',').map(|p| p.trim().to_string()).collect())
+            }
+            "--source" => parsed.import.source = Some(value.parse()?),
+            "--utc-offset" => parsed.import.utc_offset = import::parse_offset(value)?,
+            "--out" => parsed.out = Some(PathBuf::from(value)),
+            other => {
+                if !extra(other, value)? {
+                    return Err(format!("Unknown option {}\n\n{}", other, usage));
+                }
+            }
+        }
+    }
+
+    if parsed.inputs.is_empty() {
+        return Err(usage.to_string());
+    }
+    Ok(parsed)
+}
+
+/// Every detection of `patterns` in `candles`, in order of the completing bar.
+/// Patterns without a geometric rule are never reported.
+pub fn timeline(series: &str, candles: &[Candle], patterns: &[Pattern]) -> Vec<TimelineEntry> {
+    rules::scan(candles)
+        .into_iter()
+        .filter_map(|detection| {
+            let pattern = patterns
+                .iter()
+                .find(|p| p.name.eq_ignore_ascii_case(detection.pattern))?;
+            Some(TimelineEntry {
+                series: series.to_string(),
+                pattern: pattern.name.clone(),
+                category: pattern.category,
+                direction: pattern.direction,
+                start: detection.start,
+                end: detection.end,
+                start_time: candles[detection.start].time,
+                end_time: candles[detection.end].time,
+                close: candles[detection.end].close,
+            })
+        })
+        .collect()
+}
+
+/// Taxonomy patterns narrowed to `wanted`, rejecting names the taxonomy lacks.
+pub fn select_patterns(
+    mut patterns: Vec<Pattern>,
+    taxonomy: &str,
+    wanted: Option<&[String]>,
+) -> Result<Vec<Pattern>, String> {
+    if let Some(wanted) = wanted {
+        for name in wanted {
+            if !patterns.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+                return Err(format!("Pattern {} is not in taxonomy {}", name, taxonomy));
+            }
+        }
+        patterns.retain(|p| wanted.iter().any(|w| w.eq_ignore_ascii_case(&p.name)));
+    }
+    let undefined: Vec<&str> = patterns
+        .iter()
+        .filter(|p| !rules::defined(&p.name))
+        .map(|p| p.name.as_str())
+        .collect();
+    if !undefined.is_empty() {
+        warn!("No rules for {} patterns, they are not scanned: {}", undefined.len(), undefined.join(", "));
+    }
+    Ok(patterns)
+}
+
+fn write_timeline(out: &mut dyn Write, format: Format, entries: &[TimelineEntry]) -> Result<(), String> {
+    match format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for entry in entries {
+                writer.serialize(entry).map_err(|e| e.to_string())?;
+            }
+            writer.flush().map_err(|e| e.to_string())
+        }
+        Format::Jsonl => {
+            for entry in entries {
+                let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+                writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut format = Format::Csv;
+    let args = parse_args(args, USAGE, |option, value| {
+        if option != "--format" {
+            return Ok(false);
+        }
+        format = match value {
+            "csv" => Format::Csv,
+            "jsonl" => Format::Jsonl,
+            other => return Err(format!("--format expects csv or jsonl, got {}", other)),
+        };
+        Ok(true)
+    })?;
+    let patterns = taxonomy::load_patterns(taxonomy::csv_path(&args.taxonomy))?;
+    let patterns = select_patterns(patterns, &args.taxonomy, args.patterns.as_deref())?;
+
+    let mut entries = Vec::new();
+    let mut bars = 0;
+    for path in &args.inputs {
+        let candles = candles::load_file(path, &args.import)?;
+        let found = timeline(&path.display().to_string(), &candles, &patterns);
+        info!("{}: {} bars, {} detections", path.display(), candles.len(), found.len());
+        bars += candles.len();
+        entries.extend(found);
+    }
+
+    match &args.out {
+        Some(path) => {
+            let mut file = std::fs::File::create(path)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+            write_timeline(&mut file, format, &entries)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        None => write_timeline(&mut std::io::stdout().lock(), format, &entries)?,
+    }
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for entry in &entries {
+        *counts.entry(entry.pattern.as_str()).or_default() += 1;
+    }
+    for (pattern, count) in &counts {
+        info!("  {}: {}", pattern, count);
+    }
+    info!(
+        "Scanned {} bars in {} files: {} detections{}",
+        bars,
+        args.inputs.len(),
+        entries.len(),
+        args.out.as_deref().map(Path::display).map(|p| format!(", written to {}", p)).unwrap_or_default()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Pattern;
+
+    fn default_patterns() -> Vec<Pattern> {
+        taxonomy::load_patterns(taxonomy::csv_path(DEFAULT_TAXONOMY)).unwrap()
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    /// Six falling candles, then a hammer on bar 6.
+    fn hammer_after_downtrend() -> Vec<Candle> {
+        let mut candles: Vec<Candle> = (0..6)
+            .map(|i| {
+                let close = 106.0 - i as f64;
+                Candle { time: Some(i * 60), open: close + 0.8, high: close + 1.0, low: close - 0.2, close, volume: None }
+            })
+            .collect();
+        candles.push(Candle { time: Some(360), open: 100.0, high: 100.6, low: 98.0, close: 100.5, volume: None });
+        candles
+    }
+
+    #[test]
+    fn timeline_reports_taxonomy_patterns_at_their_bars() {
+        let candles = hammer_after_downtrend();
+        let entries = timeline("s.csv", &candles, &default_patterns());
+        let hammer = entries.iter().find(|e| e.pattern == "Hammer").expect("hammer detected");
+        assert_eq!((hammer.series.as_str(), hammer.start, hammer.end), ("s.csv", 6, 6));
+        assert_eq!((hammer.start_time, hammer.end_time, hammer.close), (Some(360), Some(360), 100.5));
+        assert_eq!(hammer.direction, Direction::Bullish);
+        assert!(entries.windows(2).all(|w| w[0].end <= w[1].end));
+
+        // Patterns left out of the list are never reported
+        assert!(entries.iter().any(|e| e.pattern != "Hammer"));
+        let only = select_patterns(default_patterns(), DEFAULT_TAXONOMY, Some(&args(&["Hammer"]))).unwrap();
+        let names: Vec<String> = timeline("s.csv", &candles, &only).into_iter().map(|e| e.pattern).collect();
+        assert_eq!(names, ["Hammer"]);
+    }
+
+    #[test]
+    fn select_patterns_matches_names_case_insensitively() {
+        let all = default_patterns();
+        assert_eq!(select_patterns(all.clone(), DEFAULT_TAXONOMY, None).unwrap().len(), all.len());
+
+        let picked = select_patterns(all.clone(), DEFAULT_TAXONOMY, Some(&args(&["hammer", "SHOOTING STAR"]))).unwrap();
+        let names: Vec<&str> = picked.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"Hammer") && names.contains(&"Shooting Star"));
+
+        let err = select_patterns(all, DEFAULT_TAXONOMY, Some(&args(&["Hammer", "Moon"]))).unwrap_err();
+        assert_eq!(err, "Pattern Moon is not in taxonomy default");
+    }
+
+    #[test]
+    fn parse_args_hands_unknown_options_to_the_caller() {
+        let mut seen = Vec::new();
+        let parsed = parse_args(
+            &args(&["a.csv", "--patterns", "Hammer, Doji", "--utc-offset", "+02:00", "--depth", "3", "b.csv"]),
+            "usage",
+            |option, value| {
+                seen.push((option.to_string(), value.to_string()));
+                Ok(option == "--depth")
+            },
+        )
+        .unwrap();
+        assert_eq!(parsed.inputs, [PathBuf::from("a.csv"), PathBuf::from("b.csv")]);
+        assert_eq!(parsed.patterns, Some(args(&["Hammer", "Doji"])));
+        assert_eq!(parsed.import.utc_offset, 7200);
+        assert_eq!(seen, [("--depth".to_string(), "3".to_string())]);
+
+        let unknown = parse_args(&args(&["a.csv", "--depth", "3"]), "usage", |_, _| Ok(false));
+        assert_eq!(unknown.err().as_deref(), Some("Unknown option --depth\n\nusage"));
+        assert_eq!(parse_args(&args(&["--taxonomy", "default"]), "usage", |_, _| Ok(false)).err().as_deref(), Some("usage"));
+    }
+}
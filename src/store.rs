@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::experiments::Assignment;
+use crate::models::{AnalyzeResponse, Confidence, Direction, PatternCategory};
+
+// Layout:
+//   <data_dir>/analyses.jsonl   one StoredAnalysis per line
+//   <data_dir>/feedback.jsonl   one Feedback per line; the latest per analysis wins
+//   <data_dir>/failures.jsonl   one FailedAnalysis per failed experiment request
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredAnalysis {
+    pub id: String,
+    pub timestamp: u64,
+    pub taxonomy: String,
+    pub pattern: String,
+    pub category: Option<PatternCategory>,
+    pub direction: Option<Direction>,
+    pub confidence: Confidence,
+    pub chart_description: String,
+    pub experiment: Option<Assignment>,
+    pub total_cost_usd: f64,
+    /// Wall time of the request; absent on records from before it was kept.
+    pub latency_ms: Option<u64>,
+}
+
+/// An experiment request that errored, kept so its variant's metrics count it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedAnalysis {
+    pub timestamp: u64,
+    pub experiment: Assignment,
+    /// The reasoner answered, but not with parseable pattern JSON.
+    pub parse_failure: bool,
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Feedback {
+    pub analysis_id: String,
+    pub timestamp: u64,
+    /// Canonical taxonomy name of the pattern the chart actually shows.
+    pub correct_pattern: String,
+    /// Whether the stored prediction matched `correct_pattern`.
+    pub correct: bool,
+    pub comment: Option<String>,
+}
+
+pub struct AnalysisStore {
+    dir: PathBuf,
+    analyses: Vec<StoredAnalysis>,
+    index: HashMap<String, usize>,
+    feedback: HashMap<String, Feedback>,
+    failures: Vec<FailedAnalysis>,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 16 random hex chars; plenty for a single-node store.
+pub fn new_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+pub fn read_jsonl<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    let mut items = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(item) => items.push(item),
+            Err(e) => warn!("Skipping corrupt line {} of {}: {}", n + 1, path.display(), e),
+        }
+    }
+    Ok(items)
+}
+
+pub fn append_jsonl<T: Serialize>(path: &Path, item: &T) -> Result<(), String> {
+    append_all_jsonl(path, std::slice::from_ref(item))
+}
+
+/// Appends `items` with a single write, so a failure leaves none of them half-added.
+pub fn append_all_jsonl<T: Serialize>(path: &Path, items: &[T]) -> Result<(), String> {
+    let mut lines = String::new();
+    for item in items {
+        lines.push_str(&serde_json::to_string(item).map_err(|e| e.to_string())?);
+        lines.push('\n');
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    file.write_all(lines.as_bytes())
+        .map_err(|e| format!("Failed to append {}: {}", path.display(), e))
+}
+
+impl StoredAnalysis {
+    pub fn from_response(response: &AnalyzeResponse, latency_ms: u64) -> Self {
+        StoredAnalysis {
+            id: response.id.clone(),
+            timestamp: now_secs(),
+            taxonomy: response.taxonomy.clone(),
+            pattern: response.pattern.clone(),
+            category: response.category,
+            direction: response.direction,
+            confidence: response.confidence,
+            chart_description: response.chart_description.clone(),
+            experiment: response.experiment.clone(),
+            total_cost_usd: response.cost.total_cost_usd,
+            latency_ms: Some(latency_ms),
+        }
+    }
+}
+
+impl AnalysisStore {
+    pub fn load(dir: &str) -> Result<Self, String> {
+        let dir = PathBuf::from(dir);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let analyses: Vec<StoredAnalysis> = read_jsonl(&dir.join("analyses.jsonl"))?;
+        let index = analyses
+            .iter()
+            .enumerate()
+            .map(|(i, a)| (a.id.clone(), i))
+            .collect();
+        let feedback = read_jsonl::<Feedback>(&dir.join("feedback.jsonl"))?
+            .into_iter()
+            .map(|f| (f.analysis_id.clone(), f))
+            .collect::<HashMap<_, _>>();
+        let failures = read_jsonl(&dir.join("failures.jsonl"))?;
+
+        info!(
+            "Loaded {} stored analyses ({} with feedback)",
+            analyses.len(),
+            feedback.len()
+        );
+
+        Ok(AnalysisStore {
+            dir,
+            analyses,
+            index,
+            feedback,
+            failures,
+        })
+    }
+
+    pub fn insert(&mut self, analysis: StoredAnalysis) -> Result<(), String> {
+        append_jsonl(&self.dir.join("analyses.jsonl"), &analysis)?;
+        self.index.insert(analysis.id.clone(), self.analyses.len());
+        self.analyses.push(analysis);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&StoredAnalysis> {
+        self.index.get(id).map(|&i| &self.analyses[i])
+    }
+
+    pub fn insert_failure(&mut self, failure: FailedAnalysis) -> Result<(), String> {
+        append_jsonl(&self.dir.join("failures.jsonl"), &failure)?;
+        self.failures.push(failure);
+        Ok(())
+    }
+
+    /// Every stored analysis, oldest first.
+    pub fn analyses(&self) -> &[StoredAnalysis] {
+        &self.analyses
+    }
+
+    pub fn failures(&self) -> &[FailedAnalysis] {
+        &self.failures
+    }
+
+    pub fn add_feedback(&mut self, feedback: Feedback) -> Result<(), String> {
+        append_jsonl(&self.dir.join("feedback.jsonl"), &feedback)?;
+        self.feedback.insert(feedback.analysis_id.clone(), feedback);
+        Ok(())
+    }
+
+    /// Analyses paired with their latest feedback, oldest first.
+    pub fn with_feedback(&self) -> impl Iterator<Item = (&StoredAnalysis, &Feedback)> {
+        self.analyses
+            .iter()
+            .filter_map(|a| self.feedback.get(&a.id).map(|f| (a, f)))
+    }
+}
@@ -0,0 +1,747 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::candles::{self, Candle};
+use crate::models::{Direction, Pattern, PatternCategory};
+use crate::render::{self, RenderOptions, Theme};
+use crate::taxonomy::{self, DEFAULT_TAXONOMY};
+
+const USAGE: &str = "\
+Usage: deepseek-test generate [options]
+
+Generates labelled synthetic candlestick charts (PNG + OHLC CSV) for every
+pattern in a taxonomy and writes a manifest.jsonl usable by `eval`.
+
+Options:
+  --taxonomy <name>      taxonomy to generate (default: default)
+  --patterns <a,b,...>   only these patterns
+  --count <n>            samples per pattern (default 10)
+  --out <dir>            output directory (default synthetic)
+  --seed <n>             RNG seed for reproducible datasets
+  --context <n>          max trend candles before the pattern (default 12)
+  --noise <f>            noise multiplier for trend and chart paths (default 1.0)
+  --styles <a,b,...>     chart styles: light, dark, classic, contrast (default all)
+  --sizes <WxH,...>      image sizes (default 800x500,1024x640,640x400)
+  --grid <on|off|mixed>  gridlines (default mixed)";
+
+/// open, high, low, close in abstract price units relative to the pattern start.
+type Bar = [f64; 4];
+
+struct GenerateArgs {
+    taxonomy: String,
+    patterns: Option<Vec<String>>,
+    count: usize,
+    out: PathBuf,
+    seed: u64,
+    context: usize,
+    noise: f64,
+    styles: Vec<&'static Theme>,
+    sizes: Vec<(u32, u32)>,
+    grid: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry {
+    id: String,
+    pattern: String,
+    taxonomy: String,
+    image: String,
+    ohlc: String,
+    style: String,
+    width: u32,
+    height: u32,
+    /// Inclusive candle indices that form the pattern.
+    pattern_range: [usize; 2],
+}
+
+fn parse_args(args: &[String]) -> Result<GenerateArgs, String> {
+    let mut parsed = GenerateArgs {
+        taxonomy: DEFAULT_TAXONOMY.to_string(),
+        patterns: None,
+        count: 10,
+        out: PathBuf::from("synthetic"),
+        seed: rand::random(),
+        context: 12,
+        noise: 1.0,
+        styles: render::THEMES.iter().collect(),
+        sizes: vec![(800, 500), (1024, 640), (640, 400)],
+        grid: None,
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(USAGE.to_string());
+        }
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("{} needs a value\n\n{}", arg, USAGE))?;
+        let number = |what: &str| format!("{} expects a number, got {}", what, value);
+        match arg.as_str() {
+            "--taxonomy" => parsed.taxonomy = value.clone(),
+            "--patterns" => {
+                parsed.patterns = Some(value.split(',').map(|p| p.trim().to_string()).collect())
+            }
+            "--count" => parsed.count = value.parse().map_err(|_| number(arg))?,
+            "--out" => parsed.out = PathBuf::from(value),
+            "--seed" => parsed.seed = value.parse().map_err(|_| number(arg))?,
+            "--context" => parsed.context = value.parse().map_err(|_| number(arg))?,
+            "--noise" => parsed.noise = value.parse().map_err(|_| number(arg))?,
+            "--styles" => {
+                parsed.styles = value
+                    .split(',')
+                    .map(|s| render::theme(s.trim()).ok_or_else(|| format!("Unknown style {}", s)))
+                    .collect::<Result<_, _>>()?
+            }
+            "--sizes" => {
+                parsed.sizes = value
+                    .split(',')
+                    .map(|s| {
+                        s.trim()
+                            .split_once('x')
+                            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                            .ok_or_else(|| format!("Invalid size {}, expected WxH", s))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            "--grid" => {
+                parsed.grid = match value.as_str() {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    "mixed" => None,
+                    other => return Err(format!("--grid expects on, off or mixed, got {}", other)),
+                }
+            }
+            other => return Err(format!("Unknown option {}\n\n{}", other, USAGE)),
+        }
+    }
+
+    if parsed.styles.is_empty() || parsed.sizes.is_empty() {
+        return Err("At least one style and one size are required".to_string());
+    }
+    Ok(parsed)
+}
+
+fn r(rng: &mut StdRng, lo: f64, hi: f64) -> f64 {
+    if hi <= lo {
+        lo
+    } else {
+        rng.gen_range(lo..hi)
+    }
+}
+
+/// Bar from its body and wick lengths.
+fn c(open: f64, close: f64, upper: f64, lower: f64) -> Bar {
+    [open, open.max(close) + upper, open.min(close) - lower, close]
+}
+
+/// Reflects bars around the start price, turning a bullish shape into its bearish twin.
+fn mirror(bars: Vec<Bar>) -> Vec<Bar> {
+    bars.into_iter().map(|[o, h, l, cl]| [-o, -l, -h, -cl]).collect()
+}
+
+/// Three long bullish candles, each opening inside the prior body and closing above it.
+fn soldiers(rng: &mut StdRng) -> Vec<Bar> {
+    let mut bars = Vec::new();
+    let (mut open, mut body) = (0.0, r(rng, 1.4, 2.0));
+    for _ in 0..3 {
+        bars.push(c(open, open + body, r(rng, 0.0, 0.2), r(rng, 0.0, 0.2)));
+        open += body * r(rng, 0.4, 0.8);
+        body = r(rng, 1.4, 2.0);
+    }
+    bars
+}
+
+/// Shapes for the candlestick patterns, built with randomized proportions that
+/// still satisfy the pattern definition.
+fn candle_template(name: &str, rng: &mut StdRng) -> Option<Vec<Bar>> {
+    let big = r(rng, 1.6, 2.4);
+    let small = r(rng, 0.25, 0.5);
+    let doji = r(rng, 0.0, 0.03);
+    let wick = r(rng, 0.1, 0.35);
+    let tiny = r(rng, 0.0, 0.05);
+
+    let bars = match name {
+        "Hammer" => vec![c(0.0, small, tiny, small * r(rng, 2.2, 3.5))],
+        "Inverted Hammer" => vec![c(0.0, small, small * r(rng, 2.2, 3.5), tiny)],
+        "Dragonfly Doji" => vec![c(0.0, doji, 0.0, r(rng, 2.0, 3.5))],
+        "Spinning Top (Bullish)" | "Spinning Top (Bearish)" => {
+            let legs = r(rng, 0.8, 1.3);
+            let body = if name.contains("Bullish") { small * 0.6 } else { -small * 0.6 };
+            vec![c(0.0, body, legs * r(rng, 0.9, 1.1), legs * r(rng, 0.9, 1.1))]
+        }
+        "Marubozu (Bullish)" => vec![c(0.0, big, 0.0, 0.0)],
+        "Marubozu (Bearish)" => vec![c(0.0, -big, 0.0, 0.0)],
+        "Hanging Man" => vec![c(0.0, -small, tiny, small * r(rng, 2.2, 3.5))],
+        "Shooting Star" => vec![c(0.0, -small, small * r(rng, 2.2, 3.5), tiny)],
+        "Gravestone Doji" => vec![c(0.0, -doji, r(rng, 2.0, 3.5), 0.0)],
+        "Doji (Standard)" => vec![c(0.0, doji, r(rng, 0.4, 0.9), r(rng, 0.4, 0.9))],
+        "Long-legged Doji" => vec![c(0.0, doji, r(rng, 1.8, 3.0), r(rng, 1.8, 3.0))],
+        "Four-Price Doji" => vec![c(0.0, 0.0, 0.0, 0.0)],
+        "Rickshaw Man" => {
+            let legs = r(rng, 1.8, 3.0);
+            vec![c(0.0, 0.0, legs, legs)]
+        }
+
+        "Bullish Engulfing" => vec![
+            c(0.0, -small, wick, wick),
+            c(-small - r(rng, 0.1, 0.3), r(rng, 0.2, 0.6), wick, wick),
+        ],
+        "Piercing Line" => {
+            let lower = r(rng, 0.1, 0.3);
+            let open = -big - lower - r(rng, 0.1, 0.3);
+            vec![
+                c(0.0, -big, wick, lower),
+                c(open, -big * r(rng, 0.2, 0.45), wick, tiny),
+            ]
+        }
+        "Bullish Harami" => vec![
+            c(0.0, -big, wick, wick),
+            c(-big * r(rng, 0.6, 0.75), -big * r(rng, 0.3, 0.45), tiny, tiny),
+        ],
+        "Tweezer Bottom" => {
+            let lower = r(rng, 0.4, 0.8);
+            let low = -small - lower;
+            let open = -small + r(rng, -0.1, 0.1);
+            vec![
+                c(0.0, -small, wick, lower),
+                c(open, open + small, wick, open - low),
+            ]
+        }
+        "Bullish Kicker" => {
+            let open = r(rng, 0.2, 0.5);
+            vec![c(0.0, -big, wick, wick), c(open, open + big, wick, 0.0)]
+        }
+        "On-Neck Line" => {
+            let lower = r(rng, 0.1, 0.3);
+            let low = -big - lower;
+            vec![
+                c(0.0, -big, wick, lower),
+                c(low - r(rng, 0.3, 0.6), low, tiny, wick),
+            ]
+        }
+        "Bullish Counterattack Line" => vec![
+            c(0.0, -big, wick, wick),
+            c(-big - r(rng, 0.9, 1.4), -big, wick, wick),
+        ],
+        "In-Neck Line" => {
+            let lower = r(rng, 0.1, 0.3);
+            vec![
+                c(0.0, -big, wick, lower),
+                c(-big - lower - r(rng, 0.3, 0.6), -big + r(rng, 0.0, 0.15), tiny, wick),
+            ]
+        }
+        "Thrusting Line" => {
+            let lower = r(rng, 0.1, 0.3);
+            vec![
+                c(0.0, -big, wick, lower),
+                c(-big - lower - r(rng, 0.3, 0.6), -big * r(rng, 0.55, 0.8), tiny, wick),
+            ]
+        }
+        "Bearish Engulfing" => mirror(candle_template("Bullish Engulfing", rng)?),
+        "Dark Cloud Cover" => mirror(candle_template("Piercing Line", rng)?),
+        "Bearish Harami" => mirror(candle_template("Bullish Harami", rng)?),
+        "Tweezer Top" => mirror(candle_template("Tweezer Bottom", rng)?),
+        "Bearish Kicker" => mirror(candle_template("Bullish Kicker", rng)?),
+        "Bearish Counterattack Line" => mirror(candle_template("Bullish Counterattack Line", rng)?),
+
+        "Morning Star" | "Morning Doji Star" => {
+            let body = if name == "Morning Star" { small } else { doji };
+            let open = -big - r(rng, 0.3, 0.6);
+            vec![
+                c(0.0, -big, wick, tiny),
+                c(open, open - body, tiny, tiny),
+                c(open + r(rng, 0.0, 0.2), -big * r(rng, 0.2, 0.45), wick, tiny),
+            ]
+        }
+        "Evening Star" => mirror(candle_template("Morning Star", rng)?),
+        "Evening Doji Star" => mirror(candle_template("Morning Doji Star", rng)?),
+        "Three White Soldiers" => soldiers(rng),
+        "Three Black Crows" => mirror(soldiers(rng)),
+        "Three Inside Up" => {
+            let first = c(0.0, -big, wick, wick);
+            let second = c(-big * r(rng, 0.6, 0.75), -big * r(rng, 0.3, 0.45), tiny, tiny);
+            let third = c(second[3], first[1] + r(rng, 0.2, 0.6), wick, tiny);
+            vec![first, second, third]
+        }
+        "Three Inside Down" => mirror(candle_template("Three Inside Up", rng)?),
+        "Three Outside Up" => {
+            let mut bars = candle_template("Bullish Engulfing", rng)?;
+            let open = bars[1][3];
+            bars.push(c(open - r(rng, 0.0, 0.1), open + big, wick, tiny));
+            bars
+        }
+        "Three Outside Down" => mirror(candle_template("Three Outside Up", rng)?),
+        "Bullish Abandoned Baby" => {
+            let lower = tiny;
+            let first_low = -big - lower;
+            let star_upper = r(rng, 0.1, 0.3);
+            let star_open = first_low - r(rng, 0.3, 0.6) - star_upper;
+            let star_lower = r(rng, 0.1, 0.3);
+            let star_high = star_open + doji + star_upper;
+            let open = star_high + r(rng, 0.3, 0.6);
+            vec![
+                c(0.0, -big, wick, lower),
+                c(star_open, star_open + doji, star_upper, star_lower),
+                c(open, open + big, wick, 0.0),
+            ]
+        }
+        "Bearish Abandoned Baby" => mirror(candle_template("Bullish Abandoned Baby", rng)?),
+        "Unique Three River Bottom" => {
+            // Hammer-like second candle whose lower wick stays above the first candle's low.
+            let lower = r(rng, 0.4, 0.7);
+            let open = -big * r(rng, 0.55, 0.65);
+            let close = open - big * r(rng, 0.05, 0.12);
+            vec![
+                c(0.0, -big, wick * 0.5, lower),
+                c(open, close, tiny, close + big + lower * r(rng, 0.4, 0.8)),
+                c(-big * r(rng, 0.9, 0.95), -big * r(rng, 0.75, 0.85), tiny, tiny),
+            ]
+        }
+        "Mat Hold (Bullish)" => {
+            let mut bars = vec![c(0.0, big, tiny, tiny)];
+            let mut open = big + r(rng, 0.2, 0.4);
+            for _ in 0..3 {
+                let close = open - r(rng, 0.2, 0.35);
+                bars.push(c(open, close, tiny, tiny));
+                open = close + r(rng, 0.0, 0.1);
+            }
+            bars.push(c(open, big + r(rng, 0.8, 1.4), wick, tiny));
+            bars
+        }
+        "Identical Three Crows" => {
+            let mut bars = Vec::new();
+            let mut open = 0.0;
+            for _ in 0..3 {
+                let close = open - r(rng, 1.3, 2.0);
+                bars.push(c(open, close, r(rng, 0.0, 0.15), r(rng, 0.0, 0.15)));
+                open = close;
+            }
+            bars
+        }
+        "Deliberation Pattern" => {
+            let mut bars = soldiers(rng);
+            let open = bars[1][3] + r(rng, 0.0, 0.1);
+            bars[2] = c(open, open + small * 0.6, wick, tiny);
+            bars
+        }
+        "Advance Block" => {
+            let mut bars = Vec::new();
+            let (mut open, mut body, mut upper) = (0.0, r(rng, 1.8, 2.2), r(rng, 0.1, 0.2));
+            for _ in 0..3 {
+                bars.push(c(open, open + body, upper, tiny));
+                open += body * r(rng, 0.5, 0.8);
+                body *= r(rng, 0.5, 0.7);
+                upper += r(rng, 0.3, 0.6);
+            }
+            bars
+        }
+
+        "Rising Three Methods" => {
+            let mut bars = vec![c(0.0, big, wick, wick)];
+            let mut open = big * r(rng, 0.85, 0.95);
+            for _ in 0..3 {
+                let close = open - r(rng, 0.25, 0.4);
+                bars.push(c(open, close, tiny, tiny));
+                open = close + r(rng, 0.0, 0.1);
+            }
+            bars.push(c(open, big + r(rng, 0.3, 0.8), wick, tiny));
+            bars
+        }
+        "Falling Three Methods" => mirror(candle_template("Rising Three Methods", rng)?),
+        "Bullish Three-Line Strike" => {
+            let mut bars = soldiers(rng);
+            let top = bars[2][3];
+            bars.push(c(top + r(rng, 0.1, 0.3), -r(rng, 0.2, 0.5), tiny, tiny));
+            bars
+        }
+        "Bearish Three-Line Strike" => mirror(candle_template("Bullish Three-Line Strike", rng)?),
+        "Concealing Baby Swallow" => {
+            let second = -big - r(rng, 1.2, 1.8);
+            let third_open = second - r(rng, 0.3, 0.5);
+            let third_close = third_open - small;
+            let third_high = second + r(rng, 0.3, 0.6);
+            vec![
+                c(0.0, -big, 0.0, 0.0),
+                c(-big, second, 0.0, 0.0),
+                c(third_open, third_close, third_high - third_open, 0.0),
+                c(third_high + r(rng, 0.1, 0.3), third_close - r(rng, 0.3, 0.6), 0.0, 0.0),
+            ]
+        }
+        "Ladder Bottom" => {
+            let mut bars = mirror(soldiers(rng));
+            let open = bars[2][3] + r(rng, 0.0, 0.1);
+            bars.push(c(open, open - small, r(rng, 0.8, 1.2), tiny));
+            bars.push(c(open + r(rng, 0.2, 0.4), open + big, wick, tiny));
+            bars
+        }
+        "Stick Sandwich (Bullish)" => {
+            let open = -big + r(rng, 0.05, 0.2);
+            let close = -r(rng, 0.0, 0.3);
+            vec![
+                c(0.0, -big, wick, tiny),
+                c(open, close, wick, tiny),
+                c(close + r(rng, 0.1, 0.3), -big, wick, tiny),
+            ]
+        }
+        "Descent Block" => mirror(candle_template("Advance Block", rng)?),
+        "Tower Top" => {
+            let mut bars = vec![c(0.0, big, wick, tiny), c(big, 2.0 * big, wick, tiny)];
+            let top = 2.0 * big;
+            for _ in 0..rng.gen_range(3..=4) {
+                let open = top + r(rng, -0.2, 0.2);
+                bars.push(c(open, open + r(rng, -0.3, 0.3), wick, wick));
+            }
+            bars.push(c(top, big, tiny, wick));
+            bars.push(c(big, 0.0, tiny, wick));
+            bars
+        }
+
+        "Upside Tasuki Gap" => {
+            let first = c(0.0, big, tiny, tiny);
+            let gap = r(rng, 0.3, 0.6);
+            let open = first[1] + gap;
+            let second = c(open, open + big, tiny, 0.0);
+            let third_open = open + big * r(rng, 0.3, 0.7);
+            vec![first, second, c(third_open, first[1] + gap * r(rng, 0.3, 0.7), tiny, 0.0)]
+        }
+        "Downside Tasuki Gap" => mirror(candle_template("Upside Tasuki Gap", rng)?),
+        "Side-by-Side White Lines" => {
+            let first = c(0.0, big, tiny, tiny);
+            let open = first[1] + r(rng, 0.3, 0.6);
+            let body = big * r(rng, 0.5, 0.7);
+            vec![
+                first,
+                c(open, open + body, wick, 0.0),
+                c(open, open + body * r(rng, 0.9, 1.1), wick, 0.0),
+            ]
+        }
+        "Rising Window" => {
+            let first = c(0.0, big, wick, wick);
+            let lower = tiny;
+            let open = first[1] + r(rng, 0.3, 0.6) + lower;
+            vec![first, c(open, open + big, wick, lower)]
+        }
+        "Falling Window" => mirror(candle_template("Rising Window", rng)?),
+        "Separating Lines (Bullish)" => vec![c(0.0, -big, wick, wick), c(0.0, big, wick, 0.0)],
+        "Separating Lines (Bearish)" => mirror(candle_template("Separating Lines (Bullish)", rng)?),
+
+        "Hikkake Pattern" | "Hikkake Modified" => {
+            let mut bars = vec![c(0.0, big, wick, wick)];
+            let inside = if name == "Hikkake Pattern" { 1 } else { rng.gen_range(2..=3) };
+            let (mut low, mut high) = (bars[0][2], bars[0][1]);
+            for _ in 0..inside {
+                low += (high - low) * r(rng, 0.1, 0.2);
+                high -= (high - low) * r(rng, 0.1, 0.2);
+                let open = low + (high - low) * r(rng, 0.3, 0.45);
+                bars.push([open, high, low, low + (high - low) * r(rng, 0.55, 0.7)]);
+            }
+            // False breakdown below the inside bar, then a reversal above its high.
+            let fake_low = low - r(rng, 0.3, 0.6);
+            let fake_high = high - (high - low) * r(rng, 0.3, 0.5);
+            let range = fake_high - fake_low;
+            let close = fake_low + range * 0.2;
+            bars.push([fake_high - range * 0.2, fake_high, fake_low, close]);
+            bars.push(c(close, high + r(rng, 0.4, 0.8), wick, tiny));
+            bars
+        }
+        "Matching Low" => vec![
+            c(0.0, -big, wick, tiny),
+            c(-big * r(rng, 0.4, 0.6), -big, wick, tiny),
+        ],
+        "Matching High" => mirror(candle_template("Matching Low", rng)?),
+        "Belt Hold (Bullish)" => vec![c(0.0, big, wick, 0.0)],
+        "Belt Hold (Bearish)" => mirror(candle_template("Belt Hold (Bullish)", rng)?),
+        "Breakaway (Bullish)" => {
+            let mut bars = vec![c(0.0, -big, wick, tiny)];
+            let mut open = -big - tiny - r(rng, 0.3, 0.6);
+            for _ in 0..3 {
+                let close = open - r(rng, 0.4, 0.8);
+                bars.push(c(open, close, tiny, tiny));
+                open = close + r(rng, -0.1, 0.1);
+            }
+            bars.push(c(open, -big - r(rng, 0.0, 0.2), wick, tiny));
+            bars
+        }
+        "Breakaway (Bearish)" => mirror(candle_template("Breakaway (Bullish)", rng)?),
+        _ => return None,
+    };
+    Some(bars)
+}
+
+/// Closing-price waypoints (candle steps, level) for the multi-week chart
+/// formations, walked with noisy candles.
+fn path_waypoints(name: &str) -> Option<(Vec<(usize, f64)>, bool)> {
+    let (points, mirrored): (&[(usize, f64)], bool) = match name {
+        "Head and Shoulders" => (&[(4, 4.0), (3, 2.5), (4, 6.5), (4, 2.5), (3, 4.0), (4, 0.5)], false),
+        "Inverse Head and Shoulders" => (&[(4, 4.0), (3, 2.5), (4, 6.5), (4, 2.5), (3, 4.0), (4, 0.5)], true),
+        "Double Top" => (&[(5, 5.0), (4, 2.5), (4, 5.0), (5, 0.5)], false),
+        "Double Bottom" => (&[(5, 5.0), (4, 2.5), (4, 5.0), (5, 0.5)], true),
+        "Triple Top" => (&[(4, 5.0), (3, 2.5), (3, 5.0), (3, 2.5), (3, 5.0), (4, 1.0)], false),
+        "Triple Bottom" => (&[(4, 5.0), (3, 2.5), (3, 5.0), (3, 2.5), (3, 5.0), (4, 1.0)], true),
+        "Ascending Triangle" => (
+            &[(4, 5.0), (3, 2.0), (3, 5.0), (3, 3.0), (2, 5.0), (2, 4.0), (2, 5.0), (3, 7.0)],
+            false,
+        ),
+        "Descending Triangle" => (
+            &[(4, 5.0), (3, 2.0), (3, 5.0), (3, 3.0), (2, 5.0), (2, 4.0), (2, 5.0), (3, 7.0)],
+            true,
+        ),
+        "Symmetrical Triangle" => (
+            &[(4, 5.0), (3, 1.0), (3, 4.2), (3, 1.8), (2, 3.5), (2, 2.4), (2, 3.0), (3, 5.5)],
+            false,
+        ),
+        "Bull Flag" => (&[(4, 6.0), (2, 5.0), (2, 5.6), (2, 4.6), (2, 5.2), (2, 4.2), (3, 7.5)], false),
+        "Bear Flag" => (&[(4, 6.0), (2, 5.0), (2, 5.6), (2, 4.6), (2, 5.2), (2, 4.2), (3, 7.5)], true),
+        "Bull Pennant" => (&[(4, 6.0), (2, 5.0), (2, 5.8), (2, 5.3), (2, 5.6), (2, 5.4), (3, 7.5)], false),
+        "Bear Pennant" => (&[(4, 6.0), (2, 5.0), (2, 5.8), (2, 5.3), (2, 5.6), (2, 5.4), (3, 7.5)], true),
+        "Rising Wedge" => (
+            &[(3, 3.0), (3, 1.5), (3, 4.5), (3, 3.2), (2, 5.5), (2, 4.8), (2, 6.0), (4, 3.0)],
+            false,
+        ),
+        "Falling Wedge" => (
+            &[(3, 3.0), (3, 1.5), (3, 4.5), (3, 3.2), (2, 5.5), (2, 4.8), (2, 6.0), (4, 3.0)],
+            true,
+        ),
+        "Cup and Handle" => (
+            &[(3, -2.5), (3, -4.0), (3, -4.3), (3, -4.0), (3, -2.5), (3, 0.0), (2, -0.9), (2, -0.6), (2, -1.1), (3, 1.5)],
+            false,
+        ),
+        "Rectangle Range" => (&[(3, 3.0), (3, 0.0), (3, 3.0), (3, 0.0), (3, 3.0), (3, 0.0), (2, 1.5)], false),
+        _ => return None,
+    };
+    Some((points.to_vec(), mirrored))
+}
+
+fn walk(rng: &mut StdRng, start: f64, waypoints: &[(usize, f64)], noise: f64) -> Vec<Bar> {
+    let mut bars = Vec::new();
+    let mut close = start;
+    let mut level = start;
+    for &(steps, target) in waypoints {
+        let steps = steps + rng.gen_range(0..=1);
+        let from = level;
+        for i in 1..=steps {
+            let expected = from + (target - from) * i as f64 / steps as f64;
+            let open = close + r(rng, -0.1, 0.1) * noise;
+            close = expected + r(rng, -0.2, 0.2) * noise;
+            bars.push(c(
+                open,
+                close,
+                r(rng, 0.0, 0.3) * noise,
+                r(rng, 0.0, 0.3) * noise,
+            ));
+        }
+        level = target;
+    }
+    bars
+}
+
+fn template(name: &str, rng: &mut StdRng, noise: f64) -> Option<Vec<Bar>> {
+    if let Some(bars) = candle_template(name, rng) {
+        return Some(bars);
+    }
+    let (waypoints, mirrored) = path_waypoints(name)?;
+    let bars = walk(rng, 0.0, &waypoints, noise);
+    Some(if mirrored { mirror(bars) } else { bars })
+}
+
+/// Reversal patterns are preceded by the opposite trend, continuations by the same one.
+fn context_is_uptrend(pattern: &Pattern, rng: &mut StdRng) -> bool {
+    let continuation = pattern.category == PatternCategory::Continuation
+        || matches!(
+            pattern.direction,
+            Direction::BullishContinuation | Direction::BearishContinuation
+        );
+    match pattern.direction {
+        Direction::Bullish | Direction::BullishContinuation => continuation,
+        Direction::Bearish | Direction::BearishContinuation => !continuation,
+        Direction::Neutral | Direction::Both => rng.gen_bool(0.5),
+    }
+}
+
+fn trend(rng: &mut StdRng, len: usize, up: bool, noise: f64) -> Vec<Bar> {
+    let drift = if up { 1.0 } else { -1.0 };
+    let mut bars = Vec::new();
+    let mut close = 0.0;
+    for _ in 0..len {
+        let open = close + r(rng, -0.1, 0.1) * noise;
+        close = open + drift * r(rng, 0.2, 0.9) + r(rng, -0.5, 0.5) * noise;
+        bars.push(c(open, close, r(rng, 0.05, 0.4) * noise, r(rng, 0.05, 0.4) * noise));
+    }
+    bars
+}
+
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// Returns the candles and the inclusive index range of the pattern.
+fn sample(
+    pattern: &Pattern,
+    rng: &mut StdRng,
+    args: &GenerateArgs,
+) -> Option<(Vec<Candle>, [usize; 2])> {
+    let context_len = if args.context == 0 {
+        0
+    } else {
+        rng.gen_range(args.context.div_ceil(2)..=args.context)
+    };
+    let up = context_is_uptrend(pattern, rng);
+    let mut bars = trend(rng, context_len, up, args.noise);
+    let offset = bars.last().map(|b| b[3]).unwrap_or(0.0);
+    let shape = template(&pattern.name, rng, args.noise)?;
+    let range = [bars.len(), bars.len() + shape.len() - 1];
+    bars.extend(shape.into_iter().map(|bar| bar.map(|v| v + offset)));
+
+    let base = r(rng, 50.0, 500.0);
+    let unit = base * r(rng, 0.004, 0.012);
+    let volume = r(rng, 1e5, 1e6);
+    let start = 1_700_006_400 + rng.gen_range(0..1000) * 86_400;
+    let round = |v: f64| (v * 10_000.0).round() / 10_000.0;
+
+    let candles = bars
+        .iter()
+        .enumerate()
+        .map(|(i, [o, h, l, cl])| {
+            let boost = if i >= range[0] { r(rng, 0.9, 1.8) } else { 1.0 };
+            Candle {
+                time: Some(start + i as i64 * 86_400),
+                open: round(base + o * unit),
+                high: round(base + h * unit),
+                low: round(base + l * unit),
+                close: round(base + cl * unit),
+                volume: Some((volume * r(rng, 0.6, 1.4) * boost).round()),
+            }
+        })
+        .collect();
+    Some((candles, range))
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = parse_args(args)?;
+    let mut patterns = taxonomy::load_patterns(taxonomy::csv_path(&args.taxonomy))?;
+    if let Some(wanted) = &args.patterns {
+        for name in wanted {
+            if !patterns.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+                return Err(format!("Pattern {} is not in taxonomy {}", name, args.taxonomy));
+            }
+        }
+        patterns.retain(|p| wanted.iter().any(|w| w.eq_ignore_ascii_case(&p.name)));
+    }
+
+    for dir in ["images", "ohlc"] {
+        std::fs::create_dir_all(args.out.join(dir))
+            .map_err(|e| format!("Failed to create {}: {}", args.out.display(), e))?;
+    }
+    let manifest_path = args.out.join("manifest.jsonl");
+    let mut manifest = std::fs::File::create(&manifest_path)
+        .map_err(|e| format!("Failed to create {}: {}", manifest_path.display(), e))?;
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let mut written = 0;
+    let mut skipped = Vec::new();
+
+    for pattern in &patterns {
+        for i in 0..args.count {
+            let Some((candles, range)) = sample(pattern, &mut rng, &args) else {
+                skipped.push(pattern.name.clone());
+                break;
+            };
+            let style = args.styles[rng.gen_range(0..args.styles.len())];
+            let (width, height) = args.sizes[rng.gen_range(0..args.sizes.len())];
+            let gridlines = args.grid.unwrap_or_else(|| rng.gen_bool(0.5));
+
+            let id = format!("{}-{:03}", slug(&pattern.name), i + 1);
+            let entry = ManifestEntry {
+                image: format!("images/{}.png", id),
+                ohlc: format!("ohlc/{}.csv", id),
+                pattern: pattern.name.clone(),
+                taxonomy: args.taxonomy.clone(),
+                style: style.name.to_string(),
+                width,
+                height,
+                pattern_range: range,
+                id,
+            };
+
+            let png = render::render_png(
+                &candles,
+                &RenderOptions {
+                    theme: style,
+                    width,
+                    height,
+                    gridlines,
+                    volume: false,
+                    highlights: &[],
+                },
+            )?;
+            std::fs::write(args.out.join(&entry.image), png)
+                .map_err(|e| format!("Failed to write {}: {}", entry.image, e))?;
+            candles::save_csv(&args.out.join(&entry.ohlc), &candles)?;
+
+            let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+            writeln!(manifest, "{}", line)
+                .map_err(|e| format!("Failed to write {}: {}", manifest_path.display(), e))?;
+            written += 1;
+        }
+    }
+
+    if !skipped.is_empty() {
+        warn!("No generator for {} patterns: {}", skipped.len(), skipped.join(", "));
+    }
+    info!(
+        "Wrote {} samples for {} patterns to {} (seed {})",
+        written,
+        patterns.len() - skipped.len(),
+        manifest_path.display(),
+        args.seed
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules;
+
+    /// Every candlestick template must be found by its own rule at exactly the
+    /// labelled range, so generated datasets and the rules never drift apart.
+    #[test]
+    fn rules_detect_every_candle_template() {
+        let patterns = taxonomy::load_patterns(taxonomy::csv_path(DEFAULT_TAXONOMY)).unwrap();
+        let mut args = parse_args(&[]).unwrap();
+        args.seed = 42;
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let mut misses = Vec::new();
+
+        for pattern in &patterns {
+            if candle_template(&pattern.name, &mut rng).is_none() {
+                continue;
+            }
+            assert!(rules::defined(&pattern.name), "{} has a template but no rule", pattern.name);
+            for n in 0..10 {
+                let (candles, [start, end]) = sample(pattern, &mut rng, &args).unwrap();
+                let found = rules::scan(&candles)
+                    .iter()
+                    .any(|d| d.pattern == pattern.name && d.start == start && d.end == end);
+                if !found {
+                    misses.push(format!("{} #{} at {}-{}", pattern.name, n, start, end));
+                }
+            }
+        }
+        assert!(misses.is_empty(), "not detected: {:#?}", misses);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config;
+use crate::models::{Direction, Pattern, PatternCategory};
+use crate::taxonomy_history;
+
+pub const DEFAULT_TAXONOMY: &str = "default";
+
+const CSV_HEADER: [&str; 4] = ["Pattern Name", "Category", "Direction", "How It Looks"];
+
+#[derive(Debug, Clone)]
+pub struct Taxonomy {
+    pub name: String,
+    pub patterns: Vec<Pattern>,
+    /// CSV file the taxonomy was loaded from and is persisted back to.
+    pub path: PathBuf,
+    /// Latest version recorded in the taxonomy history.
+    pub version: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaxonomySummary {
+    pub name: String,
+    pub pattern_count: usize,
+    pub version: u64,
+    pub is_default: bool,
+}
+
+impl Taxonomy {
+    pub fn summary(&self) -> TaxonomySummary {
+        TaxonomySummary {
+            name: self.name.clone(),
+            pattern_count: self.patterns.len(),
+            version: self.version,
+            is_default: self.name == DEFAULT_TAXONOMY,
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Pattern> {
+        self.patterns.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Loads the built-in taxonomy from `default_csv` plus every `*.csv` in `dir`,
+/// keyed by file stem (`taxonomies/high-reliability.csv` -> "high-reliability").
+pub fn load_taxonomies(
+    default_csv: &str,
+    dir: &str,
+    history_dir: &str,
+) -> BTreeMap<String, Arc<Taxonomy>> {
+    let mut sources = vec![(DEFAULT_TAXONOMY.to_string(), PathBuf::from(default_csv))];
+
+    match std::fs::read_dir(dir) {
+        Ok(entries) => {
+            let mut paths: Vec<_> = entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("csv"))
+                .collect();
+            paths.sort();
+            for path in paths {
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    sources.push((name.to_string(), path.clone()));
+                }
+            }
+        }
+        Err(e) => warn!("Taxonomy directory {} not readable: {}", dir, e),
+    }
+
+    let mut taxonomies = BTreeMap::new();
+
+    for (name, path) in sources {
+        if taxonomies.contains_key(&name) {
+            warn!("Skipping {}: taxonomy {:?} already loaded", path.display(), name);
+            continue;
+        }
+        let patterns = match load_patterns(&path) {
+            Ok(patterns) => patterns,
+            Err(e) if name == DEFAULT_TAXONOMY => panic!("Failed to load default taxonomy: {}", e),
+            Err(e) => {
+                warn!("Skipping taxonomy {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let version = taxonomy_history::sync_version(history_dir, &name, &patterns)
+            .unwrap_or_else(|e| {
+                warn!("Taxonomy history for {:?} unavailable: {}", name, e);
+                0
+            });
+        info!(
+            "Loaded taxonomy {:?} ({} patterns, v{})",
+            name,
+            patterns.len(),
+            version
+        );
+        taxonomies.insert(
+            name.clone(),
+            Arc::new(Taxonomy {
+                name,
+                patterns,
+                path,
+                version,
+            }),
+        );
+    }
+
+    taxonomies
+}
+
+/// CSV a taxonomy is read from without loading them all, for offline commands.
+pub fn csv_path(name: &str) -> PathBuf {
+    if name == DEFAULT_TAXONOMY {
+        PathBuf::from("candlestick_patterns.csv")
+    } else {
+        Path::new(&config::taxonomy_dir()).join(format!("{}.csv", name))
+    }
+}
+
+pub fn load_patterns(path: impl AsRef<Path>) -> Result<Vec<Pattern>, String> {
+    let path = path.as_ref();
+    let mut reader = csv::Reader::from_path(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut patterns = Vec::new();
+
+    for result in reader.records() {
+        let record = result.map_err(|e| format!("Failed to read CSV record: {}", e))?;
+        if record.len() >= 4 {
+            patterns.push(Pattern {
+                name: record[0].to_string(),
+                category: PatternCategory::parse_exact(&record[1])
+                    .map_err(|e| format!("Invalid category for {}: {}", &record[0], e))?,
+                direction: Direction::parse_exact(&record[2])
+                    .map_err(|e| format!("Invalid direction for {}: {}", &record[0], e))?,
+                description: record[3].to_string(),
+            });
+        }
+    }
+
+    if patterns.is_empty() {
+        return Err(format!("{} contains no patterns", path.display()));
+    }
+
+    Ok(patterns)
+}
+
+/// Writes patterns in the same four-column layout `load_patterns` reads. The
+/// file is written to a sibling temp file first so a crash never leaves a
+/// half-written taxonomy behind.
+pub fn save_patterns(path: impl AsRef<Path>, patterns: &[Pattern]) -> Result<(), String> {
+    let path = path.as_ref();
+    let tmp = path.with_extension("csv.tmp");
+
+    let mut writer = csv::WriterBuilder::new()
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_path(&tmp)
+        .map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+    writer
+        .write_record(CSV_HEADER)
+        .map_err(|e| format!("Failed to write CSV header: {}", e))?;
+    for p in patterns {
+        writer
+            .write_record([
+                p.name.as_str(),
+                p.category.as_str(),
+                p.direction.as_str(),
+                p.description.as_str(),
+            ])
+            .map_err(|e| format!("Failed to write CSV record: {}", e))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to flush {}: {}", tmp.display(), e))?;
+    drop(writer);
+
+    std::fs::rename(&tmp, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
@@ -0,0 +1,177 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::Pattern;
+use crate::taxonomy::{load_patterns, save_patterns};
+
+// Layout per taxonomy:
+//   <history_dir>/<name>/log.jsonl   one HistoryEntry per line
+//   <history_dir>/<name>/v<N>.csv    full snapshot of version N
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub version: u64,
+    pub timestamp: u64,
+    pub action: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatternChange {
+    pub name: String,
+    pub before: Pattern,
+    pub after: Pattern,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaxonomyDiff {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub added: Vec<Pattern>,
+    pub removed: Vec<Pattern>,
+    pub changed: Vec<PatternChange>,
+    /// True when the patterns present in both versions appear in a different order.
+    pub reordered: bool,
+}
+
+fn taxonomy_dir(history_dir: &str, name: &str) -> PathBuf {
+    PathBuf::from(history_dir).join(name)
+}
+
+fn snapshot_path(history_dir: &str, name: &str, version: u64) -> PathBuf {
+    taxonomy_dir(history_dir, name).join(format!("v{}.csv", version))
+}
+
+pub fn load_history(history_dir: &str, name: &str) -> Result<Vec<HistoryEntry>, String> {
+    let path = taxonomy_dir(history_dir, name).join("log.jsonl");
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            serde_json::from_str(l)
+                .map_err(|e| format!("Corrupt history entry in {}: {}", path.display(), e))
+        })
+        .collect()
+}
+
+pub fn load_version(history_dir: &str, name: &str, version: u64) -> Result<Vec<Pattern>, String> {
+    let path = snapshot_path(history_dir, name, version);
+    if !path.exists() {
+        return Err(format!("Taxonomy {} has no version {}", name, version));
+    }
+    load_patterns(path)
+}
+
+/// Snapshots `patterns` as `version` and appends the matching log entry.
+pub fn record(
+    history_dir: &str,
+    name: &str,
+    version: u64,
+    action: &str,
+    detail: &str,
+    patterns: &[Pattern],
+) -> Result<HistoryEntry, String> {
+    let dir = taxonomy_dir(history_dir, name);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    save_patterns(snapshot_path(history_dir, name, version), patterns)?;
+
+    let entry = HistoryEntry {
+        version,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        action: action.to_string(),
+        detail: detail.to_string(),
+    };
+
+    let log_path = dir.join("log.jsonl");
+    let mut log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(|e| format!("Failed to open {}: {}", log_path.display(), e))?;
+    let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    writeln!(log, "{}", line).map_err(|e| format!("Failed to append {}: {}", log_path.display(), e))?;
+
+    Ok(entry)
+}
+
+/// Returns the current version of a taxonomy loaded from disk, recording an
+/// initial snapshot on first run and a new version if the CSV was edited by
+/// hand since the last recorded one.
+pub fn sync_version(history_dir: &str, name: &str, patterns: &[Pattern]) -> Result<u64, String> {
+    let history = load_history(history_dir, name)?;
+
+    let Some(latest) = history.last() else {
+        record(history_dir, name, 1, "initial", "first load", patterns)?;
+        return Ok(1);
+    };
+
+    let unchanged = load_version(history_dir, name, latest.version)
+        .map(|snapshot| snapshot == patterns)
+        .unwrap_or(false);
+    if unchanged {
+        return Ok(latest.version);
+    }
+
+    let version = latest.version + 1;
+    record(history_dir, name, version, "external", "CSV changed on disk", patterns)?;
+    Ok(version)
+}
+
+pub fn diff(from_version: u64, old: &[Pattern], to_version: u64, new: &[Pattern]) -> TaxonomyDiff {
+    let find = |list: &[Pattern], name: &str| list.iter().find(|p| p.name == name).cloned();
+
+    let added = new
+        .iter()
+        .filter(|p| find(old, &p.name).is_none())
+        .cloned()
+        .collect();
+    let removed = old
+        .iter()
+        .filter(|p| find(new, &p.name).is_none())
+        .cloned()
+        .collect();
+    let changed = new
+        .iter()
+        .filter_map(|after| {
+            let before = find(old, &after.name)?;
+            (before != *after).then(|| PatternChange {
+                name: after.name.clone(),
+                before,
+                after: after.clone(),
+            })
+        })
+        .collect();
+
+    let common_old: Vec<&str> = old
+        .iter()
+        .filter(|p| find(new, &p.name).is_some())
+        .map(|p| p.name.as_str())
+        .collect();
+    let common_new: Vec<&str> = new
+        .iter()
+        .filter(|p| find(old, &p.name).is_some())
+        .map(|p| p.name.as_str())
+        .collect();
+
+    TaxonomyDiff {
+        from_version,
+        to_version,
+        added,
+        removed,
+        changed,
+        reordered: common_old != common_new,
+    }
+}
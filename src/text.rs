@@ -0,0 +1,7 @@
+/// The first `max_chars` characters of `text`, cut on a char boundary.
+pub fn truncate(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((i, _)) => &text[..i],
+        None => text,
+    }
+}
@@ -0,0 +1,541 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::candles::Candle;
+use crate::import::OhlcInput;
+use crate::models::Direction;
+use crate::rules;
+use crate::store::{append_all_jsonl, read_jsonl};
+use crate::{ApiError, AppState};
+
+// Layout:
+//   <data_dir>/symbols/<SYMBOL>.jsonl   one appended Candle per line; detection
+//                                       state is rebuilt by replaying them
+
+/// Candles kept per symbol for detection; enough for every rule plus its trend.
+const WINDOW: usize = 40;
+
+/// Patterns that need a follow-up candle: `(pattern, bullish, candles allowed)`.
+/// Hikkake goes either way, so its direction (`None`) comes from the candles.
+const FOLLOW_UP: &[(&str, Option<bool>, usize)] = &[
+    ("Hammer", Some(true), 1),
+    ("Inverted Hammer", Some(true), 1),
+    ("Dragonfly Doji", Some(true), 1),
+    ("Bullish Harami", Some(true), 1),
+    ("Tweezer Bottom", Some(true), 1),
+    ("Hanging Man", Some(false), 1),
+    ("Shooting Star", Some(false), 1),
+    ("Gravestone Doji", Some(false), 1),
+    ("Bearish Harami", Some(false), 1),
+    ("Tweezer Top", Some(false), 1),
+    ("Hikkake Pattern", None, 1),
+    ("Hikkake Modified", None, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pending,
+    Confirmed,
+    Invalidated,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusChange {
+    pub status: Status,
+    /// Symbol-wide candle index, 0 = first appended candle.
+    pub bar: usize,
+    pub time: Option<i64>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedDetection {
+    /// Sequence number within the symbol, stable across restarts.
+    pub id: usize,
+    pub pattern: &'static str,
+    pub direction: Direction,
+    pub start: usize,
+    pub end: usize,
+    pub status: Status,
+    /// Candles after `end` the pattern has to confirm within.
+    pub window: usize,
+    /// Close that confirms the pattern.
+    pub confirm_level: f64,
+    /// Close that invalidates it before the window runs out.
+    pub invalidate_level: f64,
+    pub history: Vec<StatusChange>,
+}
+
+impl TrackedDetection {
+    fn bullish(&self) -> bool {
+        self.direction == Direction::Bullish
+    }
+
+    /// Resolves a pending detection against the candle at `bar`, if it settles it.
+    fn observe(&mut self, bar: usize, candle: &Candle) -> Option<StatusChange> {
+        let (confirmed, invalidated) = if self.bullish() {
+            (candle.close > self.confirm_level, candle.close < self.invalidate_level)
+        } else {
+            (candle.close < self.confirm_level, candle.close > self.invalidate_level)
+        };
+        let side = if self.bullish() { "above" } else { "below" };
+        let opposite = if self.bullish() { "below" } else { "above" };
+
+        let (status, reason) = if confirmed {
+            (Status::Confirmed, format!("closed at {} {} {}", candle.close, side, self.confirm_level))
+        } else if invalidated {
+            (Status::Invalidated, format!("closed at {} {} {}", candle.close, opposite, self.invalidate_level))
+        } else if bar >= self.end + self.window {
+            (
+                Status::Invalidated,
+                format!("no close {} {} within {} candle(s)", side, self.confirm_level, self.window),
+            )
+        } else {
+            return None;
+        };
+
+        let change = StatusChange { status, bar, time: candle.time, reason };
+        self.status = status;
+        self.history.push(change.clone());
+        Some(change)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SymbolState {
+    bars: usize,
+    recent: Vec<Candle>,
+    detections: Vec<TrackedDetection>,
+}
+
+/// A status change together with the detection it belongs to.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transition {
+    pub id: usize,
+    pub pattern: &'static str,
+    #[serde(flatten)]
+    pub change: StatusChange,
+}
+
+impl SymbolState {
+    fn last_time(&self) -> Option<i64> {
+        self.recent.last().and_then(|c| c.time)
+    }
+
+    /// Settles pending detections with `candle` first, so a pattern never
+    /// confirms on its own last candle, then records newly completed ones.
+    fn push(&mut self, candle: Candle) -> Vec<Transition> {
+        let bar = self.bars;
+        self.bars += 1;
+        self.recent.push(candle);
+        if self.recent.len() > WINDOW {
+            self.recent.remove(0);
+        }
+
+        let mut transitions = Vec::new();
+        for detection in self.detections.iter_mut().filter(|d| d.status == Status::Pending) {
+            if let Some(change) = detection.observe(bar, &candle) {
+                transitions.push(Transition { id: detection.id, pattern: detection.pattern, change });
+            }
+        }
+
+        let offset = self.bars - self.recent.len();
+        let found: Vec<Setup> = rules::detect(&self.recent)
+            .into_iter()
+            .filter_map(|d| {
+                let &(_, bullish, window) = FOLLOW_UP.iter().find(|(p, _, _)| *p == d.pattern)?;
+                let candles = &self.recent[d.start..=d.end];
+                // A Hikkake reversal closes beyond the false break, so it is
+                // bullish when it closes above the breakout candle.
+                let bullish = bullish.unwrap_or_else(|| candle.close > candles[candles.len() - 2].close);
+                let (confirm_level, invalidate_level) = if bullish {
+                    (candle.body_top(), candles.iter().map(|c| c.low).fold(f64::INFINITY, f64::min))
+                } else {
+                    (candle.body_bottom(), candles.iter().map(|c| c.high).fold(f64::NEG_INFINITY, f64::max))
+                };
+                Some(Setup { pattern: d.pattern, bullish, window, start: d.start, confirm_level, invalidate_level })
+            })
+            .collect();
+
+        for setup in found {
+            let change = StatusChange {
+                status: Status::Pending,
+                bar,
+                time: candle.time,
+                reason: format!("awaiting confirmation within {} candle(s)", setup.window),
+            };
+            let id = self.detections.len();
+            transitions.push(Transition { id, pattern: setup.pattern, change: change.clone() });
+            self.detections.push(TrackedDetection {
+                id,
+                pattern: setup.pattern,
+                direction: if setup.bullish { Direction::Bullish } else { Direction::Bearish },
+                start: offset + setup.start,
+                end: bar,
+                status: Status::Pending,
+                window: setup.window,
+                confirm_level: setup.confirm_level,
+                invalidate_level: setup.invalidate_level,
+                history: vec![change],
+            });
+        }
+        transitions
+    }
+}
+
+/// A detection completing on the latest candle; `start` indexes the window.
+struct Setup {
+    pattern: &'static str,
+    bullish: bool,
+    window: usize,
+    start: usize,
+    confirm_level: f64,
+    invalidate_level: f64,
+}
+
+pub struct Tracker {
+    dir: PathBuf,
+    symbols: BTreeMap<String, SymbolState>,
+}
+
+/// Upper-cased symbol, or an error for names unsafe as file names.
+pub fn normalize_symbol(symbol: &str) -> Result<String, String> {
+    let valid = (1..=32).contains(&symbol.len())
+        && !symbol.starts_with('.')
+        && symbol.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid {
+        return Err(format!(
+            "Invalid symbol '{}': use 1-32 letters, digits, '.', '_' or '-'",
+            symbol
+        ));
+    }
+    Ok(symbol.to_ascii_uppercase())
+}
+
+impl Tracker {
+    pub fn load(data_dir: &str) -> Result<Self, String> {
+        let dir = Path::new(data_dir).join("symbols");
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let mut symbols = BTreeMap::new();
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(symbol) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let mut state = SymbolState::default();
+            for candle in read_jsonl::<Candle>(&path)? {
+                state.push(candle);
+            }
+            symbols.insert(symbol.to_string(), state);
+        }
+
+        info!("Loaded {} tracked symbols", symbols.len());
+        Ok(Tracker { dir, symbols })
+    }
+
+    /// Rejects candles that do not continue `symbol`'s series in time.
+    pub fn check_order(&self, symbol: &str, candles: &[Candle]) -> Result<(), String> {
+        let mut last_time = self.symbols.get(symbol).and_then(SymbolState::last_time);
+        for (i, candle) in candles.iter().enumerate() {
+            if let (Some(previous), Some(time)) = (last_time, candle.time) {
+                if time <= previous {
+                    return Err(format!(
+                        "Candle {} at {} is not after the previous candle at {}",
+                        i, time, previous
+                    ));
+                }
+            }
+            last_time = candle.time.or(last_time);
+        }
+        Ok(())
+    }
+
+    /// Persists `candles` (already checked with [`Tracker::check_order`]) as
+    /// one batch, then replays them and returns every status change. Nothing
+    /// is replayed if the write fails.
+    pub fn append(&mut self, symbol: &str, candles: &[Candle]) -> Result<Vec<Transition>, String> {
+        append_all_jsonl(&self.dir.join(format!("{}.jsonl", symbol)), candles)?;
+        let state = self.symbols.entry(symbol.to_string()).or_default();
+        Ok(candles.iter().flat_map(|candle| state.push(*candle)).collect())
+    }
+}
+
+#[derive(Serialize)]
+struct AppendResponse {
+    symbol: String,
+    appended: usize,
+    bars: usize,
+    changes: Vec<Transition>,
+    pending: Vec<TrackedDetection>,
+}
+
+async fn append_handler(
+    State(state): State<Arc<AppState>>,
+    UrlPath(symbol): UrlPath<String>,
+    Json(ohlc): Json<OhlcInput>,
+) -> Result<(StatusCode, Json<AppendResponse>), ApiError> {
+    let bad_request = |e: String| (StatusCode::BAD_REQUEST, e);
+    let symbol = normalize_symbol(&symbol).map_err(bad_request)?;
+    let candles = ohlc.into_candles().map_err(bad_request)?;
+
+    let mut tracker = state.tracker.write().await;
+    tracker.check_order(&symbol, &candles).map_err(bad_request)?;
+    let changes = tracker
+        .append(&symbol, &candles)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let symbol_state = &tracker.symbols[&symbol];
+
+    info!("Appended {} candles to {} ({} status changes)", candles.len(), symbol, changes.len());
+
+    Ok((
+        StatusCode::CREATED,
+        Json(AppendResponse {
+            bars: symbol_state.bars,
+            pending: symbol_state
+                .detections
+                .iter()
+                .filter(|d| d.status == Status::Pending)
+                .cloned()
+                .collect(),
+            symbol,
+            appended: candles.len(),
+            changes,
+        }),
+    ))
+}
+
+#[derive(Serialize)]
+struct SymbolSummary {
+    symbol: String,
+    bars: usize,
+    last_time: Option<i64>,
+    pending: usize,
+    confirmed: usize,
+    invalidated: usize,
+}
+
+async fn list_symbols(State(state): State<Arc<AppState>>) -> Json<Vec<SymbolSummary>> {
+    let tracker = state.tracker.read().await;
+    let count = |s: &SymbolState, status| s.detections.iter().filter(|d| d.status == status).count();
+    Json(
+        tracker
+            .symbols
+            .iter()
+            .map(|(symbol, s)| SymbolSummary {
+                symbol: symbol.clone(),
+                bars: s.bars,
+                last_time: s.last_time(),
+                pending: count(s, Status::Pending),
+                confirmed: count(s, Status::Confirmed),
+                invalidated: count(s, Status::Invalidated),
+            })
+            .collect(),
+    )
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    status: Option<Status>,
+    pattern: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SymbolHistory {
+    symbol: String,
+    bars: usize,
+    last_time: Option<i64>,
+    /// Newest first.
+    detections: Vec<TrackedDetection>,
+}
+
+async fn symbol_history(
+    State(state): State<Arc<AppState>>,
+    UrlPath(symbol): UrlPath<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<SymbolHistory>, ApiError> {
+    let symbol = normalize_symbol(&symbol).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let tracker = state.tracker.read().await;
+    let s = tracker
+        .symbols
+        .get(&symbol)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Symbol '{}' not found", symbol)))?;
+
+    let detections = s
+        .detections
+        .iter()
+        .rev()
+        .filter(|d| query.status.is_none_or(|status| d.status == status))
+        .filter(|d| query.pattern.as_ref().is_none_or(|p| d.pattern.eq_ignore_ascii_case(p)))
+        .cloned()
+        .collect();
+
+    Ok(Json(SymbolHistory { symbol, bars: s.bars, last_time: s.last_time(), detections }))
+}
+
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/symbols", get(list_symbols))
+        .route("/symbols/{symbol}", get(symbol_history))
+        .route("/symbols/{symbol}/candles", post(append_handler))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(open: f64, high: f64, low: f64, close: f64) -> Candle {
+        Candle { time: None, open, high, low, close, volume: None }
+    }
+
+    /// Six rising candles closing at 105.8.
+    fn uptrend() -> Vec<Candle> {
+        (0..6)
+            .map(|i| {
+                let base = 100.0 + i as f64;
+                candle(base, base + 1.0, base - 0.2, base + 0.8)
+            })
+            .collect()
+    }
+
+    fn replay(candles: &[Candle]) -> (SymbolState, Vec<Transition>) {
+        let mut state = SymbolState::default();
+        let transitions = candles.iter().flat_map(|c| state.push(*c)).collect();
+        (state, transitions)
+    }
+
+    fn tracked<'a>(state: &'a SymbolState, pattern: &str) -> &'a TrackedDetection {
+        state
+            .detections
+            .iter()
+            .find(|d| d.pattern == pattern)
+            .unwrap_or_else(|| panic!("{} not tracked", pattern))
+    }
+
+    const HANGING_MAN: (f64, f64, f64, f64) = (106.0, 106.22, 105.0, 106.2);
+
+    #[test]
+    fn hanging_man_confirms_on_a_lower_close() {
+        let mut candles = uptrend();
+        let (o, h, l, c) = HANGING_MAN;
+        candles.push(candle(o, h, l, c));
+        let (mut state, transitions) = replay(&candles);
+        let detection = tracked(&state, "Hanging Man");
+        assert_eq!(detection.status, Status::Pending);
+        assert_eq!(detection.direction, Direction::Bearish);
+        assert_eq!((detection.end, detection.confirm_level, detection.invalidate_level), (6, 106.0, 106.22));
+        assert!(transitions.iter().any(|t| t.pattern == "Hanging Man" && t.change.status == Status::Pending));
+
+        let id = detection.id;
+        let changes = state.push(candle(106.0, 106.1, 105.4, 105.5));
+        assert!(changes.iter().any(|t| t.id == id && t.change.status == Status::Confirmed && t.change.bar == 7));
+        assert_eq!(state.detections[id].status, Status::Confirmed);
+        assert_eq!(state.detections[id].history.len(), 2);
+    }
+
+    #[test]
+    fn shooting_star_invalidates_above_its_high() {
+        let mut candles = uptrend();
+        candles.push(candle(106.0, 107.2, 105.78, 105.8));
+        let (mut state, _) = replay(&candles);
+        let id = tracked(&state, "Shooting Star").id;
+
+        state.push(candle(106.0, 107.6, 105.9, 107.5));
+        let detection = &state.detections[id];
+        assert_eq!(detection.status, Status::Invalidated);
+        assert!(detection.history[1].reason.contains("above 107.2"), "{}", detection.history[1].reason);
+    }
+
+    #[test]
+    fn pending_detection_expires_after_its_window() {
+        let mut candles = uptrend();
+        let (o, h, l, c) = HANGING_MAN;
+        candles.push(candle(o, h, l, c));
+        let (mut state, _) = replay(&candles);
+        let id = tracked(&state, "Hanging Man").id;
+
+        // Between the confirm and invalidate levels
+        state.push(candle(106.05, 106.15, 106.0, 106.1));
+        let detection = &state.detections[id];
+        assert_eq!(detection.status, Status::Invalidated);
+        assert!(detection.history[1].reason.starts_with("no close below"), "{}", detection.history[1].reason);
+    }
+
+    #[test]
+    fn hikkake_comes_from_the_rules() {
+        let candles = [
+            candle(100.0, 104.0, 96.0, 102.0),
+            candle(101.0, 103.0, 98.0, 100.0),
+            candle(99.5, 101.0, 97.0, 97.5),
+            candle(98.0, 104.0, 97.8, 103.5),
+        ];
+        assert!(rules::detect(&candles).iter().any(|d| d.pattern == "Hikkake Pattern"));
+        let (mut state, _) = replay(&candles);
+        let detection = tracked(&state, "Hikkake Pattern");
+        assert_eq!((detection.start, detection.end), (0, 3));
+        assert_eq!(detection.direction, Direction::Bullish);
+        assert_eq!((detection.confirm_level, detection.invalidate_level), (103.5, 96.0));
+
+        let id = detection.id;
+        state.push(candle(103.5, 105.0, 103.0, 104.5));
+        assert_eq!(state.detections[id].status, Status::Confirmed);
+    }
+
+    #[test]
+    fn load_replays_appended_candles() {
+        let dir = std::env::temp_dir().join(format!("tracker-test-{}", std::process::id()));
+        let data_dir = dir.to_str().unwrap();
+        let mut candles = uptrend();
+        let (o, h, l, c) = HANGING_MAN;
+        candles.push(candle(o, h, l, c));
+        candles.push(candle(106.0, 106.1, 105.4, 105.5));
+        for (i, candle) in candles.iter_mut().enumerate() {
+            candle.time = Some(1_700_000_000 + 60 * i as i64);
+        }
+
+        let mut tracker = Tracker::load(data_dir).unwrap();
+        let (first, second) = candles.split_at(4);
+        tracker.append("TEST", first).unwrap();
+        tracker.append("TEST", second).unwrap();
+        let reloaded = Tracker::load(data_dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let summary = |t: &Tracker| -> Vec<(&'static str, usize, Status)> {
+            t.symbols["TEST"].detections.iter().map(|d| (d.pattern, d.end, d.status)).collect()
+        };
+        assert_eq!(reloaded.symbols["TEST"].bars, candles.len());
+        assert_eq!(summary(&reloaded), summary(&tracker));
+        assert!(summary(&reloaded).contains(&("Hanging Man", 6, Status::Confirmed)));
+    }
+
+    #[test]
+    fn check_order_rejects_candles_not_after_the_series() {
+        let at = |time| Candle { time: Some(time), ..candle(1.0, 2.0, 0.5, 1.5) };
+        let mut tracker = Tracker { dir: PathBuf::new(), symbols: BTreeMap::new() };
+        tracker.symbols.entry("TEST".to_string()).or_default().push(at(100));
+
+        assert!(tracker.check_order("TEST", &[at(160), at(220)]).is_ok());
+        assert!(tracker.check_order("TEST", &[at(100)]).is_err());
+        assert!(tracker.check_order("TEST", &[at(160), at(160)]).is_err());
+        // Untimed candles are taken in the order sent
+        assert!(tracker.check_order("TEST", &[candle(1.0, 2.0, 0.5, 1.5), at(160)]).is_ok());
+        assert!(tracker.check_order("OTHER", &[at(1), at(2)]).is_ok());
+    }
+}
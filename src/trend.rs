@@ -0,0 +1,113 @@
+use serde::Serialize;
+
+use crate::candles::Candle;
+use crate::prefilter::{ChartFeatures, Trend};
+
+/// The prior trend is measured over at most this many candles.
+pub const CONTEXT_CANDLES: usize = 10;
+/// Regression slope, in average candle ranges per candle, that counts as trending.
+const MIN_SLOPE: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendSource {
+    /// Measured from OHLC submitted with the request.
+    Ohlc,
+    /// Measured from candles extracted from the image.
+    Extracted,
+    /// Read from the vision model's description.
+    Description,
+}
+
+/// The trend leading into the most recent candles.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendContext {
+    pub trend: Trend,
+    pub source: TrendSource,
+    /// Candles the trend was measured over; absent for descriptions.
+    pub candles: Option<usize>,
+    /// Least-squares slope of the closes in average candle ranges per candle.
+    pub slope: Option<f64>,
+}
+
+impl TrendContext {
+    /// Measured from price data rather than taken from a description.
+    pub fn measured(&self) -> bool {
+        self.source != TrendSource::Description
+    }
+
+    /// Line for the reasoner prompt's `{{trend_context}}` placeholder.
+    pub fn prompt_line(&self) -> String {
+        let trend = match self.trend {
+            Trend::Up => "an uptrend",
+            Trend::Down => "a downtrend",
+            Trend::Sideways => "a sideways range",
+        };
+        match self.source {
+            TrendSource::Description => format!(
+                "PRIOR TREND: the chart description suggests {} before the most recent candle.\n",
+                trend
+            ),
+            _ => format!(
+                "PRIOR TREND (measured from price data, reliable): the candles before the most recent \
+                 one form {}. Patterns whose definition requires a different prior trend do not apply.\n",
+                trend
+            ),
+        }
+    }
+}
+
+/// Least-squares slope of the closes, normalized by the average candle range.
+fn slope(candles: &[Candle]) -> f64 {
+    let n = candles.len() as f64;
+    let mean_x = (n - 1.0) / 2.0;
+    let mean_close = candles.iter().map(|c| c.close).sum::<f64>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (i, c) in candles.iter().enumerate() {
+        let dx = i as f64 - mean_x;
+        covariance += dx * (c.close - mean_close);
+        variance += dx * dx;
+    }
+    let average_range = candles.iter().map(Candle::range).sum::<f64>() / n;
+    covariance / variance.max(f64::EPSILON) / average_range.max(f64::EPSILON)
+}
+
+/// Trend of a series: the regression slope of its closes must be steep enough
+/// and the last close on the same side of their moving average. `None` below
+/// three candles.
+pub fn classify(candles: &[Candle]) -> Option<Trend> {
+    let last = candles.last()?;
+    if candles.len() < 3 {
+        return None;
+    }
+    let slope = slope(candles);
+    let average = candles.iter().map(|c| c.close).sum::<f64>() / candles.len() as f64;
+    Some(if slope > MIN_SLOPE && last.close > average {
+        Trend::Up
+    } else if slope < -MIN_SLOPE && last.close < average {
+        Trend::Down
+    } else {
+        Trend::Sideways
+    })
+}
+
+/// Trend over the candles leading into the last one.
+pub fn measure(series: &[Candle], source: TrendSource) -> Option<TrendContext> {
+    let end = series.len().checked_sub(1)?;
+    let context = &series[end.saturating_sub(CONTEXT_CANDLES)..end];
+    Some(TrendContext {
+        trend: classify(context)?,
+        source,
+        candles: Some(context.len()),
+        slope: Some((slope(context) * 1000.0).round() / 1000.0),
+    })
+}
+
+pub fn from_description(features: &ChartFeatures) -> Option<TrendContext> {
+    features.trend.map(|trend| TrendContext {
+        trend,
+        source: TrendSource::Description,
+        candles: None,
+        slope: None,
+    })
+}
@@ -9,18 +9,6 @@ const REPLICATE_UPLOAD_URL: &str = "https://api.replicate.com/v1/files";
 pub const VL2_VERSION: &str =
     "e5caf557dd9e5dcee46442e1315291ef1867f027991ede8ff95e304d4f734200";
 
-const VISION_PROMPT: &str = "\
-Describe this candlestick chart <image> in detail. Focus on:
-- Number of candles visible
-- Body colors (red/green) of each candle in order
-- Relative body sizes (large, medium, small, doji)
-- Wick/shadow lengths (long upper, long lower, short, none)
-- Gaps between candles (gap up, gap down, overlapping)
-- Overall trend direction before/during the pattern
-- Any notable features (engulfing, inside bars, identical highs/lows)
-
-Be precise and systematic. Describe each candle from left to right.";
-
 pub struct VisionResult {
     pub description: String,
     pub predict_seconds: f64,
@@ -82,14 +70,16 @@ pub async fn describe_chart(
     replicate_token: &str,
     image_bytes: &[u8],
     content_type: &str,
+    prompt: &str,
+    model_version: &str,
 ) -> Result<VisionResult, String> {
     let image_url = upload_image(client, replicate_token, image_bytes, content_type).await?;
 
     let request = ReplicateRequest {
-        version: VL2_VERSION.to_string(),
+        version: model_version.to_string(),
         input: ReplicateInput {
             image: image_url,
-            prompt: VISION_PROMPT.to_string(),
+            prompt: prompt.to_string(),
             temperature: 0.1,
             top_p: 0.9,
             max_length_tokens: 2048,
@@ -0,0 +1,193 @@
+use serde::Serialize;
+
+use crate::candles::Candle;
+use crate::models::Confidence;
+
+/// Patterns traders only trust on expanding volume: engulfing reversals,
+/// gaps and breakouts out of a formation. Matched as name substrings.
+const VOLUME_PATTERNS: &[&str] = &[
+    "Engulfing",
+    "Three Outside",
+    "Kicker",
+    "Breakaway",
+    "Window",
+    "Three Methods",
+    "Three-Line Strike",
+    "Head and Shoulders",
+    "Double",
+    "Triple",
+    "Triangle",
+    "Flag",
+    "Pennant",
+    "Wedge",
+    "Cup and Handle",
+    "Rectangle",
+];
+
+const HIGH_VOLUME: &[&str] = &[
+    "high volume",
+    "heavy volume",
+    "strong volume",
+    "volume spike",
+    "spike in volume",
+    "volume surge",
+    "surge in volume",
+    "above-average volume",
+    "above average volume",
+    "increasing volume",
+    "rising volume",
+];
+
+const LOW_VOLUME: &[&str] = &[
+    "low volume",
+    "light volume",
+    "weak volume",
+    "thin volume",
+    "below-average volume",
+    "below average volume",
+    "declining volume",
+    "decreasing volume",
+    "falling volume",
+];
+
+/// Fewer candles before the pattern than this give no usable average.
+const MIN_AVERAGE_CANDLES: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct VolumeConfig {
+    /// Candles before the pattern whose volume is averaged.
+    pub lookback: usize,
+    /// Relative volume at or above which the pattern counts as confirmed.
+    pub confirm_ratio: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeSource {
+    Ohlc,
+    /// Read from the vision model's description of a volume pane.
+    Description,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeConfirmation {
+    pub source: VolumeSource,
+    /// Volume of the pattern's last candle over the average before the pattern.
+    pub relative_volume: Option<f64>,
+    /// Candles that average was taken over.
+    pub average_over: Option<usize>,
+    /// Whether the pattern is one that needs volume confirmation.
+    pub required: bool,
+    pub confirmed: bool,
+    /// Confidence before the volume adjustment.
+    pub original_confidence: Confidence,
+}
+
+pub fn requires_volume(pattern: &str) -> bool {
+    VOLUME_PATTERNS.iter().any(|p| pattern.contains(p))
+}
+
+/// Volume of the pattern's last candle, at index `end`, over the mean of up to
+/// `lookback` candles before the pattern's first. `None` without volume on all of them.
+pub fn relative_volume(series: &[Candle], end: usize, pattern_candles: usize, lookback: usize) -> Option<(f64, usize)> {
+    let last = series.get(end)?.volume?;
+    let start = (end + 1).checked_sub(pattern_candles.max(1))?;
+    let before = &series[start.saturating_sub(lookback)..start];
+    if before.len() < MIN_AVERAGE_CANDLES {
+        return None;
+    }
+    let volumes = before.iter().map(|c| c.volume).collect::<Option<Vec<f64>>>()?;
+    let average = volumes.iter().sum::<f64>() / volumes.len() as f64;
+    (average > 0.0).then(|| ((last / average * 100.0).round() / 100.0, before.len()))
+}
+
+/// Words that turn a volume remark around ("no volume spike", "not above average").
+const NEGATIONS: &[&str] = &["no", "not", "without", "never", "neither", "nor", "lacks", "lacking"];
+
+/// Whether one clause calls volume high (`Some(true)`) or low. Besides the
+/// keyword lists this takes the vision prompt's own wording, volume "above"
+/// or "below" its (recent) average. Negated clauses say neither.
+fn describes_volume(clause: &str) -> Option<bool> {
+    if !clause.contains("volume") {
+        return None;
+    }
+    let negated = clause
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .any(|word| NEGATIONS.contains(&word) || word.ends_with("n't"));
+    if negated {
+        return None;
+    }
+    let average = clause.contains("average");
+    let high = HIGH_VOLUME.iter().any(|k| clause.contains(k)) || (average && clause.contains("above"));
+    let low = LOW_VOLUME.iter().any(|k| clause.contains(k)) || (average && clause.contains("below"));
+    match (high, low) {
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// `Some(true)` when the description calls the latest volume high, `Some(false)`
+/// when low; `None` when it says neither or contradicts itself.
+pub fn from_description(description: &str) -> Option<bool> {
+    let text = description.to_lowercase().replace('\u{2019}', "'");
+    let mut verdicts = text
+        .split(['.', ';', ',', '\n'])
+        .flat_map(|sentence| sentence.split(" but "))
+        .filter_map(describes_volume);
+    let first = verdicts.next()?;
+    verdicts.all(|v| v == first).then_some(first)
+}
+
+/// Confidence after volume: patterns that need it gain a level on confirming
+/// volume and lose one on below-average volume; others are left alone.
+pub fn adjust(confidence: Confidence, required: bool, confirmed: bool, below_average: bool) -> Confidence {
+    match (required, confirmed, below_average) {
+        (true, true, _) => confidence.upgrade(),
+        (true, false, true) => confidence.downgrade(),
+        _ => confidence,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_prompts_own_wording() {
+        assert_eq!(from_description("The latest candles' volume is above its recent average."), Some(true));
+        assert_eq!(from_description("Volume on the last two candles is below its recent average"), Some(false));
+        assert_eq!(from_description("Candle 5 is a large green candle on a volume spike."), Some(true));
+        assert_eq!(from_description("Thin volume throughout; the last candle is a doji."), Some(false));
+    }
+
+    #[test]
+    fn negated_remarks_say_nothing() {
+        assert_eq!(from_description("There is no volume spike on the breakout."), None);
+        assert_eq!(from_description("Volume isn\u{2019}t above its recent average."), None);
+        assert_eq!(from_description("Volume is neither high nor low."), None);
+        assert_eq!(from_description("No volume surge. The latest volume is below average."), Some(false));
+    }
+
+    #[test]
+    fn contradictions_and_silence_give_none() {
+        assert_eq!(from_description("Heavy volume early on, but the latest volume is below its recent average."), None);
+        assert_eq!(from_description("Three green candles in an uptrend."), None);
+        assert_eq!(from_description("Price is above its 20-period average."), None);
+    }
+
+    #[test]
+    fn relative_volume_compares_the_pattern_end_with_the_average_before_it() {
+        let series: Vec<Candle> = [100.0, 100.0, 100.0, 100.0, 50.0, 250.0]
+            .iter()
+            .map(|&v| Candle { time: None, open: 1.0, high: 2.0, low: 0.5, close: 1.5, volume: Some(v) })
+            .collect();
+        // Two-candle pattern: the average is over the four candles before it
+        assert_eq!(relative_volume(&series, 5, 2, 20), Some((2.5, 4)));
+        assert_eq!(relative_volume(&series, 5, 1, 3), Some((3.0, 3)));
+        assert_eq!(relative_volume(&series, 5, 4, 20), None);
+        // A pattern ending earlier is measured on its own last candle
+        assert_eq!(relative_volume(&series, 4, 1, 20), Some((0.5, 4)));
+        assert_eq!(relative_volume(&series, 6, 1, 20), None);
+    }
+}